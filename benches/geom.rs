@@ -0,0 +1,124 @@
+// Benchmarks for the geometry the router leans on: the shape tests placement
+// and DRC go through, and the lower level predicates in |memeroute::geom|.
+// Inputs are sized like real boards, e.g. 0.2 mm traces and 1-2 mm pads.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use memegeom::primitive::path_shape::Path;
+use memegeom::primitive::{circ, path, poly, pt, rt, ShapeOps};
+use memeroute::geom::distance::{
+    cap_poly_dist, line_pt_dist, path_poly_dist, pt_seg_dist, seg_seg_dist, shape_dist,
+};
+use memeroute::geom::intersects::{
+    circ_intersects_line, line_intersects_line, shape_intersects_shape,
+};
+use memeroute::geom::{path_caps, Cap, Line, Seg};
+
+fn shapes(c: &mut Criterion) {
+    let trace = path(&[pt(0.0, 0.0), pt(5.0, 0.0), pt(5.0, 5.0)], 0.1).shape();
+    let crossing = path(&[pt(2.5, -2.0), pt(2.5, 2.0)], 0.1).shape();
+    let pad = rt(4.0, 2.0, 6.0, 3.0).shape();
+    let round = circ(pt(3.0, 1.0), 0.6).shape();
+    let tri = poly(&[pt(1.0, 1.0), pt(4.0, 1.5), pt(2.0, 4.0)]).shape();
+    let outline = poly(&[pt(0.0, 0.0), pt(4.0, 0.0), pt(4.0, 4.0), pt(2.0, 2.0), pt(0.0, 4.0)]);
+    let outline = outline.shape();
+
+    let mut g = c.benchmark_group("intersects");
+    g.bench_function("path_path", |b| {
+        b.iter(|| shape_intersects_shape(black_box(&trace), black_box(&crossing)));
+    });
+    g.bench_function("rt_tri", |b| {
+        b.iter(|| shape_intersects_shape(black_box(&pad), black_box(&tri)));
+    });
+    g.bench_function("poly_rt", |b| {
+        b.iter(|| shape_intersects_shape(black_box(&outline), black_box(&pad)));
+    });
+    g.bench_function("circ_path", |b| {
+        b.iter(|| shape_intersects_shape(black_box(&round), black_box(&trace)));
+    });
+    g.finish();
+
+    let mut g = c.benchmark_group("dist");
+    g.bench_function("path_path", |b| {
+        b.iter(|| shape_dist(black_box(&trace), black_box(&crossing)));
+    });
+    g.bench_function("path_poly", |b| {
+        b.iter(|| shape_dist(black_box(&trace), black_box(&outline)));
+    });
+    g.bench_function("circ_rt", |b| b.iter(|| shape_dist(black_box(&round), black_box(&pad))));
+    g.finish();
+}
+
+// Every trace on a board against its outline, as clearance checks against a
+// keepout or board edge do. Traces are three segment paths spread over a
+// 100 mm board with a notched outline.
+fn poly_dist(c: &mut Criterion) {
+    let outline = [
+        pt(0.0, 0.0),
+        pt(100.0, 0.0),
+        pt(100.0, 80.0),
+        pt(60.0, 80.0),
+        pt(60.0, 60.0),
+        pt(40.0, 60.0),
+        pt(40.0, 80.0),
+        pt(0.0, 80.0),
+    ];
+    let traces = |n: usize| -> Vec<Path> {
+        (0..n)
+            .map(|i| {
+                let x = (i * 37 % 97) as f64 + 1.0;
+                let y = (i * 53 % 77) as f64 + 1.0;
+                path(&[pt(x, y), pt(x + 2.0, y), pt(x + 3.0, y + 1.0), pt(x + 3.0, y + 2.5)], 0.1)
+            })
+            .collect()
+    };
+
+    let mut g = c.benchmark_group("cap_poly_dist");
+    for n in [10, 100, 1000] {
+        let caps: Vec<Cap> =
+            traces(n).iter().flat_map(|p| path_caps(p).collect::<Vec<_>>()).collect();
+        g.bench_with_input(BenchmarkId::from_parameter(n), &caps, |b, caps| {
+            b.iter(|| caps.iter().map(|cap| cap_poly_dist(cap, black_box(&outline))).sum::<f64>());
+        });
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("path_poly_dist");
+    for n in [10, 100, 1000] {
+        g.bench_with_input(BenchmarkId::from_parameter(n), &traces(n), |b, paths| {
+            b.iter(|| paths.iter().map(|p| path_poly_dist(black_box(&outline), p)).sum::<f64>());
+        });
+    }
+    g.finish();
+}
+
+fn local(c: &mut Criterion) {
+    let a = Line::new(pt(0.0, 0.0), pt(5.0, 1.0));
+    let l = Line::new(pt(0.0, 3.0), pt(5.0, 2.0));
+    let s = Seg::new(pt(0.0, 0.0), pt(5.0, 1.0));
+    let cc = circ(pt(2.0, 2.0), 0.6);
+
+    let mut g = c.benchmark_group("geom");
+    g.bench_function("line_intersects_line", |b| {
+        b.iter(|| line_intersects_line(black_box(&a), black_box(&l)));
+    });
+    g.bench_function("circ_intersects_line", |b| {
+        b.iter(|| circ_intersects_line(black_box(&cc), black_box(&l)));
+    });
+    g.bench_function("line_pt_dist", |b| b.iter(|| line_pt_dist(black_box(&a), pt(2.0, 2.0))));
+    g.bench_function("pt_seg_dist", |b| b.iter(|| pt_seg_dist(pt(7.0, 2.0), black_box(&s))));
+    g.finish();
+
+    // Far apart segments take the bounds early-out, the others don't.
+    let far = Seg::new(pt(8.0, 3.0), pt(12.0, 4.0));
+    let near = Seg::new(pt(1.0, 0.5), pt(4.0, 3.0));
+    let crossing = Seg::new(pt(2.0, -1.0), pt(3.0, 2.0));
+    let mut g = c.benchmark_group("seg_seg_dist");
+    g.bench_function("far", |b| b.iter(|| seg_seg_dist(black_box(&s), black_box(&far))));
+    g.bench_function("near", |b| b.iter(|| seg_seg_dist(black_box(&s), black_box(&near))));
+    g.bench_function("crossing", |b| {
+        b.iter(|| seg_seg_dist(black_box(&s), black_box(&crossing)));
+    });
+    g.finish();
+}
+
+criterion_group!(benches, shapes, poly_dist, local);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+// Benchmarks for the parts of routing that scale with board size rather than
+// with the search, e.g. building the ratsnest of a net with many pins.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use memegeom::primitive::pt;
+use memeroute::route::ratsnest::mst;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Spanning trees of nets with pins spread over a 100 mm board, up to the size
+// of a large ground net.
+fn ratsnest(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut g = c.benchmark_group("mst");
+    for n in [10, 100, 1000] {
+        let pts: Vec<_> =
+            (0..n).map(|_| pt(rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0))).collect();
+        g.bench_with_input(BenchmarkId::from_parameter(n), &pts, |b, pts| {
+            b.iter(|| mst(black_box(pts)));
+        });
+    }
+    g.finish();
+}
+
+criterion_group!(benches, ratsnest);
+criterion_main!(benches);
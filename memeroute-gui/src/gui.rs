@@ -1,11 +1,17 @@
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::Instant;
 
 use eframe::egui;
 use eframe::egui::Widget;
 use memeroute::dsn::pcb_to_session::PcbToSession;
-use memeroute::model::pcb::Pcb;
-use memeroute::route::router::{apply_route_result, Router};
+use memeroute::error::MemerouteError;
+use memeroute::headless::load_pcb;
+use memeroute::model::pcb::{LayerId, Pcb};
+use memeroute::route::ratsnest::open_ratsnest;
+use memeroute::route::router::{apply_route_result, Progress, RouteResult, Router};
 use serde::{Deserialize, Serialize};
 
 use crate::pcb::pcb_view::PcbView;
@@ -14,12 +20,53 @@ use crate::pcb::pcb_view::PcbView;
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(default)]
 struct State {
-    filename: String,
+    filename: String, // Most recently opened board.
+    hidden_layers: Vec<LayerId>,
+    layer_opacity: f32,
+    show_ratsnest: bool,
 }
 
 impl Default for State {
     fn default() -> Self {
-        Self { filename: "data/left.dsn".to_string() }
+        Self {
+            filename: "data/left.dsn".to_string(),
+            hidden_layers: Vec::new(),
+            layer_opacity: 1.0,
+            show_ratsnest: false,
+        }
+    }
+}
+
+// A route running on a background thread.
+#[must_use]
+#[derive(Debug, Clone)]
+struct RouteJob {
+    cancel: Arc<AtomicBool>,
+    progress: Arc<Mutex<(usize, usize)>>, // Nets routed and total nets.
+    result: Arc<Mutex<Option<Result<RouteResult, MemerouteError>>>>,
+    start: Instant,
+}
+
+impl RouteJob {
+    fn start(pcb: Pcb) -> Self {
+        let job = Self {
+            cancel: Arc::new(AtomicBool::new(false)),
+            progress: Arc::new(Mutex::new((0, 0))),
+            result: Arc::new(Mutex::new(None)),
+            start: Instant::now(),
+        };
+        let mut router = Router::new(pcb);
+        router.set_cancel(Some(Arc::clone(&job.cancel)));
+        let progress = Arc::clone(&job.progress);
+        router.set_progress(Some(Progress(Arc::new(move |done, total| {
+            *progress.lock().unwrap() = (done, total);
+        }))));
+        let result = Arc::clone(&job.result);
+        thread::spawn(move || {
+            let res = router.route(router.rand_net_order());
+            *result.lock().unwrap() = Some(res);
+        });
+        job
     }
 }
 
@@ -30,6 +77,8 @@ pub struct MemerouteGui {
     pcb: Pcb,
     pcb_view: PcbView,
     data_path: PathBuf,
+    error: Option<String>, // Shown in a dialog until dismissed.
+    job: Option<RouteJob>,
 }
 
 impl MemerouteGui {
@@ -39,8 +88,33 @@ impl MemerouteGui {
         } else {
             State::default()
         };
-        let pcb_view = PcbView::new(pcb.clone(), pcb.bounds());
-        Self { s, pcb, pcb_view, data_path: data_path.as_ref().into() }
+        let mut pcb_view = PcbView::new(pcb.clone(), pcb.bounds());
+        pcb_view.set_layer_visibility(s.hidden_layers.clone(), s.layer_opacity);
+        let mut gui =
+            Self { s, pcb, pcb_view, data_path: data_path.as_ref().into(), error: None, job: None };
+        gui.update_ratsnest();
+        gui
+    }
+
+    // Recomputes the ratsnest overlay. Call whenever the PCB changes.
+    fn update_ratsnest(&mut self) {
+        let ratsnest = if self.s.show_ratsnest { open_ratsnest(&self.pcb) } else { Vec::new() };
+        self.pcb_view.set_ratsnest(ratsnest);
+    }
+
+    fn open(&mut self, path: PathBuf) {
+        match load_pcb(&path) {
+            Ok(pcb) => {
+                self.pcb_view = PcbView::new(pcb.clone(), pcb.bounds());
+                self.pcb_view
+                    .set_layer_visibility(self.s.hidden_layers.clone(), self.s.layer_opacity);
+                self.pcb = pcb;
+                self.s.filename = path.to_string_lossy().into_owned();
+                self.data_path = path;
+                self.update_ratsnest();
+            }
+            Err(e) => self.error = Some(format!("Failed to open {}: {e:?}", path.display())),
+        }
     }
 }
 
@@ -53,6 +127,17 @@ impl eframe::App for MemerouteGui {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("Open...").clicked() {
+                        ui.close_menu();
+                        let mut dialog =
+                            rfd::FileDialog::new().add_filter("Specctra DSN", &["dsn"]);
+                        if let Some(dir) = Path::new(&self.s.filename).parent() {
+                            dialog = dialog.set_directory(dir);
+                        }
+                        if let Some(path) = dialog.pick_file() {
+                            self.open(path);
+                        }
+                    }
                     if ui.button("Quit").clicked() {
                         frame.close();
                     }
@@ -60,32 +145,102 @@ impl eframe::App for MemerouteGui {
             });
         });
 
+        let mut dismissed = false;
+        if let Some(error) = &self.error {
+            egui::Window::new("Error").collapsible(false).resizable(false).show(ctx, |ui| {
+                ui.label(error);
+                dismissed = ui.button("OK").clicked();
+            });
+        }
+        if dismissed {
+            self.error = None;
+        }
+
         egui::SidePanel::left("side_panel").show(ctx, |ui| {
             ui.heading("Side Panel");
 
-            if ui.button("Route").clicked() {
-                let router = Router::new(self.pcb.clone());
-                let start = Instant::now();
-                let resp = router.route(router.rand_net_order()).unwrap();
-                // let resp = router.run_ga().unwrap();
-                println!(
-                    "Route result succeeded: {}, {} wires {} vias, time: {:?}",
-                    !resp.failed,
-                    resp.wires.len(),
-                    resp.vias.len(),
-                    Instant::now().duration_since(start)
-                );
-                apply_route_result(&mut self.pcb, &resp);
-
-                let output_path = self.data_path.with_extension("ses");
-                let ses = PcbToSession::new(self.pcb.clone()).convert().unwrap();
-                std::fs::write(output_path, ses).unwrap();
-
-                // Update pcb view.
-                self.pcb_view.set_pcb(self.pcb.clone());
+            ui.label("Layers");
+            for layer in self.pcb.layers() {
+                let mut shown = !self.s.hidden_layers.contains(&layer.layer_id);
+                if ui.checkbox(&mut shown, self.pcb.to_name(layer.name_id)).changed() {
+                    self.s.hidden_layers.retain(|&l| l != layer.layer_id);
+                    if !shown {
+                        self.s.hidden_layers.push(layer.layer_id);
+                    }
+                }
+            }
+            ui.add(egui::Slider::new(&mut self.s.layer_opacity, 0.0..=1.0).text("Opacity"));
+            self.pcb_view.set_layer_visibility(self.s.hidden_layers.clone(), self.s.layer_opacity);
+
+            if ui.checkbox(&mut self.s.show_ratsnest, "Show ratsnest").changed() {
+                self.update_ratsnest();
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Zoom to fit").on_hover_text("F").clicked() {
+                    self.pcb_view.zoom_to_fit();
+                }
+                if ui.button("Zoom to net").on_hover_text("S, after clicking a pad").clicked() {
+                    self.pcb_view.zoom_to_selection();
+                }
+            });
+
+            if ui.add_enabled(self.job.is_none(), egui::Button::new("Route")).clicked() {
+                self.job = Some(RouteJob::start(self.pcb.clone()));
+            }
+            if let Some(job) = &self.job {
+                let (done, total) = *job.progress.lock().unwrap();
+                let frac = if total == 0 { 0.0 } else { done as f32 / total as f32 };
+                ui.add(egui::ProgressBar::new(frac).text(format!("{done}/{total} nets")));
+                if ui.button("Cancel").clicked() {
+                    job.cancel.store(true, Ordering::Relaxed);
+                }
+                // Keep redrawing so progress updates without user input.
+                ctx.request_repaint();
             }
         });
 
+        let finished = self.job.as_ref().and_then(|job| job.result.lock().unwrap().take());
+        if let Some(res) = finished {
+            let start = self.job.take().unwrap().start;
+            match res {
+                // Partial results from a cancelled route are applied too.
+                Ok(mut resp) => {
+                    let stats = resp.stats();
+                    println!(
+                        "Route result succeeded: {}, cancelled: {}, {:.1}% of nets routed, \
+                         {} failed, {} vias, length {:.2} mm, time: {:?}",
+                        !resp.failed,
+                        resp.cancelled,
+                        stats.completion * 100.0,
+                        stats.failed_nets,
+                        stats.via_count,
+                        stats.layer_length.values().sum::<f64>(),
+                        Instant::now().duration_since(start)
+                    );
+                    resp.simplify();
+                    apply_route_result(&mut self.pcb, &resp);
+
+                    let output_path = self.data_path.with_extension("ses");
+                    match PcbToSession::new(self.pcb.clone()).convert() {
+                        Ok(ses) => {
+                            if let Err(e) = std::fs::write(&output_path, ses) {
+                                let path = output_path.display();
+                                self.error = Some(format!("Failed to write {path}: {e}"));
+                            }
+                        }
+                        Err(e) => self.error = Some(format!("Failed to save session: {e}")),
+                    }
+
+                    // Update pcb view.
+                    self.pcb_view.set_pcb(self.pcb.clone());
+                    self.pcb_view.set_drc(self.pcb.drc());
+                    self.update_ratsnest();
+                }
+                Err(e) => self.error = Some(format!("Routing failed: {e}")),
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.pcb_view.ui(ui);
         });
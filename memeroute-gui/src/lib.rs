@@ -39,15 +39,11 @@
 #![feature(array_windows, once_cell)]
 
 use std::fmt::Debug;
-use std::fs::read_to_string;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use clap::StructOpt;
 use eyre::Result;
-use memedsn::lexer::Lexer;
-use memedsn::parser::Parser;
-use memeroute::dsn::design_to_pcb::DesignToPcb;
-use memeroute::model::pcb::Pcb;
+use memeroute::headless::load_pcb;
 
 use crate::gui::MemerouteGui;
 
@@ -65,15 +61,6 @@ struct Args {
     data_path: PathBuf,
 }
 
-fn load_pcb<P: AsRef<Path>>(path: P) -> Result<Pcb> {
-    let data = read_to_string(path)?;
-    let lexer = Lexer::new(&data)?;
-    let parser = Parser::new(&lexer.lex()?);
-    let pcb = parser.parse()?;
-    let pcb = DesignToPcb::new(pcb).convert()?;
-    Ok(pcb)
-}
-
 pub fn run() -> Result<()> {
     let args = Args::parse();
     let pcb = load_pcb(&args.data_path)?;
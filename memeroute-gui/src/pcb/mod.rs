@@ -5,6 +5,7 @@ use memegeom::primitive::{pt, rt};
 
 pub mod pcb_view;
 pub mod primitives;
+pub mod view_transform;
 
 #[must_use]
 pub fn to_pos2(p: Pt) -> Pos2 {
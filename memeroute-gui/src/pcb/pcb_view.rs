@@ -1,17 +1,21 @@
 use std::sync::LazyLock;
 
 use eframe::egui::epaint::{Mesh, TessellationOptions, Tessellator};
-use eframe::egui::{epaint, Color32, Context, PointerButton, Response, Sense, Ui, Widget};
+use eframe::egui::{epaint, Color32, Context, Key, PointerButton, Response, Sense, Ui, Widget};
 use memegeom::primitive::point::Pt;
 use memegeom::primitive::rect::Rt;
 use memegeom::primitive::shape::Shape;
 use memegeom::primitive::{path, pt, ShapeOps};
 use memegeom::tf::Tf;
+use memeroute::geom::contains::pt_in_shape;
 use memeroute::model::pcb::{
-    Component, Keepout, LayerId, LayerSet, LayerShape, Padstack, Pcb, Pin,
+    Component, DrcViolation, Keepout, LayerId, LayerSet, LayerShape, Padstack, Pcb, Pin, PinRef,
 };
+use memeroute::name::Id;
+use memeroute::route::ratsnest::{is_inter_layer, net_ratsnest};
 
 use crate::pcb::primitives::{fill_circle, fill_polygon, fill_rt, stroke_path};
+use crate::pcb::view_transform::ViewTransform;
 use crate::pcb::{to_pos2, to_pt, to_rt};
 
 // Index 0 is front, index 1 is back.
@@ -47,6 +51,17 @@ static WIRE: LazyLock<[Color32; 2]> = LazyLock::new(|| {
 static VIA: LazyLock<Color32> =
     LazyLock::new(|| Color32::from_rgba_unmultiplied(100, 100, 100, 180));
 
+static RATSNEST: LazyLock<Color32> =
+    LazyLock::new(|| Color32::from_rgba_unmultiplied(40, 40, 40, 200));
+
+static RATSNEST_INTER_LAYER: LazyLock<Color32> =
+    LazyLock::new(|| Color32::from_rgba_unmultiplied(230, 120, 0, 200));
+
+static HIGHLIGHT: LazyLock<Color32> =
+    LazyLock::new(|| Color32::from_rgba_unmultiplied(255, 0, 0, 230));
+
+static DRC: LazyLock<Color32> = LazyLock::new(|| Color32::from_rgba_unmultiplied(255, 0, 0, 120));
+
 static DEBUG: LazyLock<Color32> =
     LazyLock::new(|| Color32::from_rgba_unmultiplied(123, 0, 255, 180));
 
@@ -54,12 +69,22 @@ static DEBUG: LazyLock<Color32> =
 #[derive(Debug, Clone)]
 pub struct PcbView {
     pcb: Pcb,
-    screen_area: Rt,
-    local_area: Rt,
-    offset: Pt,
-    zoom: f64,
+    view: ViewTransform,
     dirty: bool,
     mesh: Mesh,
+    hidden_layers: Vec<LayerId>, // Wires, pads, and vias only on these are not drawn.
+    opacity: f32,                // Applied to wires, pads, and vias.
+    ratsnest: Vec<Vec<Shape>>,   // Drawn on top of everything else.
+    highlight_net: Option<Id>,   // Net whose ratsnest is highlighted, from clicking a pad.
+    drc: Vec<DrcViolation>,      // Marked on top of everything else.
+}
+
+// Object under the cursor.
+#[derive(Debug, Clone)]
+enum Hit {
+    Drc(usize), // Index into PcbView::drc.
+    Pin(PinRef),
+    Wire(usize), // Index into Pcb::wires.
 }
 
 impl Widget for &mut PcbView {
@@ -69,22 +94,40 @@ impl Widget for &mut PcbView {
 
         if response.dragged_by(PointerButton::Middle) {
             let p = response.drag_delta();
-            self.offset += pt(p.x as f64, p.y as f64);
+            self.view.pan(pt(p.x as f64, p.y as f64));
         }
 
         if ui.rect_contains_pointer(response.rect) {
             let pos = to_pt(ui.ctx().input().pointer.interact_pos().unwrap());
             let delta = ui.ctx().input().scroll_delta.y as f64;
             let fac = 10.0 * delta / response.rect.height() as f64;
-            self.offset = self.offset + (self.offset - pos) * fac;
-            self.zoom *= 1.0 + fac;
+            self.view.zoom_at(pos, fac);
         }
 
         self.set_screen_area(to_rt(response.rect));
+        // F fits the whole board, S fits the highlighted net.
+        if response.hovered() && ui.input().key_pressed(Key::F) {
+            self.zoom_to_fit();
+        }
+        if response.hovered() && ui.input().key_pressed(Key::S) {
+            self.zoom_to_selection();
+        }
+        let hit = response.hover_pos().and_then(|pos| self.hit(self.view.to_world(to_pt(pos))));
+        if response.clicked() {
+            let net_id = match &hit {
+                Some(Hit::Pin(p)) => self.pcb.pin_ref_net(p),
+                _ => None,
+            };
+            self.set_highlight_net(net_id);
+        }
+
         let mesh = self.render(ui.ctx());
         painter.rect_filled(response.rect, 0.0, Color32::WHITE);
         painter.add(epaint::Shape::Mesh(mesh));
-        response
+        match hit {
+            Some(hit) => response.on_hover_text_at_pointer(self.describe(&hit)),
+            None => response,
+        }
     }
 }
 
@@ -92,13 +135,123 @@ impl PcbView {
     pub fn new(pcb: Pcb, local_area: Rt) -> Self {
         Self {
             pcb,
-            local_area,
+            view: ViewTransform::new(local_area),
             dirty: true,
-            offset: Pt::zero(),
-            zoom: 1.0,
-            screen_area: Rt::default(),
             mesh: Mesh::default(),
+            hidden_layers: Vec::new(),
+            opacity: 1.0,
+            ratsnest: Vec::new(),
+            highlight_net: None,
+            drc: Vec::new(),
+        }
+    }
+
+    pub fn set_highlight_net(&mut self, net_id: Option<Id>) {
+        if net_id != self.highlight_net {
+            self.highlight_net = net_id;
+            self.dirty = true;
+            self.mesh.clear(); // Regenerate mesh.
+        }
+    }
+
+    pub fn zoom_to_fit(&mut self) {
+        self.view.fit(self.pcb.bounds(), self.view.screen_area);
+        self.dirty = true;
+    }
+
+    // Fits the highlighted net, if there is one.
+    pub fn zoom_to_selection(&mut self) {
+        if let Some(net_id) = self.highlight_net {
+            self.view.fit(self.pcb.net_bounds(net_id), self.view.screen_area);
+            self.dirty = true;
+        }
+    }
+
+    // Finds a DRC marker, or a visible pad or wire under |p|, preferring
+    // markers, then pads. Scans everything, which is fast enough at
+    // interactive rates for boards of a few thousand objects.
+    fn hit(&self, p: Pt) -> Option<Hit> {
+        if let Some(idx) = self.drc.iter().position(|v| v.p.dist(p) <= Self::drc_radius(v)) {
+            return Some(Hit::Drc(idx));
+        }
+        for c in self.pcb.components() {
+            for pin in c.pins() {
+                let tf = c.pin_tf(pin);
+                let hit = pin.padstack.shapes.iter().any(|ls| {
+                    Self::is_visible(&self.hidden_layers, ls)
+                        && pt_in_shape(p, &tf.shape(&ls.shape))
+                });
+                if hit {
+                    return Some(Hit::Pin(PinRef::new(c, pin)));
+                }
+            }
         }
+        self.pcb
+            .wires()
+            .iter()
+            .position(|w| {
+                Self::is_visible(&self.hidden_layers, &w.shape) && pt_in_shape(p, &w.shape.shape)
+            })
+            .map(Hit::Wire)
+    }
+
+    fn describe(&self, hit: &Hit) -> String {
+        let net = |net_id: Option<Id>| net_id.map_or("none".to_owned(), |id| self.pcb.to_name(id));
+        let layer = |l: LayerId| self.pcb.to_name(self.pcb.layer_by_id(l).name_id);
+        match hit {
+            Hit::Drc(idx) => {
+                let v = &self.drc[*idx];
+                format!(
+                    "Clearance violation\nLayer: {}\nMeasured: {:.3} mm\nRequired: {:.3} mm",
+                    layer(v.layer),
+                    v.measured,
+                    v.required
+                )
+            }
+            Hit::Pin(p) => format!(
+                "Component: {}\nPin: {}\nNet: {}",
+                self.pcb.to_name(p.component),
+                self.pcb.to_name(p.pin),
+                net(self.pcb.pin_ref_net(p))
+            ),
+            Hit::Wire(idx) => {
+                let wire = &self.pcb.wires()[*idx];
+                let layers = wire.shape.layers.iter().map(layer).collect::<Vec<_>>().join(", ");
+                format!("Net: {}\nLayer: {layers}", net(Some(wire.net_id)))
+            }
+        }
+    }
+
+    pub fn set_ratsnest(&mut self, ratsnest: Vec<Vec<Shape>>) {
+        self.ratsnest = ratsnest;
+        self.dirty = true;
+        self.mesh.clear(); // Regenerate mesh.
+    }
+
+    pub fn set_drc(&mut self, drc: Vec<DrcViolation>) {
+        self.drc = drc;
+        self.dirty = true;
+        self.mesh.clear(); // Regenerate mesh.
+    }
+
+    // Markers are at least 0.25 mm across so they stay visible for tiny
+    // clearances.
+    fn drc_radius(v: &DrcViolation) -> f64 {
+        v.required.max(0.25)
+    }
+
+    pub fn set_layer_visibility(&mut self, hidden_layers: Vec<LayerId>, opacity: f32) {
+        if hidden_layers != self.hidden_layers || (opacity - self.opacity).abs() > f32::EPSILON {
+            self.hidden_layers = hidden_layers;
+            self.opacity = opacity;
+            self.dirty = true;
+            self.mesh.clear(); // Regenerate mesh.
+        }
+    }
+
+    // Whether |v| is on at least one layer that isn't hidden.
+    fn is_visible(hidden: &[LayerId], v: &LayerShape) -> bool {
+        v.layers.iter().any(|l| !hidden.contains(&l))
     }
 
     pub fn set_pcb(&mut self, pcb: Pcb) {
@@ -108,8 +261,7 @@ impl PcbView {
     }
 
     fn set_screen_area(&mut self, screen_area: Rt) {
-        self.screen_area = screen_area;
-        self.local_area = self.local_area.match_aspect(&self.screen_area);
+        self.view.set_screen_area(screen_area);
         self.dirty = true;
     }
 
@@ -137,19 +289,29 @@ impl PcbView {
         Self::draw_shape(tf, &v.shape, col)
     }
 
-    fn draw_padstack(tf: &Tf, v: &Padstack, col: Color32) -> Vec<epaint::Shape> {
+    fn draw_padstack(
+        tf: &Tf,
+        v: &Padstack,
+        col: Color32,
+        hidden: &[LayerId],
+    ) -> Vec<epaint::Shape> {
         let mut shapes = Vec::new();
-        for shape in &v.shapes {
+        for shape in v.shapes.iter().filter(|s| Self::is_visible(hidden, s)) {
             shapes.extend(Self::draw_shape(tf, shape, col));
         }
         shapes
     }
 
-    fn draw_pin(tf: &Tf, v: &Pin, col: Color32) -> Vec<epaint::Shape> {
-        Self::draw_padstack(&(tf * v.tf()), &v.padstack, col)
+    fn draw_pin(tf: &Tf, v: &Pin, col: Color32, hidden: &[LayerId]) -> Vec<epaint::Shape> {
+        Self::draw_padstack(&(tf * v.tf()), &v.padstack, col, hidden)
     }
 
-    fn draw_component(tf: &Tf, v: &Component) -> Vec<epaint::Shape> {
+    fn draw_component(
+        tf: &Tf,
+        v: &Component,
+        hidden: &[LayerId],
+        opacity: f32,
+    ) -> Vec<epaint::Shape> {
         let mut shapes = Vec::new();
         let tf = tf * v.tf();
         // TODO: Push this colour handling down, just do per layer colours.
@@ -162,7 +324,7 @@ impl PcbView {
         }
         for pin in v.pins() {
             let idx = pin.padstack.layers().first().unwrap();
-            shapes.extend(Self::draw_pin(&tf, pin, PIN[idx]));
+            shapes.extend(Self::draw_pin(&tf, pin, PIN[idx].linear_multiply(opacity), hidden));
         }
         shapes
     }
@@ -192,19 +354,46 @@ impl PcbView {
                 Self::tessellate(&mut tess, &mut mesh, shapes);
             }
             for component in self.pcb.components() {
-                let shapes = Self::draw_component(&tf, component);
+                let shapes =
+                    Self::draw_component(&tf, component, &self.hidden_layers, self.opacity);
                 Self::tessellate(&mut tess, &mut mesh, shapes);
             }
             for wire in self.pcb.wires() {
+                if !Self::is_visible(&self.hidden_layers, &wire.shape) {
+                    continue;
+                }
                 // TODO!!: Fix up layerset to color mapping.
-                let col = WIRE[Self::layer_id_to_color_idx(wire.shape.layers.id().unwrap())];
+                let col = WIRE[Self::layer_id_to_color_idx(wire.shape.layers.id().unwrap())]
+                    .linear_multiply(self.opacity);
                 let shapes = Self::draw_shape(&tf, &wire.shape, col);
                 Self::tessellate(&mut tess, &mut mesh, shapes);
             }
             for via in self.pcb.vias() {
-                let shapes = Self::draw_padstack(&via.tf(), &via.padstack, *VIA);
+                let shapes = Self::draw_padstack(
+                    &via.tf(),
+                    &via.padstack,
+                    VIA.linear_multiply(self.opacity),
+                    &self.hidden_layers,
+                );
+                Self::tessellate(&mut tess, &mut mesh, shapes);
+            }
+            for shape in self.ratsnest.iter().flatten() {
+                let col = if is_inter_layer(shape) { *RATSNEST_INTER_LAYER } else { *RATSNEST };
+                let ls = LayerShape { shape: shape.clone(), layers: LayerSet::empty() };
+                let shapes = Self::draw_shape(&tf, &ls, col);
                 Self::tessellate(&mut tess, &mut mesh, shapes);
             }
+            if let Some(net) = self.highlight_net.and_then(|id| self.pcb.net(id)) {
+                for shape in net_ratsnest(&self.pcb, net) {
+                    let ls = LayerShape { shape, layers: LayerSet::empty() };
+                    let shapes = Self::draw_shape(&tf, &ls, *HIGHLIGHT);
+                    Self::tessellate(&mut tess, &mut mesh, shapes);
+                }
+            }
+            for v in &self.drc {
+                let shape = fill_circle(&tf, v.p, Self::drc_radius(v), *DRC);
+                Self::tessellate(&mut tess, &mut mesh, vec![shape]);
+            }
             for rt in self.pcb.debug_rts() {
                 let mut pts = rt.pts().to_vec();
                 pts.push(rt.pts()[0]);
@@ -217,12 +406,7 @@ impl PcbView {
         }
         let mut mesh = self.mesh.clone();
         if self.dirty {
-            let inv = Tf::scale(pt(1.0, -1.0)); // Invert y axis
-            let local_area = inv.rt(&self.local_area).bounds();
-            let tf = Tf::translate(self.offset)
-                * Tf::scale(pt(self.zoom, self.zoom))
-                * Tf::affine(&local_area, &self.screen_area)
-                * inv;
+            let tf = self.view.tf();
             for vert in &mut mesh.vertices {
                 vert.pos = to_pos2(tf.pt(to_pt(vert.pos)));
             }
@@ -0,0 +1,74 @@
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::tf::Tf;
+
+// Space left around bounds when fitting them, as a fraction of their size.
+const FIT_MARGIN: f64 = 0.05;
+
+// Maps board coordinates to screen coordinates. |local_area| of the board is
+// fitted to |screen_area|, then zoomed by |zoom| and moved by |offset|, both
+// in screen space.
+#[must_use]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewTransform {
+    pub local_area: Rt,
+    pub screen_area: Rt,
+    pub offset: Pt,
+    pub zoom: f64,
+}
+
+impl ViewTransform {
+    pub fn new(local_area: Rt) -> Self {
+        Self { local_area, screen_area: Rt::default(), offset: Pt::zero(), zoom: 1.0 }
+    }
+
+    pub fn set_screen_area(&mut self, screen_area: Rt) {
+        self.screen_area = screen_area;
+        self.local_area = self.local_area.match_aspect(&self.screen_area);
+    }
+
+    // Shows all of |bounds|, centred, in |viewport|. Empty bounds are ignored.
+    pub fn fit(&mut self, bounds: Rt, viewport: Rt) {
+        if bounds.w() <= 0.0 && bounds.h() <= 0.0 {
+            return;
+        }
+        let margin = FIT_MARGIN * bounds.w().max(bounds.h());
+        self.screen_area = viewport;
+        self.local_area = bounds.inset(-margin, -margin).match_aspect(&viewport);
+        self.offset = Pt::zero();
+        self.zoom = 1.0;
+    }
+
+    pub fn pan(&mut self, d: Pt) {
+        self.offset += d;
+    }
+
+    // Zooms by a factor of 1 + |fac|, keeping the screen point |pos| fixed.
+    pub fn zoom_at(&mut self, pos: Pt, fac: f64) {
+        self.offset = self.offset + (self.offset - pos) * fac;
+        self.zoom *= 1.0 + fac;
+    }
+
+    // Transform from board coordinates to screen coordinates.
+    pub fn tf(&self) -> Tf {
+        let inv = Tf::scale(pt(1.0, -1.0)); // Invert y axis
+        let local_area = inv.rt(&self.local_area).bounds();
+        Tf::translate(self.offset)
+            * Tf::scale(pt(self.zoom, self.zoom))
+            * Tf::affine(&local_area, &self.screen_area)
+            * inv
+    }
+
+    // Inverse of |tf|.
+    #[must_use]
+    pub fn to_world(&self, p: Pt) -> Pt {
+        let inv = Tf::scale(pt(1.0, -1.0));
+        let local_area = inv.rt(&self.local_area).bounds();
+        let tf = inv
+            * Tf::affine(&self.screen_area, &local_area)
+            * Tf::scale(pt(1.0 / self.zoom, 1.0 / self.zoom))
+            * Tf::translate(pt(-self.offset.x, -self.offset.y));
+        tf.pt(p)
+    }
+}
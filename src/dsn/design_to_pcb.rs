@@ -1,24 +1,100 @@
 use std::collections::HashMap;
+use std::f64::consts::PI;
 
 use eyre::{eyre, Result};
 use itertools::Itertools;
 use memedsn::types::{
     DsnCircuit, DsnClass, DsnClearance, DsnClearanceType, DsnComponent, DsnDimensionUnit, DsnImage,
     DsnKeepout, DsnKeepoutType, DsnLayerType, DsnNet, DsnPadstack, DsnPcb, DsnPin, DsnRect,
-    DsnRule, DsnShape, DsnSide,
+    DsnRule, DsnShape, DsnSide, DsnVia, DsnWire,
 };
-use memegeom::geom::math::{eq, pt_eq};
 use memegeom::primitive::point::Pt;
 use memegeom::primitive::rect::Rt;
-use memegeom::primitive::{circ, path, poly, rt, ShapeOps};
+use memegeom::primitive::{circ, path, poly, pt, rt, ShapeOps};
 use strum::IntoEnumIterator;
 
+use crate::error::MemerouteError;
+use crate::geom::intersects::seg_intersects_seg;
+use crate::geom::math::{f64_eq, f64_le, pt_eq};
+use crate::geom::polygon::poly_area;
+use crate::geom::Seg;
 use crate::model::pcb::{
-    Clearance, Component, Keepout, KeepoutType, Layer, LayerId, LayerKind, LayerSet, LayerShape,
-    Net, ObjectKind, Padstack, Pcb, Pin, PinRef, Rule, RuleSet,
+    Clearance, Component, DiffPair, Keepout, KeepoutType, Layer, LayerId, LayerKind, LayerSet,
+    LayerShape, Net, ObjectKind, Padstack, Pcb, Pin, PinRef, Rule, RuleSet, Via, Wire,
 };
 use crate::name::Id;
 
+// Name of the class holding the board wide rules.
+const DEFAULT_CLASS: &str = "default";
+// Maximum angle covered by each segment of a flattened arc.
+const ARC_STEP: f64 = PI / 16.0;
+
+// Suffixes marking the positive and negative nets of a diff pair.
+const PAIR_SUFFIXES: [(&str, &str); 3] = [("+", "-"), ("P", "N"), ("p", "n")];
+
+// The positive and negative net of |a| and |b| if they are named as a diff
+// pair, e.g. USB_P and USB_N.
+fn pair_nets<'a>(a: &'a str, b: &'a str) -> Option<(&'a str, &'a str)> {
+    PAIR_SUFFIXES.iter().find_map(|&(pos, neg)| {
+        for (p, n) in [(a, b), (b, a)] {
+            if p.strip_suffix(pos).map_or(false, |v| Some(v) == n.strip_suffix(neg)) {
+                return Some((p, n));
+            }
+        }
+        None
+    })
+}
+
+// Points along the arc around |centre| going counterclockwise from |start| to
+// |end|. Equal start and end points give a full circle.
+fn arc_pts(centre: Pt, start: Pt, end: Pt) -> Vec<Pt> {
+    let r = centre.dist(start);
+    let a0 = (start.y - centre.y).atan2(start.x - centre.x);
+    let mut da = (end.y - centre.y).atan2(end.x - centre.x) - a0;
+    if f64_le(da, 0.0) {
+        da += 2.0 * PI;
+    }
+    let n = ((da / ARC_STEP).ceil() as usize).max(1);
+    let mut pts: Vec<Pt> = (0..n)
+        .map(|i| {
+            let ang = a0 + da * i as f64 / n as f64;
+            centre + r * pt(ang.cos(), ang.sin())
+        })
+        .collect();
+    pts.push(end);
+    pts
+}
+
+// Removes repeated consecutive vertices, then rejects polygons that would
+// break triangulation and intersection tests: fewer than three vertices, zero
+// area, or edges crossing each other.
+fn validate_polygon(mut pts: Vec<Pt>) -> Result<Vec<Pt>> {
+    pts.dedup_by(|a, b| pt_eq(*a, *b));
+    while pts.len() >= 2 && pt_eq(pts[0], pts[pts.len() - 1]) {
+        pts.pop();
+    }
+    let n = pts.len();
+    if n < 3 {
+        return Err(eyre!("polygon has fewer than three distinct vertices"));
+    }
+    if f64_eq(poly_area(&pts), 0.0) {
+        return Err(eyre!("polygon has zero area"));
+    }
+    let edge = |k: usize| Seg::new(pts[k], pts[(k + 1) % n]);
+    for i in 0..n {
+        // Skip adjacent edges, which always share a vertex.
+        for j in (i + 2)..n {
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+            if seg_intersects_seg(&edge(i), &edge(j)) {
+                return Err(eyre!("polygon is self-intersecting at {:?}", pts[i]));
+            }
+        }
+    }
+    Ok(pts)
+}
+
 #[must_use]
 #[derive(Debug, Clone)]
 pub struct DesignToPcb {
@@ -40,8 +116,9 @@ impl DesignToPcb {
         }
     }
 
-    fn mm(&self) -> f64 {
-        match self.dsn.resolution.dimension {
+    // Millimetres per unit of |d|.
+    fn dimension_mm(d: &DsnDimensionUnit) -> f64 {
+        match d {
             DsnDimensionUnit::Inch => 25.4,
             DsnDimensionUnit::Mil => 0.0254,
             DsnDimensionUnit::Cm => 10.0,
@@ -50,8 +127,23 @@ impl DesignToPcb {
         }
     }
 
+    // Coordinates are written in the unit clause's dimension.
+    fn mm(&self) -> f64 {
+        Self::dimension_mm(&self.dsn.unit.dimension)
+    }
+
+    // The resolution is the precision of the design, and may use a different
+    // dimension to the unit, e.g. (unit mil) with (resolution um 10) is a grid
+    // of 0.1 um.
+    fn resolution_mm(&self) -> f64 {
+        let res = &self.dsn.resolution;
+        Self::dimension_mm(&res.dimension) / f64::from(res.amount.max(1))
+    }
+
+    // Converts to mm, snapped to the resolution grid.
     fn coord(&self, v: f64) -> f64 {
-        self.mm() * v
+        let step = self.resolution_mm();
+        (self.mm() * v / step).round() * step
     }
 
     fn rect(&self, v: &DsnRect) -> Rt {
@@ -97,13 +189,18 @@ impl DesignToPcb {
                 shape: circ(self.pt(v.p), self.coord(v.diameter / 2.0)).shape(),
             },
             DsnShape::Polygon(v) => {
-                let mut pts: Vec<Pt> = v.pts.iter().map(|&v| self.pt(v)).collect();
-                // Polygons seem to have the first vertex repeated.
-                if pts.len() >= 2 && pt_eq(*pts.first().unwrap(), *pts.last().unwrap()) {
-                    pts.pop();
-                }
-                assert!(eq(v.aperture_width, 0.0), "aperture width for polygons is unsupported");
-                LayerShape { layers: self.layers(&v.layer_id)?, shape: poly(&pts).shape() }
+                let mut pts = self.polygon_pts(&v.pts);
+                // A zero aperture width means a filled region. Otherwise, the
+                // polygon is an outline stroked with the aperture.
+                let shape = if f64_eq(v.aperture_width, 0.0) {
+                    poly(&validate_polygon(pts)?).shape()
+                } else {
+                    if let Some(&first) = pts.first() {
+                        pts.push(first);
+                    }
+                    path(&pts, self.coord(v.aperture_width) / 2.0).shape()
+                };
+                LayerShape { layers: self.layers(&v.layer_id)?, shape }
             }
             DsnShape::Path(v) => LayerShape {
                 layers: self.layers(&v.layer_id)?,
@@ -113,7 +210,43 @@ impl DesignToPcb {
                 )
                 .shape(),
             },
-            DsnShape::QArc(_v) => todo!(),
+            // memegeom has no arc shape yet, so flatten the arc into a path.
+            DsnShape::QArc(v) => LayerShape {
+                layers: self.layers(&v.layer_id)?,
+                shape: path(
+                    &arc_pts(self.pt(v.center), self.pt(v.start), self.pt(v.end)),
+                    self.coord(v.aperture_width) / 2.0,
+                )
+                .shape(),
+            },
+        })
+    }
+
+    fn polygon_pts(&self, pts: &[Pt]) -> Vec<Pt> {
+        let mut pts: Vec<Pt> = pts.iter().map(|&v| self.pt(v)).collect();
+        // Polygons seem to have the first vertex repeated.
+        if pts.len() >= 2 && pt_eq(*pts.first().unwrap(), *pts.last().unwrap()) {
+            pts.pop();
+        }
+        pts
+    }
+
+    // Boundaries always describe a filled region, regardless of any aperture
+    // width used to draw their outline.
+    fn boundary(&self, v: &DsnShape) -> Result<LayerShape> {
+        Ok(match v {
+            DsnShape::Polygon(v) => LayerShape {
+                layers: self.layers(&v.layer_id)?,
+                shape: poly(&validate_polygon(self.polygon_pts(&v.pts))?).shape(),
+            },
+            DsnShape::Path(v) => LayerShape {
+                layers: self.layers(&v.layer_id)?,
+                shape: poly(&validate_polygon(self.polygon_pts(&v.pts))?).shape(),
+            },
+            _ => {
+                let LayerShape { layers, shape } = self.shape(v)?;
+                LayerShape { layers, shape: shape.filled() }
+            }
         })
     }
 
@@ -124,10 +257,42 @@ impl DesignToPcb {
                 DsnKeepoutType::ViaKeepout => KeepoutType::ViaKeepout,
                 DsnKeepoutType::WireKeepout => KeepoutType::WireKeepout,
             },
-            shape: self.shape(&v.shape)?,
+            // Keepout polygons cover their interior even if drawn with an
+            // aperture. Paths may be open, so leave those as strokes.
+            shape: match &v.shape {
+                DsnShape::Polygon(_) => self.boundary(&v.shape)?,
+                shape => self.shape(shape)?,
+            },
         })
     }
 
+    fn wire(&self, v: &DsnWire) -> Result<Wire> {
+        let net_id = self.pcb.to_id(&v.net_id);
+        if self.pcb.net(net_id).is_none() {
+            return Err(eyre!("wire on unknown net {}", v.net_id));
+        }
+        Ok(Wire { shape: self.shape(&v.shape)?, net_id })
+    }
+
+    // A DSN via may place the same padstack at several points.
+    fn via(&self, v: &DsnVia) -> Result<Vec<Via>> {
+        let padstack_id = self.pcb.to_id(&v.padstack_id);
+        let padstack = self
+            .pcb
+            .via_padstacks()
+            .iter()
+            .find(|p| p.id == padstack_id)
+            .ok_or_else(|| eyre!("via uses undeclared via padstack {}", v.padstack_id))?;
+        let net_id = self.pcb.to_id(&v.net_id);
+        if self.pcb.net(net_id).is_none() {
+            return Err(eyre!("via on unknown net {}", v.net_id));
+        }
+        Ok(v.pts
+            .iter()
+            .map(|&p| Via { padstack: padstack.clone(), p: self.pt(p), net_id })
+            .collect())
+    }
+
     fn padstack(&self, v: &DsnPadstack) -> Result<Padstack> {
         Ok(Padstack {
             id: self.pcb.to_id(&v.padstack_id),
@@ -229,9 +394,26 @@ impl DesignToPcb {
         let id = self.pcb.to_id(&v.class_id);
         let mut rules: Vec<Rule> = v.rules.iter().map(|r| self.rule(r)).collect();
         rules.extend(v.circuits.iter().map(|c| self.circuit(c)));
+        // Take anything the class doesn't specify from the board wide rules.
+        let has_width = rules.iter().any(|r| matches!(r, Rule::Radius(_)));
+        let has_clearance = rules.iter().any(|r| matches!(r, Rule::Clearance(_)));
+        for r in self.dsn.structure.rules.iter().map(|r| self.rule(r)) {
+            match r {
+                Rule::Radius(_) if has_width => {}
+                Rule::Clearance(_) if has_clearance => {}
+                r => rules.push(r),
+            }
+        }
         RuleSet::new(id, rules)
     }
 
+    // Board wide rules from the structure section. These apply to nets not in
+    // any class, unless a class without nets overrides them.
+    fn default_ruleset(&self) -> Result<RuleSet> {
+        let rules = self.dsn.structure.rules.iter().map(|r| self.rule(r)).collect();
+        RuleSet::new(self.pcb.to_id(DEFAULT_CLASS), rules)
+    }
+
     fn convert_padstacks(&mut self) -> Result<()> {
         for v in &self.dsn.library.padstacks {
             if self.padstacks.insert(self.pcb.to_id(&v.padstack_id), self.padstack(v)?).is_some() {
@@ -250,16 +432,31 @@ impl DesignToPcb {
         Ok(())
     }
 
-    pub fn convert(mut self) -> Result<Pcb> {
-        self.pcb.set_pcb_name(&self.dsn.pcb_id);
-        if self.dsn.unit.dimension != self.dsn.resolution.dimension {
-            return Err(eyre!(
-                "unit override unimplemented: {} {}",
-                self.dsn.unit.dimension,
-                self.dsn.resolution.dimension
-            ));
+    pub fn convert(self) -> Result<Pcb, MemerouteError> {
+        self.convert_pcb().map_err(MemerouteError::Dsn)
+    }
+
+    // Pre-routed wires. Must run after nets are added.
+    fn convert_wires(&mut self) -> Result<()> {
+        for v in &self.dsn.wiring.wires {
+            let wire = self.wire(v)?;
+            self.pcb.add_wire(wire);
+        }
+        Ok(())
+    }
+
+    // Pre-placed vias. Must run after nets and via padstacks are added.
+    fn convert_vias(&mut self) -> Result<()> {
+        for v in &self.dsn.wiring.vias {
+            for via in self.via(v)? {
+                self.pcb.add_via(via);
+            }
         }
+        Ok(())
+    }
 
+    fn convert_pcb(mut self) -> Result<Pcb> {
+        self.pcb.set_pcb_name(&self.dsn.pcb_id);
         // Layers needed for padstacks and images.
         for (id, v) in self.dsn.structure.layers.iter().enumerate() {
             let id = id as LayerId;
@@ -285,8 +482,10 @@ impl DesignToPcb {
         // Physical structure:
         for v in &self.dsn.structure.boundaries {
             // Convert boundaries to closed shapes.
-            let LayerShape { layers, shape } = self.shape(v)?;
-            self.pcb.add_boundary(LayerShape { layers, shape: shape.filled() });
+            self.pcb.add_boundary(self.boundary(v)?);
+        }
+        if !self.pcb.boundaries().is_empty() {
+            self.pcb.set_origin(self.pcb.bounds().bl());
         }
         for v in &self.dsn.structure.keepouts {
             self.pcb.add_keepout(self.keepout(v)?);
@@ -309,6 +508,9 @@ impl DesignToPcb {
         for v in &self.dsn.network.nets {
             self.pcb.add_net(self.net(v));
         }
+        let default = self.default_ruleset()?;
+        self.pcb.set_default_net_ruleset(default.id);
+        self.pcb.add_ruleset(default);
         for v in &self.dsn.network.classes {
             let ruleset = self.ruleset(v)?;
             self.pcb.add_ruleset(ruleset.clone());
@@ -322,10 +524,252 @@ impl DesignToPcb {
             }
         }
 
-        // TODO: Add wires
-        // TODO: Add vias
-        // TODO: Support classes for nets.
-        // TODO: Support rules from structure.
+        // DSN has no diff pairs of its own, so a class of just the two nets
+        // of a pair marks one. The traces are kept the class clearance apart.
+        for v in &self.dsn.network.classes {
+            let [a, b] = &v.net_ids[..] else { continue };
+            let Some((p, n)) = pair_nets(a, b) else { continue };
+            let (net_a, net_b) = (self.pcb.to_id(p), self.pcb.to_id(n));
+            let clearance = self
+                .pcb
+                .net_ruleset(net_a)
+                .clearances()
+                .iter()
+                .map(Clearance::amount)
+                .fold(0.0, f64::max);
+            let spacing = 2.0 * self.pcb.net_radius(net_a) + clearance;
+            self.pcb.add_diff_pair(DiffPair { net_a, net_b, spacing })?;
+        }
+
+        self.convert_wires()?;
+        self.convert_vias()?;
         Ok(self.pcb)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::pt;
+    use memegeom::primitive::shape::Shape;
+
+    use super::{arc_pts, pair_nets, validate_polygon, ARC_STEP};
+    use crate::dsn::pcb_to_session::PcbToSession;
+    use crate::dsn::session_to_pcb::SessionToPcb;
+    use crate::geom::math::{f64_eq, f64_le, pt_eq};
+    use crate::model::pcb::Pcb;
+    use crate::testing::{dsn, load_dsn, UM};
+
+    const EMPTY: &str = "(placement) (library) (network) (wiring)";
+
+    // Image "part" with pin 1 at (1, 0) mm and pin 2, rotated, at (0, 0.5) mm.
+    const LIBRARY: &str = r#"(library
+    (image part
+      (outline (path signal 100  -500 -500  500 -500))
+      (pin pad 1 1000 0)
+      (pin pad (rotate 90) 2 0 500)
+      (keepout "" (circle F.Cu 400))
+    )
+    (padstack pad (shape (circle F.Cu 600)) (attach off))
+  )"#;
+
+    // World coordinates of pin |pin| on component |name|.
+    fn pin_pt(pcb: &Pcb, name: &str, pin: &str) -> Pt {
+        let c = pcb.component(pcb.to_id(name)).unwrap();
+        c.pin_pt(c.pin(pcb.to_id(pin)).unwrap())
+    }
+
+    #[test]
+    fn bowtie_polygon_is_rejected() {
+        let bowtie = vec![pt(0.0, 0.0), pt(2.0, 2.0), pt(2.0, 0.0), pt(0.0, 2.0)];
+        let err = validate_polygon(bowtie).unwrap_err();
+        assert!(err.to_string().contains("self-intersecting"), "{err}");
+    }
+
+    #[test]
+    fn degenerate_polygons_are_rejected() {
+        let line = vec![pt(0.0, 0.0), pt(1.0, 0.0), pt(2.0, 0.0)];
+        assert!(validate_polygon(line).unwrap_err().to_string().contains("zero area"));
+        let two = vec![pt(0.0, 0.0), pt(1.0, 0.0), pt(1.0, 0.0), pt(0.0, 0.0)];
+        assert!(validate_polygon(two).unwrap_err().to_string().contains("three"));
+    }
+
+    #[test]
+    fn repeated_vertices_are_removed() {
+        let pts = vec![pt(0.0, 0.0), pt(1.0, 0.0), pt(1.0, 0.0), pt(1.0, 1.0), pt(0.0, 0.0)];
+        assert_eq!(validate_polygon(pts).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn qarc_is_flattened_onto_the_arc() {
+        // Half a circle of radius 1 mm, counterclockwise from (1, 0) to (-1, 0).
+        let library = "(library (image part (outline (qarc signal 100  1000 0  -1000 0  0 0))))";
+        let body = format!(
+            "(placement (component part (place U1 0 0 front 0))) {library} (network) (wiring)"
+        );
+        let pcb = load_dsn(&dsn(UM, "", &body)).unwrap();
+        let c = pcb.component(pcb.to_id("U1")).unwrap();
+        let Shape::Path(p) = &c.outlines[0].shape else { panic!("expected a path") };
+        assert!(f64_eq(p.r(), 0.05));
+        let pts = p.pts();
+        assert!(pt_eq(pts[0], pt(1.0, 0.0)) && pt_eq(pts[pts.len() - 1], pt(-1.0, 0.0)), "{pts:?}");
+        // Every vertex is on the arc, and no chord strays further from it than
+        // the sagitta of one step.
+        let sagitta = 1.0 - (ARC_STEP / 2.0).cos();
+        for v in pts.windows(2) {
+            assert!(f64_eq(v[0].dist(Pt::zero()), 1.0) && f64_le(0.0, v[0].y), "{v:?}");
+            let mid = 0.5 * (v[0] + v[1]);
+            assert!(f64_le(1.0 - mid.dist(Pt::zero()), sagitta), "{v:?}");
+        }
+        // Equal ends go all the way round.
+        let circle = arc_pts(Pt::zero(), pt(1.0, 0.0), pt(1.0, 0.0));
+        assert_eq!(circle.len(), 33);
+        assert!(pt_eq(circle[16], pt(-1.0, 0.0)));
+    }
+
+    #[test]
+    fn bowtie_keepout_fails_import() {
+        let keepout = "(keepout \"\" (polygon F.Cu 0  1000 1000  3000 3000  3000 1000  1000 3000))";
+        let err = load_dsn(&dsn(UM, keepout, EMPTY)).unwrap_err();
+        assert!(format!("{err:#}").contains("self-intersecting"), "{err:#}");
+    }
+
+    #[test]
+    fn unit_differs_from_resolution() {
+        // The boundary is 10000 units square.
+        let pcb = load_dsn(&dsn("(resolution um 10) (unit mil)", "", EMPTY)).unwrap();
+        assert!(f64_eq(pcb.bounds().w(), 254.0), "{:?}", pcb.bounds());
+        // 10000 mm is 393700.79 mil, which is off the 1 mil grid.
+        let pcb = load_dsn(&dsn("(resolution mil 1) (unit mm)", "", EMPTY)).unwrap();
+        assert!(f64_eq(pcb.bounds().w(), 393_701.0 * 0.0254), "{:?}", pcb.bounds());
+        let pcb = load_dsn(&dsn(UM, "", EMPTY)).unwrap();
+        assert!(f64_eq(pcb.bounds().w(), 10.0), "{:?}", pcb.bounds());
+    }
+
+    #[test]
+    fn pair_classes_become_diff_pairs() {
+        let network = "(network
+            (net USB_N (pins)) (net USB_P (pins)) (net CLK (pins)) (net DATA (pins))
+            (class usb USB_N USB_P (rule (width 150) (clearance 100)))
+            (class misc CLK DATA))";
+        let pcb = load_dsn(&dsn(UM, "", &format!("(placement) (library) {network} (wiring)")));
+        let pcb = pcb.unwrap();
+        assert_eq!(pcb.diff_pairs().len(), 1);
+        let pair = pcb.diff_pairs()[0];
+        assert_eq!((pair.net_a, pair.net_b), (pcb.to_id("USB_P"), pcb.to_id("USB_N")));
+        // Two half widths and the clearance between the traces' edges.
+        assert!(f64_eq(pair.spacing, 0.25), "{}", pair.spacing);
+        assert_eq!(pair_nets("CLK+", "CLK-"), Some(("CLK+", "CLK-")));
+        assert_eq!(pair_nets("LVDSn", "LVDSp"), Some(("LVDSp", "LVDSn")));
+        assert_eq!(pair_nets("CLK", "DATA"), None);
+    }
+
+    #[test]
+    fn components_are_placed() {
+        let placement = "(placement (component part
+            (place U1 5000 5000 front 0)
+            (place U2 5000 5000 front 90)
+            (place U3 5000 5000 back 0)
+            (place U4 5000 5000 back 90)))";
+        let body = format!("{placement} {LIBRARY} (network) (wiring)");
+        let pcb = load_dsn(&dsn(UM, "", &body)).unwrap();
+        assert_eq!(pcb.components().len(), 4);
+        // Rotation is counterclockwise in degrees, and the back side mirrors
+        // the image horizontally before rotating it.
+        let want = [
+            ("U1", (6.0, 5.0), (5.0, 5.5)),
+            ("U2", (5.0, 6.0), (4.5, 5.0)),
+            ("U3", (4.0, 5.0), (5.0, 5.5)),
+            ("U4", (5.0, 4.0), (4.5, 5.0)),
+        ];
+        for (name, (x1, y1), (x2, y2)) in want {
+            let (p1, p2) = (pin_pt(&pcb, name, "1"), pin_pt(&pcb, name, "2"));
+            assert!(pt_eq(p1, pt(x1, y1)), "{name} pin 1 at {p1:?}");
+            assert!(pt_eq(p2, pt(x2, y2)), "{name} pin 2 at {p2:?}");
+        }
+        // Back side pads move to the other layer.
+        let c = pcb.component(pcb.to_id("U3")).unwrap();
+        let pin = c.pin(pcb.to_id("1")).unwrap();
+        assert_eq!(pin.padstack.layers().first(), Some(1));
+    }
+
+    #[test]
+    fn image_has_pins_outlines_and_keepouts() {
+        let body = format!(
+            "(placement (component part (place U1 0 0 front 0))) {LIBRARY} (network) (wiring)"
+        );
+        let pcb = load_dsn(&dsn(UM, "", &body)).unwrap();
+        let c = pcb.component(pcb.to_id("U1")).unwrap();
+        assert_eq!(c.footprint_id, pcb.to_id("part"));
+        assert_eq!(c.pins().len(), 2);
+        let pin = |name: &str| c.pin(pcb.to_id(name)).unwrap();
+        assert!(pt_eq(pin("1").p, pt(1.0, 0.0)));
+        assert!(pt_eq(pin("2").p, pt(0.0, 0.5)));
+        assert!(f64_eq(pin("2").rotation, 90.0));
+        assert_eq!(pin("1").padstack.id, pcb.to_id("pad"));
+        assert_eq!(c.outlines.len(), 1);
+        assert_eq!(c.keepouts.len(), 1);
+    }
+
+    #[test]
+    fn image_with_missing_padstack_fails() {
+        let library = "(library (image part (pin nope 1 0 0)))";
+        let err = load_dsn(&dsn(UM, "", &format!("(placement) {library} (network) (wiring)")));
+        let err = format!("{:#}", err.unwrap_err());
+        assert!(err.contains("missing padstack with id nope"), "{err}");
+    }
+
+    #[test]
+    fn offset_board_exports_absolute_coordinates() {
+        // The same design, moved so the board starts at (100, 100) mm.
+        let body = "(placement) (library) (network (net N1 (pins)))
+          (wiring (wire (path F.Cu 200  101000 102000  105000 102000  105000 107500) (net N1)))";
+        let data = dsn(UM, "", body).replace(
+            "0 0  10000 0  10000 10000  0 10000  0 0",
+            "100000 100000  110000 100000  110000 110000  100000 110000  100000 100000",
+        );
+        let pcb = load_dsn(&data).unwrap();
+        assert!(pt_eq(pcb.origin(), pt(100.0, 100.0)), "{:?}", pcb.origin());
+        let want = [pt(101.0, 102.0), pt(105.0, 102.0), pt(105.0, 107.5)];
+        let Shape::Path(p) = &pcb.wires()[0].shape.shape else { panic!("expected a path") };
+        assert!(p.pts().iter().zip(&want).all(|(&a, &b)| pt_eq(a, b)), "{:?}", p.pts());
+
+        // Sessions are in units of 10 nm, from the same zero as the design.
+        let ses = PcbToSession::new(pcb.clone()).convert().unwrap();
+        assert!(ses.contains("10100000 10200000 10500000 10200000 10500000 10750000"), "{ses}");
+        let mut base = pcb.clone();
+        base.retain_copper(|_| false);
+        let loaded = SessionToPcb::new(base, ses).convert().unwrap();
+        let Shape::Path(p) = &loaded.wires()[0].shape.shape else { panic!("expected a path") };
+        assert!(p.pts().iter().zip(&want).all(|(&a, &b)| pt_eq(a, b)), "{:?}", p.pts());
+    }
+
+    #[test]
+    fn vias_survive_round_trip() {
+        let library = "(library (padstack via (shape (circle signal 600)) (attach off)))";
+        let design = |wiring: &str| {
+            let body = format!("(placement) {library} (network (net N1 (pins))) (wiring {wiring})");
+            dsn(UM, "(via via)", &body)
+        };
+        let pcb =
+            load_dsn(&design("(via via 1000 2000 (net N1)) (via via 3000 4000 (net N1))")).unwrap();
+        let pts: Vec<_> = pcb.vias().iter().map(|v| v.p).collect();
+        assert_eq!(pts.len(), 2);
+        assert!(pt_eq(pts[0], pt(1.0, 2.0)) && pt_eq(pts[1], pt(3.0, 4.0)), "{pts:?}");
+        assert!(pcb.vias().iter().all(|v| v.padstack.id == pcb.to_id("via")));
+        assert!(pcb.vias().iter().all(|v| v.net_id == pcb.to_id("N1")));
+
+        // Written to a session and read back onto the board without them.
+        let ses = PcbToSession::new(pcb.clone()).convert().unwrap();
+        let base = load_dsn(&design("")).unwrap();
+        let loaded = SessionToPcb::new(base, ses).convert().unwrap();
+        assert_eq!(loaded.vias().len(), 2);
+        for (a, b) in loaded.vias().iter().zip(pcb.vias()) {
+            assert!(pt_eq(a.p, b.p));
+            assert_eq!(a.padstack.id, b.padstack.id);
+        }
+
+        let err = load_dsn(&design("(via pad 1000 2000 (net N1))")).unwrap_err();
+        assert!(format!("{err:#}").contains("undeclared via padstack pad"), "{err:#}");
+    }
+}
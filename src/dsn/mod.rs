@@ -1,2 +1,6 @@
 pub mod design_to_pcb;
+pub mod pcb_to_gerber;
 pub mod pcb_to_session;
+pub mod pcb_to_svg;
+pub mod session_to_pcb;
+pub mod sexp;
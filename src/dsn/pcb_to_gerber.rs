@@ -0,0 +1,304 @@
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+use eyre::Result;
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::pt;
+use memegeom::primitive::shape::Shape;
+use memegeom::tf::Tf;
+use ordered_float::OrderedFloat;
+
+use crate::error::MemerouteError;
+use crate::geom::math::{cross, cross_at, dot, f64_eq, f64_le, orientation};
+use crate::geom::transform::shape_tf;
+use crate::geom::Line;
+use crate::model::pcb::{LayerId, LayerShape, Pcb};
+
+// Coordinates are written as millimetres with this many decimal places.
+const DECIMALS: usize = 6;
+// Gerber reserves aperture codes below 10.
+const FIRST_APERTURE: usize = 10;
+// Largest turn between two segments of a path that can be part of an arc.
+// Fillets and flattened DSN arcs both turn by at most this much per segment.
+const ARC_STEP: f64 = PI / 16.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Aperture {
+    Circle(OrderedFloat<f64>),                  // Diameter.
+    Rect(OrderedFloat<f64>, OrderedFloat<f64>), // Width and height.
+}
+
+// Writes the copper on one layer as RS-274X, for fabrication. Pads and vias
+// are flashed, path wires are drawn with a round aperture of the wire width,
+// and polygons are written as regions. memegeom has no arc shape, so arcs come
+// to us as runs of short segments; those are drawn as G02/G03 arcs.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct PcbToGerber {
+    pcb: Pcb,
+    layer: LayerId,
+    apertures: Vec<Aperture>, // Aperture i has code FIRST_APERTURE + i.
+    current: Option<usize>,   // Currently selected aperture.
+    s: String,
+}
+
+impl PcbToGerber {
+    pub fn new(pcb: Pcb, layer: LayerId) -> Self {
+        Self { pcb, layer, apertures: Vec::new(), current: None, s: String::new() }
+    }
+
+    fn coord(v: f64) -> i64 {
+        (v * 10f64.powi(DECIMALS as i32)).round() as i64
+    }
+
+    fn pt(p: Pt) -> String {
+        format!("X{}Y{}", Self::coord(p.x), Self::coord(p.y))
+    }
+
+    fn select(&mut self, aperture: Aperture) -> Result<()> {
+        let idx = if let Some(idx) = self.apertures.iter().position(|&a| a == aperture) {
+            idx
+        } else {
+            self.apertures.push(aperture);
+            self.apertures.len() - 1
+        };
+        if self.current != Some(idx) {
+            writeln!(self.s, "D{}*", FIRST_APERTURE + idx)?;
+            self.current = Some(idx);
+        }
+        Ok(())
+    }
+
+    fn shape(&mut self, tf: &Tf, ls: &LayerShape) -> Result<()> {
+        if !ls.layers.contains(self.layer) {
+            return Ok(());
+        }
+        match shape_tf(tf, &ls.shape) {
+            Shape::Circle(s) => {
+                self.select(Aperture::Circle(OrderedFloat(s.r() * 2.0)))?;
+                writeln!(self.s, "{}D03*", Self::pt(s.p()))?;
+            }
+            Shape::Rect(s) => {
+                self.select(Aperture::Rect(OrderedFloat(s.w()), OrderedFloat(s.h())))?;
+                writeln!(self.s, "{}D03*", Self::pt(s.center()))?;
+            }
+            Shape::Path(s) => {
+                let Some((&first, rest)) = s.pts().split_first() else { return Ok(()) };
+                self.select(Aperture::Circle(OrderedFloat(s.r() * 2.0)))?;
+                writeln!(self.s, "{}D02*", Self::pt(first))?;
+                // A single point path is a round pad, so flash it instead.
+                if rest.is_empty() {
+                    writeln!(self.s, "{}D03*", Self::pt(first))?;
+                }
+                self.draw(s.pts())?;
+            }
+            Shape::Polygon(s) => {
+                let Some((&first, rest)) = s.pts().split_first() else { return Ok(()) };
+                writeln!(self.s, "G36*")?;
+                writeln!(self.s, "{}D02*", Self::pt(first))?;
+                for &p in rest {
+                    writeln!(self.s, "{}D01*", Self::pt(p))?;
+                }
+                writeln!(self.s, "{}D01*", Self::pt(first))?;
+                writeln!(self.s, "G37*")?;
+            }
+            _ => {} // Other shapes don't appear on boards.
+        }
+        Ok(())
+    }
+
+    // Draws through |pts| from the first point.
+    fn draw(&mut self, pts: &[Pt]) -> Result<()> {
+        let mut i = 0;
+        while i + 1 < pts.len() {
+            let Some((j, centre)) = arc_at(pts, i) else {
+                writeln!(self.s, "{}D01*", Self::pt(pts[i + 1]))?;
+                i += 1;
+                continue;
+            };
+            let ccw = orientation(&Line::new(pts[i], pts[i + 1]), pts[i + 2]) > 0;
+            let d = centre - pts[i];
+            writeln!(self.s, "{}*", if ccw { "G03" } else { "G02" })?;
+            writeln!(self.s, "{}I{}J{}D01*", Self::pt(pts[j]), Self::coord(d.x), Self::coord(d.y))?;
+            writeln!(self.s, "G01*")?;
+            i = j;
+        }
+        Ok(())
+    }
+
+    pub fn convert(self) -> Result<String, MemerouteError> {
+        self.convert_gerber().map_err(MemerouteError::Export)
+    }
+
+    fn convert_gerber(mut self) -> Result<String> {
+        let pcb = self.pcb.clone();
+        writeln!(self.s, "G01*")?;
+        // Arcs may go round more than a quadrant.
+        writeln!(self.s, "G75*")?;
+        for c in pcb.components() {
+            for pin in c.pins() {
+                for v in &pin.padstack.shapes {
+                    self.shape(&c.pin_tf(pin), v)?;
+                }
+            }
+        }
+        for v in pcb.vias() {
+            for s in &v.padstack.shapes {
+                self.shape(&v.tf(), s)?;
+            }
+        }
+        let id = Tf::identity();
+        for v in pcb.wires() {
+            self.shape(&id, &v.shape)?;
+        }
+        writeln!(self.s, "M02*")?;
+
+        // Apertures have to be defined before use, so write the header last.
+        let mut header = String::new();
+        writeln!(header, "%FSLAX4{DECIMALS}Y4{DECIMALS}*%")?;
+        writeln!(header, "%MOMM*%")?;
+        writeln!(header, "%LPD*%")?;
+        for (i, a) in self.apertures.iter().enumerate() {
+            let code = FIRST_APERTURE + i;
+            match a {
+                Aperture::Circle(d) => writeln!(header, "%ADD{code}C,{:.DECIMALS$}*%", d.0)?,
+                Aperture::Rect(w, h) => {
+                    writeln!(header, "%ADD{code}R,{:.DECIMALS$}X{:.DECIMALS$}*%", w.0, h.0)?;
+                }
+            }
+        }
+        Ok(header + &self.s)
+    }
+}
+
+// If the path |pts| runs along an arc from |pts[i]|, the index of the last
+// point on it and its centre. Segments of an arc all have the same length and
+// turn by the same small angle. Two segments is the shortest arc.
+fn arc_at(pts: &[Pt], i: usize) -> Option<(usize, Pt)> {
+    let turn = |k: usize| {
+        let (u, v) = (pts[k] - pts[k - 1], pts[k + 1] - pts[k]);
+        cross(u, v).atan2(dot(u, v))
+    };
+    if i + 2 >= pts.len() {
+        return None;
+    }
+    let (len, angle) = (pts[i].dist(pts[i + 1]), turn(i + 1));
+    if f64_eq(len, 0.0) || !f64_eq(pts[i + 1].dist(pts[i + 2]), len) {
+        return None;
+    }
+    if f64_eq(angle, 0.0) || !f64_le(angle.abs(), ARC_STEP) {
+        return None;
+    }
+    let centre = circumcentre(pts[i], pts[i + 1], pts[i + 2])?;
+    let mut j = i + 2;
+    while j + 1 < pts.len() && f64_eq(pts[j].dist(pts[j + 1]), len) && f64_eq(turn(j), angle) {
+        j += 1;
+    }
+    Some((j, centre))
+}
+
+// Centre of the circle through |a|, |b|, and |c|, if they aren't collinear.
+fn circumcentre(a: Pt, b: Pt, c: Pt) -> Option<Pt> {
+    let d = 2.0 * cross_at(a, b, c);
+    if f64_eq(d, 0.0) {
+        return None;
+    }
+    let (b, c) = (b - a, c - a);
+    let (bb, cc) = (dot(b, b), dot(c, c));
+    Some(a + pt((c.y * bb - b.y * cc) / d, (b.x * cc - c.x * bb) / d))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::pt;
+
+    use super::PcbToGerber;
+    use crate::model::pcb::{Pcb, Via};
+    use crate::testing::{add_component, add_net, board, wire};
+
+    fn gerber(pcb: &Pcb) -> String {
+        PcbToGerber::new(pcb.clone(), 0).convert().unwrap()
+    }
+
+    #[test]
+    fn small_board_snapshot() {
+        let mut pcb = board(5.0, 5.0);
+        add_component(&mut pcb, "U1", pt(1.0, 1.0), &[Pt::zero()]);
+        let net_id = add_net(&mut pcb, "A", &[("U1", "1")]);
+        pcb.add_wire(wire(net_id, 0, &[pt(1.0, 1.0), pt(3.0, 1.0), pt(3.0, 2.5)]));
+        // Wires on other layers aren't drawn.
+        pcb.add_wire(wire(net_id, 1, &[pt(1.0, 1.0), pt(1.0, 4.0)]));
+        let want = "%FSLAX46Y46*%
+%MOMM*%
+%LPD*%
+%ADD10C,0.600000*%
+%ADD11C,0.200000*%
+G01*
+G75*
+D10*
+X1000000Y1000000D03*
+D11*
+X1000000Y1000000D02*
+X3000000Y1000000D01*
+X3000000Y2500000D01*
+M02*
+";
+        assert_eq!(gerber(&pcb), want);
+    }
+
+    #[test]
+    fn apertures_and_draws() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 2.0), &[Pt::zero(), pt(0.0, 1.0)]);
+        let net_id = add_net(&mut pcb, "A", &[("U1", "1")]);
+        let padstack = pcb.via_padstacks()[0].clone();
+        pcb.add_via(Via { p: pt(5.0, 5.0), padstack, net_id });
+        let wires = [
+            vec![pt(2.0, 2.0), pt(5.0, 2.0), pt(5.0, 5.0)],
+            vec![pt(2.0, 3.0), pt(8.0, 3.0)],
+            vec![pt(6.0, 6.0), pt(7.0, 7.0), pt(8.0, 7.0), pt(8.0, 9.0)],
+        ];
+        for pts in &wires {
+            pcb.add_wire(wire(net_id, 0, pts));
+        }
+        let s = gerber(&pcb);
+        // Pads and the via share a round aperture, and wires use another.
+        assert_eq!(s.matches("%ADD").count(), 2, "{s}");
+        assert_eq!(s.matches("D03*").count(), 3, "{s}");
+        assert_eq!(s.matches("D02*").count(), wires.len(), "{s}");
+        let segs: usize = wires.iter().map(|w| w.len() - 1).sum();
+        assert_eq!(s.matches("D01*").count(), segs, "{s}");
+    }
+
+    #[test]
+    fn arcs_are_drawn_as_arcs() {
+        let mut pcb = board(10.0, 10.0);
+        let net_id = add_net(&mut pcb, "A", &[]);
+        // Along the x axis, then a quarter circle of radius 2 around (4, 3)
+        // turning left in 8 steps, then up.
+        let mut pts = vec![pt(1.0, 1.0)];
+        for i in 0..=8 {
+            let a = -PI / 2.0 + PI / 2.0 * i as f64 / 8.0;
+            pts.push(pt(4.0 + 2.0 * a.cos(), 3.0 + 2.0 * a.sin()));
+        }
+        pts.push(pt(6.0, 6.0));
+        pcb.add_wire(wire(net_id, 0, &pts));
+        let s = gerber(&pcb);
+        let lines: Vec<_> = s.lines().skip_while(|l| *l != "X1000000Y1000000D02*").collect();
+        assert_eq!(
+            lines,
+            [
+                "X1000000Y1000000D02*",
+                "X4000000Y1000000D01*",
+                "G03*",
+                "X6000000Y3000000I0J2000000D01*",
+                "G01*",
+                "X6000000Y6000000D01*",
+                "M02*",
+            ]
+        );
+    }
+}
@@ -9,6 +9,7 @@ use memegeom::primitive::rect::Rt;
 use memegeom::primitive::shape::Shape;
 use strum::IntoEnumIterator;
 
+use crate::error::MemerouteError;
 use crate::model::pcb::{
     Component, LayerKind, LayerSet, LayerShape, Net, Padstack, Pcb, Via, Wire,
 };
@@ -55,9 +56,11 @@ impl PcbToSession {
         self.name(&self.pcb.to_name(id));
     }
 
+    // Quoted with the default string_quote, so a backslash escapes quotes
+    // and backslashes in |name|.
     fn name(&mut self, name: &str) {
-        // TODO: Assumes double quotes.
-        self.token(&("\"".to_owned() + name + "\""));
+        let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+        self.token(&format!("\"{name}\""));
     }
 
     fn coord(&mut self, v: f64) {
@@ -178,24 +181,27 @@ impl PcbToSession {
         }
     }
 
-    fn shape(&mut self, shape: &LayerShape) {
-        let l = self.layer_id(shape.layers).unwrap();
+    fn shape(&mut self, shape: &LayerShape) -> Result<()> {
+        let l = self
+            .layer_id(shape.layers)
+            .ok_or_else(|| eyre!("no layer name for layers {:?}", shape.layers))?;
         match &shape.shape {
             Shape::Circle(s) => self.circle(&l, s),
             Shape::Path(s) => self.path(&l, s),
             Shape::Polygon(s) => self.polygon(&l, s),
             Shape::Rect(s) => self.rect(&l, s),
-            _ => unimplemented!(), // TODO: Transform these shapes.
+            s => return Err(eyre!("can't write {:?} to a session", s)),
         }
+        Ok(())
     }
 
-    fn padstack(&mut self, ps: &Padstack) {
+    fn padstack(&mut self, ps: &Padstack) -> Result<()> {
         self.begin("padstack");
         self.id(ps.id);
 
         for shape in &ps.shapes {
             self.begin("shape");
-            self.shape(shape);
+            self.shape(shape)?;
             self.end();
         }
 
@@ -206,12 +212,14 @@ impl PcbToSession {
         }
 
         self.end();
+        Ok(())
     }
 
-    fn wire(&mut self, w: &Wire) {
+    fn wire(&mut self, w: &Wire) -> Result<()> {
         self.begin("wire");
-        self.shape(&w.shape);
+        self.shape(&w.shape)?;
         self.end();
+        Ok(())
     }
 
     fn via(&mut self, v: &Via) {
@@ -224,19 +232,24 @@ impl PcbToSession {
         self.end();
     }
 
-    fn net(&mut self, net: &Net, wires: &[Wire], vias: &[Via]) {
+    fn net(&mut self, net: &Net, wires: &[Wire], vias: &[Via]) -> Result<()> {
         self.begin("net");
         self.id(net.id);
         for wire in wires {
-            self.wire(wire);
+            self.wire(wire)?;
         }
         for via in vias {
             self.via(via);
         }
         self.end();
+        Ok(())
     }
 
-    pub fn convert(mut self) -> Result<String> {
+    pub fn convert(self) -> Result<String, MemerouteError> {
+        self.convert_session().map_err(MemerouteError::Export)
+    }
+
+    fn convert_session(mut self) -> Result<String> {
         let pcb = self.pcb.clone();
 
         self.begin("session");
@@ -264,9 +277,16 @@ impl PcbToSession {
         self.resolution();
 
         self.begin("library_out");
-        // Output vias used
-        for ps in pcb.via_padstacks() {
-            self.padstack(ps);
+        // Output via padstacks, including any used by placed vias which
+        // aren't in the via list, so every via refers to a defined padstack.
+        let mut padstacks: Vec<&Padstack> = pcb.via_padstacks().iter().collect();
+        for via in pcb.vias() {
+            if !padstacks.iter().any(|ps| ps.id == via.padstack.id) {
+                padstacks.push(&via.padstack);
+            }
+        }
+        for ps in padstacks {
+            self.padstack(ps)?;
         }
         self.end();
 
@@ -288,8 +308,11 @@ impl PcbToSession {
                 .push(via.clone());
         }
 
-        for (net, wires, vias) in nets.values() {
-            self.net(net, wires, vias);
+        // Sort by id so the output doesn't depend on map order.
+        let mut nets: Vec<_> = nets.into_values().collect();
+        nets.sort_unstable_by_key(|(net, _, _)| net.id);
+        for (net, wires, vias) in &nets {
+            self.net(net, wires, vias)?;
         }
         self.end();
 
@@ -299,3 +322,43 @@ impl PcbToSession {
         Ok(self.s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+
+    use super::{PcbToSession, MM_RESOLUTION};
+    use crate::dsn::session_to_pcb::SessionToPcb;
+    use crate::geom::math::pt_eq;
+    use crate::model::pcb::LayerSet;
+    use crate::route::router::{apply_route_result, Router};
+    use crate::testing::{add_net, add_smd_component, board};
+
+    #[test]
+    fn routed_via_is_written_once() {
+        // The pads are on opposite sides, so the route needs a via.
+        let mut pcb = board(10.0, 10.0);
+        add_smd_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)], LayerSet::one(0));
+        add_smd_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)], LayerSet::one(1));
+        let net_id = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let router = Router::new(pcb.clone());
+        let res = router.route(vec![net_id]).unwrap();
+        assert!(!res.failed);
+        assert_eq!(res.vias.len(), 1);
+        let base = pcb.clone();
+        apply_route_result(&mut pcb, &res);
+
+        let ses = PcbToSession::new(pcb).convert().unwrap();
+        let p = res.vias[0].p;
+        let coord = |v: f64| (v * MM_RESOLUTION as f64).round() as i64;
+        assert_eq!(ses.matches("(via ").count(), 1, "{ses}");
+        let want = format!("(via \"via\" {} {}", coord(p.x), coord(p.y));
+        assert!(ses.contains(&want), "{ses}");
+        // Both wire layers are named.
+        assert!(ses.contains("(path \"F.Cu\"") && ses.contains("(path \"B.Cu\""), "{ses}");
+        // And the via reads back where it was.
+        let loaded = SessionToPcb::new(base, ses).convert().unwrap();
+        assert_eq!(loaded.vias().len(), 1);
+        assert!(pt_eq(loaded.vias()[0].p, p));
+    }
+}
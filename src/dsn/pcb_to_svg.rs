@@ -0,0 +1,188 @@
+use std::fmt::Write;
+
+use eyre::Result;
+use memegeom::primitive::shape::Shape;
+use memegeom::tf::Tf;
+
+use crate::error::MemerouteError;
+use crate::geom::transform::shape_tf;
+use crate::model::pcb::{LayerShape, Pcb};
+
+// Colours for each layer, indexed by layer id.
+const LAYER_COLORS: [&str; 6] = ["#c83434", "#3434c8", "#34a034", "#c8a034", "#a034c8", "#34a0a0"];
+// Colour for shapes on more than one layer, e.g. through hole pads.
+const MULTI_LAYER_COLOR: &str = "#b0b0b0";
+const VIA_COLOR: &str = "#e0e0e0";
+const KEEPOUT_COLOR: &str = "#ff00ff";
+const BOUNDARY_COLOR: &str = "#404040";
+const BOUNDARY_WIDTH: f64 = 0.1;
+// Extra space around the board.
+const MARGIN: f64 = 1.0;
+
+// How a shape is painted.
+#[derive(Debug, Copy, Clone)]
+enum Paint<'a> {
+    Fill(&'a str),
+    Stroke(&'a str, f64),
+}
+
+// Renders a PCB as an SVG, for looking at results without the GUI.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct PcbToSvg {
+    pcb: Pcb,
+    s: String,
+}
+
+impl PcbToSvg {
+    pub fn new(pcb: Pcb) -> Self {
+        Self { pcb, s: String::new() }
+    }
+
+    fn layer_color(ls: &LayerShape) -> &'static str {
+        ls.layers.id().map_or(MULTI_LAYER_COLOR, |l| LAYER_COLORS[l % LAYER_COLORS.len()])
+    }
+
+    fn shape(&mut self, tf: &Tf, ls: &LayerShape, paint: Paint<'_>) -> Result<()> {
+        let style = match paint {
+            Paint::Fill(c) => format!("fill=\"{c}\""),
+            Paint::Stroke(c, w) => format!("fill=\"none\" stroke=\"{c}\" stroke-width=\"{w}\""),
+        };
+        match shape_tf(tf, &ls.shape) {
+            Shape::Circle(s) => {
+                let (p, r) = (s.p(), s.r());
+                writeln!(self.s, "<circle cx=\"{}\" cy=\"{}\" r=\"{r}\" {style}/>", p.x, p.y)?;
+            }
+            Shape::Path(s) => {
+                // Paths are drawn with their actual width, whatever the paint.
+                let color = match paint {
+                    Paint::Fill(c) | Paint::Stroke(c, _) => c,
+                };
+                let d = s
+                    .pts()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| format!("{}{} {}", if i == 0 { "M" } else { "L" }, p.x, p.y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(
+                    self.s,
+                    "<path d=\"{d}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"{}\" \
+                     stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                    s.r() * 2.0
+                )?;
+            }
+            Shape::Polygon(s) => {
+                let pts = s
+                    .pts()
+                    .iter()
+                    .map(|p| format!("{},{}", p.x, p.y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(self.s, "<polygon points=\"{pts}\" {style}/>")?;
+            }
+            Shape::Rect(s) => {
+                writeln!(
+                    self.s,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {style}/>",
+                    s.l(),
+                    s.b(),
+                    s.w(),
+                    s.h()
+                )?;
+            }
+            _ => {} // Other shapes don't appear on boards.
+        }
+        Ok(())
+    }
+
+    pub fn convert(self) -> Result<String, MemerouteError> {
+        self.convert_svg().map_err(MemerouteError::Export)
+    }
+
+    fn convert_svg(mut self) -> Result<String> {
+        let pcb = self.pcb.clone();
+        let b = pcb.bounds().inset(-MARGIN, -MARGIN);
+        // SVG has y pointing down, so flip the board about the x axis.
+        writeln!(
+            self.s,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+            b.l(),
+            -b.t(),
+            b.w(),
+            b.h()
+        )?;
+        writeln!(self.s, "<g transform=\"scale(1,-1)\">")?;
+
+        let id = Tf::identity();
+        for v in pcb.boundaries() {
+            self.shape(&id, v, Paint::Stroke(BOUNDARY_COLOR, BOUNDARY_WIDTH))?;
+        }
+        for v in pcb.keepouts() {
+            self.shape(&id, &v.shape, Paint::Stroke(KEEPOUT_COLOR, BOUNDARY_WIDTH))?;
+        }
+        for c in pcb.components() {
+            for v in &c.keepouts {
+                self.shape(&c.tf(), &v.shape, Paint::Stroke(KEEPOUT_COLOR, BOUNDARY_WIDTH))?;
+            }
+            for pin in c.pins() {
+                for v in &pin.padstack.shapes {
+                    self.shape(&c.pin_tf(pin), v, Paint::Fill(Self::layer_color(v)))?;
+                }
+            }
+        }
+        for v in pcb.wires() {
+            self.shape(&id, &v.shape, Paint::Fill(Self::layer_color(&v.shape)))?;
+        }
+        for v in pcb.vias() {
+            for s in &v.padstack.shapes {
+                self.shape(&v.tf(), s, Paint::Fill(VIA_COLOR))?;
+            }
+        }
+
+        writeln!(self.s, "</g>")?;
+        writeln!(self.s, "</svg>")?;
+        Ok(self.s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::{pt, rt, ShapeOps};
+
+    use super::{PcbToSvg, LAYER_COLORS, MULTI_LAYER_COLOR, VIA_COLOR};
+    use crate::model::pcb::{Keepout, KeepoutType, LayerSet, LayerShape, Via};
+    use crate::testing::{add_component, add_net, add_smd_component, board, wire};
+
+    #[test]
+    fn one_element_per_shape() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 2.0), &[Pt::zero(), pt(0.0, 2.0)]);
+        add_smd_component(&mut pcb, "U2", pt(8.0, 2.0), &[Pt::zero()], LayerSet::one(0));
+        let net_id = add_net(&mut pcb, "A", &[("U1", "1"), ("U2", "1")]);
+        pcb.add_wire(wire(net_id, 0, &[pt(2.0, 2.0), pt(8.0, 2.0)]));
+        pcb.add_wire(wire(net_id, 1, &[pt(2.0, 4.0), pt(5.0, 4.0), pt(5.0, 6.0)]));
+        let padstack = pcb.via_padstacks()[0].clone();
+        pcb.add_via(Via { p: pt(5.0, 6.0), padstack, net_id });
+        let shape = LayerShape { layers: LayerSet::one(0), shape: rt(6.0, 6.0, 8.0, 8.0).shape() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+
+        let s = PcbToSvg::new(pcb).convert().unwrap();
+        assert!(
+            s.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -11 12 12\">")
+        );
+        assert!(s.ends_with("</g>\n</svg>\n"), "{s}");
+        // The boundary, the keepout, three pads and a via, and two wires.
+        assert_eq!(s.matches("<polygon").count(), 1, "{s}");
+        assert_eq!(s.matches("<rect").count(), 1, "{s}");
+        assert_eq!(s.matches("<circle").count(), 4, "{s}");
+        assert_eq!(s.matches("<path").count(), 2, "{s}");
+        // Through-hole pads are on both layers, the SMD pad on F.Cu only.
+        assert_eq!(s.matches(&format!("fill=\"{MULTI_LAYER_COLOR}\"")).count(), 2, "{s}");
+        assert_eq!(s.matches(&format!("fill=\"{}\"", LAYER_COLORS[0])).count(), 1, "{s}");
+        assert_eq!(s.matches(&format!("fill=\"{VIA_COLOR}\"")).count(), 1, "{s}");
+        assert_eq!(s.matches(&format!("stroke=\"{}\"", LAYER_COLORS[0])).count(), 1, "{s}");
+        assert_eq!(s.matches(&format!("stroke=\"{}\"", LAYER_COLORS[1])).count(), 1, "{s}");
+    }
+}
@@ -0,0 +1,233 @@
+use eyre::{eyre, Result};
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::{circ, path, poly, pt, rt, ShapeOps};
+
+use crate::dsn::sexp::{self, Sexp};
+use crate::error::MemerouteError;
+use crate::model::pcb::{LayerSet, LayerShape, Padstack, Pcb, Via, Wire};
+use crate::name::Id;
+
+// Reads the routed wires and vias of a Specctra session file, like those
+// written by |PcbToSession|, and adds them to |pcb|. Nets, layers, components
+// and via padstacks are matched by name against |pcb|, and any the session
+// refers to which aren't on the board are errors. Existing wires and vias are
+// kept, so this is usually applied to an unrouted board.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct SessionToPcb {
+    pcb: Pcb,
+    data: String,
+    mm: f64, // Millimetres per session unit.
+}
+
+impl SessionToPcb {
+    pub fn new(pcb: Pcb, data: String) -> Self {
+        Self { pcb, data, mm: 1.0 }
+    }
+
+    fn dimension_mm(d: &str) -> Result<f64> {
+        Ok(match d {
+            "inch" => 25.4,
+            "mil" => 0.0254,
+            "cm" => 10.0,
+            "mm" => 1.0,
+            "um" => 0.001,
+            _ => return Err(eyre!("unknown dimension {}", d)),
+        })
+    }
+
+    // Session coordinates are integers in units of the resolution.
+    fn resolution(&mut self, v: &Sexp) -> Result<()> {
+        match v.args() {
+            [d, n] => self.mm = Self::dimension_mm(d.atom()?)? / n.num()?,
+            _ => return Err(eyre!("bad {v}")),
+        }
+        Ok(())
+    }
+
+    fn coord(&self, v: &Sexp) -> Result<f64> {
+        Ok(v.num()? * self.mm)
+    }
+
+    fn pts(&self, v: &[Sexp]) -> Result<Vec<Pt>> {
+        if let Some(last) = v.last().filter(|_| v.len() % 2 != 0) {
+            return Err(eyre!("odd number of coordinates, ending with {last}"));
+        }
+        v.chunks(2).map(|p| Ok(pt(self.coord(&p[0])?, self.coord(&p[1])?))).collect()
+    }
+
+    fn find(&self, mut ids: impl Iterator<Item = Id>, name: &str) -> Option<Id> {
+        ids.find(|&id| self.pcb.to_name(id) == name)
+    }
+
+    fn net(&self, v: &Sexp) -> Result<Id> {
+        let name = v.atom()?;
+        self.find(self.pcb.nets().map(|n| n.id), name)
+            .ok_or_else(|| eyre!("session net {v} is not on the board"))
+    }
+
+    fn layer(&self, v: &Sexp) -> Result<LayerSet> {
+        let name = v.atom()?;
+        self.pcb
+            .layers()
+            .iter()
+            .find(|l| self.pcb.to_name(l.name_id) == name)
+            .map(|l| LayerSet::one(l.layer_id))
+            .ok_or_else(|| eyre!("session layer {v} is not on the board"))
+    }
+
+    fn padstack(&self, v: &Sexp) -> Result<Padstack> {
+        let name = v.atom()?;
+        self.pcb
+            .via_padstacks()
+            .iter()
+            .find(|ps| self.pcb.to_name(ps.id) == name)
+            .cloned()
+            .ok_or_else(|| eyre!("session via padstack {v} is not on the board"))
+    }
+
+    fn shape(&self, v: &Sexp) -> Result<LayerShape> {
+        let args = v.args();
+        let Some((layer, args)) = args.split_first() else {
+            return Err(eyre!("missing layer in {v}"));
+        };
+        let layers = self.layer(layer)?;
+        let shape = match (v.head(), args) {
+            (Some("path"), [w, rest @ ..]) => path(&self.pts(rest)?, self.coord(w)? / 2.0).shape(),
+            (Some("circle"), [d, x, y]) => {
+                circ(pt(self.coord(x)?, self.coord(y)?), self.coord(d)? / 2.0).shape()
+            }
+            (Some("polygon"), [_, rest @ ..]) => poly(&self.pts(rest)?).shape(),
+            (Some("rect"), [l, b, r, t]) => {
+                rt(self.coord(l)?, self.coord(b)?, self.coord(r)?, self.coord(t)?).shape()
+            }
+            _ => return Err(eyre!("unsupported wire shape {v}")),
+        };
+        Ok(LayerShape { layers, shape })
+    }
+
+    fn placement(&self, v: &Sexp) -> Result<()> {
+        for c in v.children("component") {
+            for place in c.children("place") {
+                let name =
+                    place.args().first().ok_or_else(|| eyre!("missing component in {place}"))?;
+                if self.find(self.pcb.components().map(|v| v.id), name.atom()?).is_none() {
+                    return Err(eyre!("session component {name} is not on the board"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn network(&mut self, v: &Sexp) -> Result<()> {
+        for net in v.children("net") {
+            let name = net.args().first().ok_or_else(|| eyre!("missing net name in {net}"))?;
+            let net_id = self.net(name)?;
+            for wire in net.children("wire") {
+                let shape = wire.args().first().ok_or_else(|| eyre!("empty {wire}"))?;
+                let shape = self.shape(shape)?;
+                self.pcb.add_wire(Wire { shape, net_id });
+            }
+            for via in net.children("via") {
+                let [ps, x, y, ..] = via.args() else {
+                    return Err(eyre!("bad {via}"));
+                };
+                let padstack = self.padstack(ps)?;
+                let p = pt(self.coord(x)?, self.coord(y)?);
+                self.pcb.add_via(Via { p, padstack, net_id });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn convert(self) -> Result<Pcb, MemerouteError> {
+        self.convert_session().map_err(MemerouteError::Session)
+    }
+
+    fn convert_session(mut self) -> Result<Pcb> {
+        let session = sexp::parse(&self.data)?;
+        if session.head() != Some("session") {
+            return Err(eyre!("not a session file"));
+        }
+        if let Ok(placement) = session.child("placement") {
+            if let Ok(res) = placement.child("resolution") {
+                self.resolution(res)?;
+            }
+            self.placement(placement)?;
+        }
+        let routes = session.child("routes")?;
+        // The routes resolution takes precedence over the placement one.
+        if let Ok(res) = routes.child("resolution") {
+            self.resolution(res)?;
+        }
+        if let Ok(network) = routes.child("network_out") {
+            self.network(network)?;
+        }
+        Ok(self.pcb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+    use memegeom::primitive::shape::Shape;
+
+    use super::SessionToPcb;
+    use crate::dsn::pcb_to_session::PcbToSession;
+    use crate::geom::math::pt_eq;
+    use crate::model::pcb::{LayerKind, Padstack, Pcb, Via};
+    use crate::testing::{add_component, add_net, board, round, wire};
+
+    // Board with names that need quoting, and that board with a wire on each
+    // layer and a via between them.
+    fn quoted_names() -> (Pcb, Pcb) {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "J (1)", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "J2", pt(8.0, 5.0), &[pt(0.0, 0.0)]);
+        let net_id = add_net(&mut pcb, r#"say "hi" \ (x)"#, &[("J (1)", "1"), ("J2", "1")]);
+        let all = pcb.layers_by_kind(LayerKind::All);
+        let padstack =
+            Padstack { id: pcb.to_id("via \"A\""), shapes: vec![round(all)], attach: false };
+        pcb.add_via_padstack(padstack.clone());
+        let base = pcb.clone();
+        pcb.add_wire(wire(net_id, 0, &[pt(2.0, 5.0), pt(5.0, 5.0)]));
+        pcb.add_wire(wire(net_id, 1, &[pt(5.0, 5.0), pt(8.0, 5.0)]));
+        pcb.add_via(Via { p: pt(5.0, 5.0), padstack, net_id });
+        (base, pcb)
+    }
+
+    #[test]
+    fn quoted_names_round_trip() {
+        let (base, pcb) = quoted_names();
+        let ses = PcbToSession::new(pcb.clone()).convert().unwrap();
+        let loaded = SessionToPcb::new(base, ses).convert().unwrap();
+        assert_eq!(loaded.wires().len(), 2);
+        for (a, b) in loaded.wires().iter().zip(pcb.wires()) {
+            assert_eq!(a.net_id, b.net_id);
+            assert_eq!(a.shape.layers, b.shape.layers);
+            let (Shape::Path(pa), Shape::Path(pb)) = (&a.shape.shape, &b.shape.shape) else {
+                panic!("expected paths");
+            };
+            assert!(pa.pts().iter().zip(pb.pts()).all(|(&p, &q)| pt_eq(p, q)));
+        }
+        assert_eq!(loaded.vias().len(), 1);
+        let (a, b) = (&loaded.vias()[0], &pcb.vias()[0]);
+        assert!(pt_eq(a.p, b.p));
+        assert_eq!((a.net_id, a.padstack.id), (b.net_id, b.padstack.id));
+    }
+
+    #[test]
+    fn errors_give_position() {
+        let (base, _) = quoted_names();
+        let err =
+            |ses: &str| SessionToPcb::new(base.clone(), ses.to_owned()).convert().unwrap_err();
+        let ses = "(session test\n  (routes\n    (network_out (net \"N 2\"))))";
+        let want = "session net N 2 at line 3, column 23 is not on the board";
+        assert!(err(ses).to_string().contains(want), "{}", err(ses));
+        // The escaped quotes in the net name don't end it early.
+        let ses = r#"(session test (routes (network_out (net "say \"hi\" \\ (x)"
+  (wire (arc F.Cu 100 0 0 1 1 2 2))))))"#;
+        let want = "unsupported wire shape (arc at line 2, column 9";
+        assert!(err(ses).to_string().contains(want), "{}", err(ses));
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use eyre::{eyre, Result};
+
+// Line and column of a character in a file, both counting from 1.
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.col)
+    }
+}
+
+// S-expression with where each atom and list starts, so errors about it can
+// say where to look.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexp {
+    Atom(String, Pos),
+    List(Vec<Sexp>, Pos),
+}
+
+impl Sexp {
+    pub fn pos(&self) -> Pos {
+        match self {
+            Sexp::Atom(_, pos) | Sexp::List(_, pos) => *pos,
+        }
+    }
+
+    pub fn atom(&self) -> Result<&str> {
+        match self {
+            Sexp::Atom(s, _) => Ok(s),
+            Sexp::List(..) => Err(eyre!("expected atom, got {}", self)),
+        }
+    }
+
+    pub fn num(&self) -> Result<f64> {
+        let s = self.atom()?;
+        s.parse().map_err(|_| eyre!("expected number, got {}", self))
+    }
+
+    // Name of a list, e.g. "wire" for (wire ...).
+    #[must_use]
+    pub fn head(&self) -> Option<&str> {
+        match self {
+            Sexp::List(v, _) => match v.first() {
+                Some(Sexp::Atom(s, _)) => Some(s),
+                _ => None,
+            },
+            Sexp::Atom(..) => None,
+        }
+    }
+
+    // Everything after the name of a list.
+    #[must_use]
+    pub fn args(&self) -> &[Sexp] {
+        match self {
+            Sexp::List(v, _) if !v.is_empty() => &v[1..],
+            _ => &[],
+        }
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Sexp> {
+        self.args().iter().filter(move |v| v.head() == Some(name))
+    }
+
+    pub fn child(&self, name: &str) -> Result<&Sexp> {
+        self.children(name).next().ok_or_else(|| eyre!("missing ({name} ...) in {}", self))
+    }
+}
+
+// Where |self| is, for error messages, e.g. "(wire at line 3, column 5".
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, self.head()) {
+            (Sexp::Atom(s, pos), _) => write!(f, "{s} at {pos}"),
+            (Sexp::List(..), Some(head)) => write!(f, "({head} at {}", self.pos()),
+            (Sexp::List(..), None) => write!(f, "( at {}", self.pos()),
+        }
+    }
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: Pos,
+    quote: char,
+}
+
+impl Reader<'_> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.pos = Pos { line: self.pos.line + 1, col: 1 };
+        } else {
+            self.pos.col += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_space(&mut self) {
+        while self.chars.peek().map_or(false, |c| c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    // Rest of a quoted string starting at |st|, after the opening quote.
+    fn string(&mut self, st: Pos) -> Result<String> {
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('\\') => {
+                    s.push(self.next().ok_or_else(|| eyre!("unterminated string at {st}"))?)
+                }
+                Some(c) if c == self.quote => return Ok(s),
+                Some(c) => s.push(c),
+                None => return Err(eyre!("unterminated string at {st}")),
+            }
+        }
+    }
+
+    fn atom(&mut self) -> String {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' {
+                break;
+            }
+            s.push(c);
+            self.next();
+        }
+        s
+    }
+}
+
+// Parses |data| as a single top level list. Quoted strings may hold spaces
+// and parentheses, and a backslash in one escapes the next character. Like
+// Specctra designs, (string_quote <c>) changes the quote character for the
+// rest of the file. Errors give the line and column they're at.
+// A missing ) usually only shows up at the end of the file, where it's the
+// outermost list that's left open. So if a ) starting a line closed a list
+// that starts further right, which in an indented file means it was meant
+// for an outer list, the error points at the first such list too.
+pub fn parse(data: &str) -> Result<Sexp> {
+    let mut r = Reader { chars: data.chars().peekable(), pos: Pos { line: 1, col: 1 }, quote: '"' };
+    // Lists not closed yet, innermost last.
+    let mut open: Vec<(Vec<Sexp>, Pos)> = Vec::new();
+    let mut top = None;
+    let mut prev_line = 0;
+    let mut misclosed = None;
+    loop {
+        r.skip_space();
+        let pos = r.pos;
+        let Some(&c) = r.chars.peek() else { break };
+        let starts_line = pos.line != prev_line;
+        prev_line = pos.line;
+        let sets_quote = matches!(
+            open.last().map(|v| v.0.as_slice()),
+            Some([Sexp::Atom(s, _)]) if s == "string_quote"
+        );
+        let v = match c {
+            '(' => {
+                r.next();
+                open.push((Vec::new(), pos));
+                continue;
+            }
+            ')' => {
+                r.next();
+                let (v, st) = open.pop().ok_or_else(|| eyre!("unexpected ) at {pos}"))?;
+                let v = Sexp::List(v, st);
+                if starts_line && pos.col < st.col && misclosed.is_none() {
+                    misclosed = Some(v.to_string());
+                }
+                v
+            }
+            // The quote character itself follows string_quote, unquoted.
+            _ if sets_quote => {
+                let s = r.atom();
+                r.quote = s.chars().next().unwrap_or(r.quote);
+                Sexp::Atom(s, pos)
+            }
+            c if c == r.quote => {
+                r.next();
+                Sexp::Atom(r.string(pos)?, pos)
+            }
+            _ => Sexp::Atom(r.atom(), pos),
+        };
+        match open.last_mut() {
+            Some((list, _)) => list.push(v),
+            None if top.is_none() => top = Some(v),
+            None => return Err(eyre!("unexpected {v} after the top level list")),
+        }
+    }
+    if let Some((list, st)) = open.pop() {
+        let v = Sexp::List(list, st);
+        return Err(match misclosed {
+            Some(m) => eyre!("unterminated {v}; {m} may be missing its )"),
+            None => eyre!("unterminated {v}"),
+        });
+    }
+    top.ok_or_else(|| eyre!("no s-expression found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Pos, Sexp};
+
+    fn atom(s: &str, line: usize, col: usize) -> Sexp {
+        Sexp::Atom(s.to_owned(), Pos { line, col })
+    }
+
+    #[test]
+    fn nested_lists_with_positions() {
+        let v = parse("(pcb x\n  (net N1 12)\n)").unwrap();
+        let want = Sexp::List(
+            vec![
+                atom("pcb", 1, 2),
+                atom("x", 1, 6),
+                Sexp::List(
+                    vec![atom("net", 2, 4), atom("N1", 2, 8), atom("12", 2, 11)],
+                    Pos { line: 2, col: 3 },
+                ),
+            ],
+            Pos { line: 1, col: 1 },
+        );
+        assert_eq!(v, want);
+    }
+
+    #[test]
+    fn quoted_strings_and_escapes() {
+        let v = parse(r#"(net "A (B) C" "say \"hi\"" "back\\slash" "")"#).unwrap();
+        let Sexp::List(items, _) = v else { panic!("expected a list") };
+        let atoms: Vec<_> = items
+            .iter()
+            .map(|v| match v {
+                Sexp::Atom(s, _) => s.as_str(),
+                Sexp::List(..) => panic!("expected an atom"),
+            })
+            .collect();
+        assert_eq!(atoms, ["net", "A (B) C", "say \"hi\"", "back\\slash", ""]);
+    }
+
+    #[test]
+    fn string_quote_changes_quote() {
+        let v = parse("(pcb (parser (string_quote ')) (net 'A \"B\"' \"C\"))").unwrap();
+        let Sexp::List(items, _) = v else { panic!("expected a list") };
+        let Sexp::List(net, _) = &items[2] else { panic!("expected a list") };
+        assert_eq!(net[1], atom("A \"B\"", 1, 37));
+        assert_eq!(net[2], atom("\"C\"", 1, 45));
+        // The Specctra default, which would otherwise open a string.
+        assert!(parse("(pcb (parser (string_quote \")) (net \"A B\"))").is_ok());
+    }
+
+    #[test]
+    fn errors_give_line_and_column() {
+        let err = |data: &str| parse(data).unwrap_err().to_string();
+        // The (structure list on line 2 is never closed.
+        let data = "(pcb test.dsn\n  (structure\n    (layer F.Cu (type signal))\n";
+        assert_eq!(err(data), "unterminated (structure at line 2, column 3");
+        // Closing (b with a ) meant for (a, going by the indentation.
+        let data = "(a\n  (b\n    (c)\n  (d)\n)\n";
+        let want =
+            "unterminated (a at line 1, column 1; (b at line 2, column 3 may be missing its )";
+        assert_eq!(err(data), want);
+        assert_eq!(err("(a (b))\n  )"), "unexpected ) at line 2, column 3");
+        assert_eq!(err("(a\n (b \"c d))"), "unterminated string at line 2, column 5");
+        assert_eq!(err("(a) b"), "unexpected b at line 1, column 5 after the top level list");
+        assert_eq!(err("  \n"), "no s-expression found");
+    }
+}
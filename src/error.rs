@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
+
+use crate::model::pcb::LayerId;
+use crate::name::Id;
+
+// Errors returned by the public entry points of the library. Internals use
+// eyre, and the underlying report is kept so no context is lost. Since this
+// implements Error, it can be propagated into eyre with `?`.
+#[derive(Debug)]
+pub enum MemerouteError {
+    // The DSN input was malformed or referenced unknown objects.
+    Dsn(eyre::Report),
+    // The router could not run to completion. Note that nets that could not
+    // be routed are not an error, they are reported in the route result.
+    Route(eyre::Report),
+    // Reading or writing |path| failed.
+    Io { path: PathBuf, source: io::Error },
+    // A session file was malformed or referred to objects not on the board.
+    Session(eyre::Report),
+    // The board could not be written out, e.g. because it has shapes the
+    // output format can't represent.
+    Export(eyre::Report),
+}
+
+impl MemerouteError {
+    // The underlying report, for every kind except |Io|.
+    #[must_use]
+    pub fn report(&self) -> Option<&eyre::Report> {
+        match self {
+            Self::Dsn(r) | Self::Route(r) | Self::Session(r) | Self::Export(r) => Some(r),
+            Self::Io { .. } => None,
+        }
+    }
+
+    // Adds |msg| as context to the underlying report, keeping the kind.
+    #[must_use]
+    pub fn wrap_err<D>(self, msg: D) -> Self
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Self::Dsn(r) => Self::Dsn(r.wrap_err(msg)),
+            Self::Route(r) => Self::Route(r.wrap_err(msg)),
+            Self::Session(r) => Self::Session(r.wrap_err(msg)),
+            Self::Export(r) => Self::Export(r.wrap_err(msg)),
+            e @ Self::Io { .. } => e,
+        }
+    }
+
+    pub(crate) fn io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        |source| Self::Io { path: path.to_path_buf(), source }
+    }
+}
+
+impl fmt::Display for MemerouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dsn(r) => write!(f, "invalid dsn: {r:#}"),
+            Self::Route(r) => write!(f, "routing failed: {r:#}"),
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Session(r) => write!(f, "invalid session: {r:#}"),
+            Self::Export(r) => write!(f, "export failed: {r:#}"),
+        }
+    }
+}
+
+impl Error for MemerouteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Problems found by |Pcb::validate|.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PcbError {
+    UnknownComponent { net_id: Id, component: Id },
+    UnknownPin { net_id: Id, component: Id, pin: Id },
+    EmptyPadstack { component: Id, pin: Id },
+    // |what| describes the object on the net, e.g. "via 2".
+    UnknownNet { net_id: Id, what: String },
+    UnknownImage { component: Id, image: Id },
+    UnknownViaPadstack { via: usize, padstack: Id },
+    // |what| describes the object using the layer, e.g. "wire 3".
+    UnknownLayer { layer: LayerId, what: String },
+}
+
+impl fmt::Display for PcbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownComponent { net_id, component } => {
+                write!(f, "net {net_id} refers to unknown component {component}")
+            }
+            Self::UnknownPin { net_id, component, pin } => {
+                write!(f, "net {net_id} refers to unknown pin {pin} on component {component}")
+            }
+            Self::EmptyPadstack { component, pin } => {
+                write!(f, "pin {pin} on component {component} has no pad shapes")
+            }
+            Self::UnknownNet { net_id, what } => write!(f, "{what} is on unknown net {net_id}"),
+            Self::UnknownImage { component, image } => {
+                write!(f, "component {component} refers to unknown image {image}")
+            }
+            Self::UnknownViaPadstack { via, padstack } => {
+                write!(f, "via {via} uses unknown via padstack {padstack}")
+            }
+            Self::UnknownLayer { layer, what } => write!(f, "{what} is on unknown layer {layer}"),
+        }
+    }
+}
+
+impl Error for PcbError {}
@@ -0,0 +1,329 @@
+use memegeom::primitive::circle::Circle;
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{pt, ShapeOps};
+
+use crate::geom::distance::{pt_seg_dist, seg_seg_dist};
+use crate::geom::intersects::seg_crosses_seg;
+use crate::geom::math::{cross_at, f64_eq, f64_ge, f64_le};
+use crate::geom::{circ_cap, path_caps, poly_edges, Cap, Seg};
+
+// Points on the boundary count as inside for all of these, matching the
+// intersection tests.
+
+#[must_use]
+pub fn pt_in_rt(p: Pt, r: &Rt) -> bool {
+    f64_le(r.l(), p.x) && f64_le(p.x, r.r()) && f64_le(r.b(), p.y) && f64_le(p.y, r.t())
+}
+
+#[must_use]
+pub fn pt_in_circ(p: Pt, c: &Circle) -> bool {
+    f64_le(p.dist(c.p()), c.r())
+}
+
+// |pts| is a polygon in either winding, without the first point repeated at
+// the end. Uses the winding number, so polygons that touch themselves at a
+// vertex, or overlap themselves like a pentagram, contain every point they go
+// around.
+#[must_use]
+pub fn pt_in_poly(p: Pt, pts: &[Pt]) -> bool {
+    if poly_edges(pts).any(|s| f64_eq(pt_seg_dist(p, &s), 0.0)) {
+        return true;
+    }
+    // Count edges crossing the ray to the right of |p|, upwards ones that
+    // pass left of |p| as +1 and downwards ones that pass right of it as -1.
+    // Points on the boundary were handled above, so |p| is never on an edge.
+    let mut winding = 0;
+    for Seg { st: a, en: b } in poly_edges(pts) {
+        if a.y <= p.y && b.y > p.y && cross_at(a, b, p) > 0.0 {
+            winding += 1;
+        } else if a.y > p.y && b.y <= p.y && cross_at(a, b, p) < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+// Whether the polygon |inner| is inside or on the boundary of |outer|. Both
+// are as for |pt_in_poly|. If no edges cross, each polygon is either inside
+// the other or apart from it, which one vertex of |inner| tells. Edges may
+// still touch without crossing, e.g. where |inner| runs along |outer|, so
+// the edge midpoints of |inner| are checked too.
+#[must_use]
+pub fn poly_in_poly(inner: &[Pt], outer: &[Pt]) -> bool {
+    if poly_edges(inner).any(|a| poly_edges(outer).any(|b| seg_crosses_seg(&a, &b))) {
+        return false;
+    }
+    inner.first().map_or(true, |&v| pt_in_poly(v, outer))
+        && poly_edges(inner).all(|s| pt_in_poly(s.point_at(0.5), outer))
+}
+
+// Whether |p| is inside or on the edge of |s|. Shapes without area never
+// contain anything.
+#[must_use]
+pub fn pt_in_shape(p: Pt, s: &Shape) -> bool {
+    match s {
+        Shape::Rect(s) => pt_in_rt(p, s),
+        Shape::Circle(s) => pt_in_circ(p, s),
+        Shape::Path(s) => path_caps(s).any(|c| f64_le(pt_seg_dist(p, &c.seg), c.r)),
+        Shape::Polygon(s) => pt_in_poly(p, s.pts()),
+        _ => false,
+    }
+}
+
+// A capsule is in the polygon if its segment is, and every edge is at least
+// its radius away from the segment.
+fn cap_in_poly(c: &Cap, pts: &[Pt]) -> bool {
+    poly_in_poly(&[c.seg.st, c.seg.en], pts)
+        && poly_edges(pts).all(|e| f64_ge(seg_seg_dist(&e, &c.seg), c.r))
+}
+
+// Whether all of |inner| is inside or on the edge of |outer|. Boundaries are
+// the only shapes things need to stay inside, and those are filled rects,
+// circles, or polygons.
+#[must_use]
+pub fn shape_in_shape(inner: &Shape, outer: &Shape) -> bool {
+    match outer {
+        Shape::Rect(r) => {
+            let b = inner.bounds();
+            pt_in_rt(pt(b.l(), b.b()), r) && pt_in_rt(pt(b.r(), b.t()), r)
+        }
+        Shape::Circle(c) => {
+            let cap_in = |v: &Cap| {
+                f64_le(v.seg.st.dist(c.p()) + v.r, c.r())
+                    && f64_le(v.seg.en.dist(c.p()) + v.r, c.r())
+            };
+            match inner {
+                Shape::Circle(v) => cap_in(&circ_cap(v)),
+                Shape::Path(v) => path_caps(v).all(|v| cap_in(&v)),
+                Shape::Rect(v) => v.pts().iter().all(|&p| pt_in_circ(p, c)),
+                Shape::Polygon(v) => v.pts().iter().all(|&p| pt_in_circ(p, c)),
+                _ => false,
+            }
+        }
+        Shape::Polygon(outer) => match inner {
+            Shape::Circle(v) => cap_in_poly(&circ_cap(v), outer.pts()),
+            Shape::Path(v) => path_caps(v).all(|v| cap_in_poly(&v, outer.pts())),
+            Shape::Rect(v) => poly_in_poly(&v.pts(), outer.pts()),
+            Shape::Polygon(v) => poly_in_poly(v.pts(), outer.pts()),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::{circ, path, poly, pt, rt, ShapeOps};
+
+    use super::{poly_in_poly, pt_in_circ, pt_in_poly, pt_in_rt, pt_in_shape, shape_in_shape};
+    use crate::geom::Tri;
+
+    #[test]
+    fn rect_circle_tri() {
+        let r = rt(0.0, 0.0, 2.0, 1.0);
+        assert!(pt_in_rt(pt(1.0, 0.5), &r));
+        assert!(pt_in_rt(pt(2.0, 1.0), &r));
+        assert!(pt_in_rt(pt(0.0, 0.5), &r));
+        assert!(!pt_in_rt(pt(2.1, 0.5), &r));
+
+        let c = circ(pt(1.0, 1.0), 1.0);
+        assert!(pt_in_circ(pt(1.5, 1.5), &c));
+        assert!(pt_in_circ(pt(2.0, 1.0), &c));
+        assert!(!pt_in_circ(pt(1.8, 1.8), &c));
+
+        let t = Tri::new(pt(0.0, 0.0), pt(2.0, 0.0), pt(0.0, 2.0));
+        assert!(t.contains(pt(0.5, 0.5)));
+        assert!(t.contains(pt(1.0, 1.0)));
+        assert!(!t.contains(pt(1.1, 1.1)));
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // A U shape opening upwards, clockwise.
+        let u = [
+            pt(0.0, 0.0),
+            pt(0.0, 3.0),
+            pt(1.0, 3.0),
+            pt(1.0, 1.0),
+            pt(2.0, 1.0),
+            pt(2.0, 3.0),
+            pt(3.0, 3.0),
+            pt(3.0, 0.0),
+        ];
+        // Strictly inside each arm and the base.
+        assert!(pt_in_poly(pt(0.5, 2.5), &u));
+        assert!(pt_in_poly(pt(2.5, 2.5), &u));
+        assert!(pt_in_poly(pt(1.5, 0.5), &u));
+        // In the notch, level with the inner vertices, and outside.
+        assert!(!pt_in_poly(pt(1.5, 2.0), &u));
+        assert!(!pt_in_poly(pt(1.5, 3.0), &u));
+        assert!(!pt_in_poly(pt(4.0, 1.0), &u));
+        assert!(!pt_in_poly(pt(-1.0, 1.0), &u));
+        // On vertices, including the concave ones, and on edges.
+        assert!(pt_in_poly(pt(0.0, 0.0), &u));
+        assert!(pt_in_poly(pt(1.0, 1.0), &u));
+        assert!(pt_in_poly(pt(2.0, 3.0), &u));
+        assert!(pt_in_poly(pt(1.5, 1.0), &u));
+        assert!(pt_in_poly(pt(3.0, 2.0), &u));
+        // Winding doesn't matter.
+        let mut rev = u;
+        rev.reverse();
+        assert!(pt_in_poly(pt(0.5, 2.5), &rev));
+        assert!(!pt_in_poly(pt(1.5, 2.0), &rev));
+    }
+
+    // Polygon, point, and whether the point is in the polygon.
+    #[allow(clippy::type_complexity)]
+    const POLY_PT_TESTS: &[(&[(f64, f64)], (f64, f64), bool)] = &[
+        // A five pointed star: in a point, the middle, and the notch between
+        // two points. Then on the tip of a point and on a concave corner.
+        (STAR, (0.0, 0.8), true),
+        (STAR, (0.0, 0.0), true),
+        (STAR, (0.0, -0.6), false),
+        (STAR, (0.0, 1.0), true),
+        (STAR, (0.0, -0.4), true),
+        // Two squares joined at the coincident vertex (1, 1).
+        (BOW, (0.5, 0.5), true),
+        (BOW, (1.5, 1.5), true),
+        (BOW, (1.0, 1.0), true),
+        (BOW, (1.5, 0.5), false),
+        (BOW, (0.5, 1.5), false),
+        // A pentagram overlaps itself, so its middle is wound around twice.
+        // Even-odd counting would leave it out.
+        (PENTAGRAM, (0.0, 0.0), true),
+        (PENTAGRAM, (0.0, 0.8), true),
+        (PENTAGRAM, (0.0, -0.9), false),
+    ];
+
+    const STAR: &[(f64, f64)] = &[
+        (0.0, 1.0),
+        (0.235, 0.324),
+        (0.951, 0.309),
+        (0.38, -0.124),
+        (0.588, -0.809),
+        (0.0, -0.4),
+        (-0.588, -0.809),
+        (-0.38, -0.124),
+        (-0.951, 0.309),
+        (-0.235, 0.324),
+    ];
+
+    const BOW: &[(f64, f64)] = &[
+        (0.0, 0.0),
+        (1.0, 0.0),
+        (1.0, 1.0),
+        (2.0, 1.0),
+        (2.0, 2.0),
+        (1.0, 2.0),
+        (1.0, 1.0),
+        (0.0, 1.0),
+    ];
+
+    const PENTAGRAM: &[(f64, f64)] =
+        &[(0.0, 1.0), (0.588, -0.809), (-0.951, 0.309), (0.951, 0.309), (-0.588, -0.809)];
+
+    fn pts(v: &[(f64, f64)]) -> Vec<Pt> {
+        v.iter().map(|&(x, y)| pt(x, y)).collect()
+    }
+
+    #[test]
+    fn polygon_table() {
+        for &(poly, (x, y), want) in POLY_PT_TESTS {
+            let mut poly = pts(poly);
+            for _ in 0..2 {
+                assert_eq!(pt_in_poly(pt(x, y), &poly), want, "({x}, {y}) in {poly:?}");
+                poly.reverse();
+            }
+        }
+    }
+
+    #[test]
+    fn polygon_in_polygon() {
+        let star = pts(STAR);
+        let square = |l: f64, b: f64, r: f64, t: f64| [pt(l, b), pt(r, b), pt(r, t), pt(l, t)];
+        // In the middle of the star, and reaching into its points.
+        assert!(poly_in_poly(&square(-0.2, -0.2, 0.2, 0.2), &star));
+        assert!(!poly_in_poly(&square(-0.35, -0.35, 0.35, 0.35), &star));
+        // A star is in itself, and in a square around it, but not the other
+        // way round.
+        assert!(poly_in_poly(&star, &star));
+        assert!(poly_in_poly(&star, &square(-1.0, -1.0, 1.0, 1.0)));
+        assert!(!poly_in_poly(&square(-1.0, -1.0, 1.0, 1.0), &star));
+        // Running along an edge of the outer polygon from inside, and
+        // spanning the notch of a U without crossing its edges.
+        assert!(poly_in_poly(&square(0.0, 0.0, 1.0, 1.0), &square(0.0, 0.0, 2.0, 2.0)));
+        let u = [
+            pt(0.0, 0.0),
+            pt(3.0, 0.0),
+            pt(3.0, 3.0),
+            pt(2.0, 3.0),
+            pt(2.0, 1.0),
+            pt(1.0, 1.0),
+            pt(1.0, 3.0),
+            pt(0.0, 3.0),
+        ];
+        assert!(!poly_in_poly(&square(1.0, 1.0, 2.0, 3.0), &u));
+        assert!(poly_in_poly(&square(0.0, 0.0, 3.0, 1.0), &u));
+        // Off to the side.
+        assert!(!poly_in_poly(&square(5.0, 5.0, 6.0, 6.0), &u));
+    }
+
+    #[test]
+    fn shapes() {
+        assert!(pt_in_shape(pt(2.0, 1.0), &rt(0.0, 0.0, 2.0, 1.0).shape()));
+        assert!(!pt_in_shape(pt(2.0, 1.1), &rt(0.0, 0.0, 2.0, 1.0).shape()));
+        assert!(pt_in_shape(pt(1.0, 2.0), &circ(pt(1.0, 1.0), 1.0).shape()));
+        let p = path(&[pt(0.0, 0.0), pt(2.0, 0.0), pt(2.0, 2.0)], 0.5).shape();
+        assert!(pt_in_shape(pt(1.0, 0.5), &p));
+        assert!(pt_in_shape(pt(2.5, 2.0), &p));
+        assert!(!pt_in_shape(pt(1.0, 1.0), &p));
+        // A single point path is a circle.
+        let dot = path(&[pt(1.0, 1.0)], 0.5).shape();
+        assert!(pt_in_shape(pt(1.0, 1.5), &dot));
+        assert!(!pt_in_shape(pt(1.0, 1.6), &dot));
+        let tri = poly(&[pt(0.0, 0.0), pt(2.0, 0.0), pt(0.0, 2.0)]).shape();
+        assert!(pt_in_shape(pt(0.5, 0.5), &tri));
+        assert!(!pt_in_shape(pt(1.5, 1.5), &tri));
+    }
+
+    #[test]
+    fn shape_in_boundary() {
+        // A U opening upwards, as a board outline.
+        let u = poly(&[
+            pt(0.0, 0.0),
+            pt(3.0, 0.0),
+            pt(3.0, 3.0),
+            pt(2.0, 3.0),
+            pt(2.0, 1.0),
+            pt(1.0, 1.0),
+            pt(1.0, 3.0),
+            pt(0.0, 3.0),
+        ])
+        .shape();
+        let tests = [
+            // In the left arm, touching its outside edge, and poking out of it.
+            (circ(pt(0.5, 2.0), 0.5).shape(), true),
+            (circ(pt(0.5, 2.0), 0.6).shape(), false),
+            (rt(0.0, 0.0, 3.0, 1.0).shape(), true),
+            // Across the base, and bridging the notch between the arms.
+            (path(&[pt(0.5, 0.5), pt(2.5, 0.5)], 0.5).shape(), true),
+            (path(&[pt(0.5, 2.0), pt(2.5, 2.0)], 0.1).shape(), false),
+            (path(&[pt(0.5, 0.5), pt(0.5, 2.5), pt(2.5, 2.5)], 0.1).shape(), false),
+            // Around the whole board.
+            (rt(-1.0, -1.0, 4.0, 4.0).shape(), false),
+        ];
+        for (s, want) in tests {
+            assert_eq!(shape_in_shape(&s, &u), want, "{s:?}");
+        }
+
+        let r = rt(0.0, 0.0, 2.0, 2.0).shape();
+        assert!(shape_in_shape(&circ(pt(1.0, 1.0), 1.0).shape(), &r));
+        assert!(!shape_in_shape(&path(&[pt(1.0, 1.0), pt(1.5, 1.0)], 0.6).shape(), &r));
+        let c = circ(pt(0.0, 0.0), 2.0).shape();
+        assert!(shape_in_shape(&path(&[pt(-1.0, 0.0), pt(1.0, 0.0)], 1.0).shape(), &c));
+        assert!(!shape_in_shape(&rt(-1.5, -1.5, 1.5, 1.5).shape(), &c));
+    }
+}
@@ -0,0 +1,295 @@
+use memegeom::primitive::path_shape::Path;
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::ShapeOps;
+
+use crate::geom::contains::pt_in_poly;
+use crate::geom::intersects::{
+    rt_intersects_rt, rt_intersects_seg, seg_intersects_seg, shape_intersects_shape,
+};
+use crate::geom::math::{cross, dot, EP};
+use crate::geom::{circ_cap, path_caps, poly_edges, Cap, Line, Seg};
+
+// Perpendicular distance from |p| to the infinite line |l|. A degenerate
+// line is treated as its start point.
+#[must_use]
+pub fn line_pt_dist(l: &Line, p: Pt) -> f64 {
+    let d = l.dir();
+    let len = d.x.hypot(d.y);
+    if len <= 0.0 {
+        return l.st.dist(p);
+    }
+    (cross(d, p - l.st) / len).abs()
+}
+
+// Point on the segment ab nearest to |p|.
+#[must_use]
+pub fn seg_nearest_pt(a: Pt, b: Pt, p: Pt) -> Pt {
+    let ab = b - a;
+    let len2 = dot(ab, ab);
+    if len2 <= 0.0 {
+        return a;
+    }
+    let t = (dot(p - a, ab) / len2).clamp(0.0, 1.0);
+    a + t * ab
+}
+
+#[must_use]
+pub fn pt_seg_dist(p: Pt, s: &Seg) -> f64 {
+    p.dist(seg_nearest_pt(s.st, s.en, p))
+}
+
+// Distances between shapes are 0 when they intersect.
+
+#[must_use]
+pub fn seg_seg_dist(a: &Seg, b: &Seg) -> f64 {
+    if !seg_bounds_apart(a, b) && seg_intersects_seg(a, b) {
+        return 0.0;
+    }
+    // Disjoint segments are closest at an end of one of them.
+    let d =
+        [pt_seg_dist(a.st, b), pt_seg_dist(a.en, b), pt_seg_dist(b.st, a), pt_seg_dist(b.en, a)];
+    d.into_iter().fold(f64::INFINITY, f64::min)
+}
+
+// Segments whose bounds are more than |EP| apart can't intersect, which
+// skips the orientation tests for most pairs on a board. The margin keeps the
+// result the same as |seg_intersects_seg|, which allows |EP| of slack.
+fn seg_bounds_apart(a: &Seg, b: &Seg) -> bool {
+    let (ra, rb) = (Rt::enclosing(a.st, a.en), Rt::enclosing(b.st, b.en));
+    ra.r() + EP < rb.l() || rb.r() + EP < ra.l() || ra.t() + EP < rb.b() || rb.t() + EP < ra.b()
+}
+
+#[must_use]
+pub fn rt_seg_dist(r: &Rt, s: &Seg) -> f64 {
+    if rt_intersects_seg(r, s) {
+        return 0.0;
+    }
+    let pts = r.pts();
+    (0..4)
+        .map(|i| seg_seg_dist(&Seg::new(pts[i], pts[(i + 1) % 4]), s))
+        .fold(f64::INFINITY, f64::min)
+}
+
+// Clearance from the edge of the capsule to the rect. |rt_seg_dist| returns
+// early when the segment touches the rect, so overlaps are cheap.
+#[must_use]
+pub fn cap_rt_dist(c: &Cap, r: &Rt) -> f64 {
+    (rt_seg_dist(r, &c.seg) - c.r).max(0.0)
+}
+
+// Clearance from the edge of the path's copper to the rect.
+#[must_use]
+pub fn rt_path_dist(r: &Rt, p: &Path) -> f64 {
+    let mut best = f64::INFINITY;
+    for cap in path_caps(p) {
+        best = best.min(cap_rt_dist(&cap, r));
+        if best <= 0.0 {
+            break;
+        }
+    }
+    best
+}
+
+// Clearance from the edge of the capsule to the polygon |pts|. A segment
+// that starts outside and gets into the polygon crosses an edge, so only
+// one that starts inside needs the containment test.
+#[must_use]
+pub fn cap_poly_dist(c: &Cap, pts: &[Pt]) -> f64 {
+    if pt_in_poly(c.seg.st, pts) {
+        return 0.0;
+    }
+    let d = poly_edges(pts).map(|e| seg_seg_dist(&e, &c.seg)).fold(f64::INFINITY, f64::min);
+    (d - c.r).max(0.0)
+}
+
+#[must_use]
+pub fn path_poly_dist(pts: &[Pt], p: &Path) -> f64 {
+    let mut best = f64::INFINITY;
+    for cap in path_caps(p) {
+        best = best.min(cap_poly_dist(&cap, pts));
+        if best <= 0.0 {
+            break;
+        }
+    }
+    best
+}
+
+fn cap_cap_dist(a: &Cap, b: &Cap) -> f64 {
+    (seg_seg_dist(&a.seg, &b.seg) - a.r - b.r).max(0.0)
+}
+
+// Polygons that don't intersect are closest between a pair of edges.
+fn region_dist(a: &Shape, pa: &[Pt], b: &Shape, pb: &[Pt]) -> f64 {
+    if shape_intersects_shape(a, b) {
+        return 0.0;
+    }
+    poly_edges(pa)
+        .flat_map(|ea| poly_edges(pb).map(move |eb| seg_seg_dist(&ea, &eb)))
+        .fold(f64::INFINITY, f64::min)
+}
+
+// Clearance between the shapes used on a board, matching
+// |shape_intersects_shape| for what counts as touching. Anything else is left
+// to memegeom.
+#[must_use]
+pub fn shape_dist(a: &Shape, b: &Shape) -> f64 {
+    match (a, b) {
+        (Shape::Circle(x), Shape::Circle(y)) => cap_cap_dist(&circ_cap(x), &circ_cap(y)),
+        (Shape::Circle(c), Shape::Path(p)) | (Shape::Path(p), Shape::Circle(c)) => {
+            let c = circ_cap(c);
+            path_caps(p).map(|v| cap_cap_dist(&c, &v)).fold(f64::INFINITY, f64::min)
+        }
+        (Shape::Path(x), Shape::Path(y)) => path_caps(x)
+            .flat_map(|cx| path_caps(y).map(move |cy| cap_cap_dist(&cx, &cy)))
+            .fold(f64::INFINITY, f64::min),
+        (Shape::Rect(x), Shape::Rect(y)) => {
+            if rt_intersects_rt(x, y) {
+                return 0.0;
+            }
+            let dx = (x.l() - y.r()).max(y.l() - x.r()).max(0.0);
+            let dy = (x.b() - y.t()).max(y.b() - x.t()).max(0.0);
+            dx.hypot(dy)
+        }
+        (Shape::Rect(r), Shape::Circle(c)) | (Shape::Circle(c), Shape::Rect(r)) => {
+            cap_rt_dist(&circ_cap(c), r)
+        }
+        (Shape::Rect(r), Shape::Path(p)) | (Shape::Path(p), Shape::Rect(r)) => rt_path_dist(r, p),
+        (Shape::Polygon(x), Shape::Polygon(y)) => region_dist(a, x.pts(), b, y.pts()),
+        (Shape::Polygon(x), Shape::Rect(r)) | (Shape::Rect(r), Shape::Polygon(x)) => {
+            region_dist(a, x.pts(), b, &r.pts())
+        }
+        (Shape::Polygon(x), Shape::Circle(c)) | (Shape::Circle(c), Shape::Polygon(x)) => {
+            cap_poly_dist(&circ_cap(c), x.pts())
+        }
+        (Shape::Polygon(x), Shape::Path(p)) | (Shape::Path(p), Shape::Polygon(x)) => {
+            path_poly_dist(x.pts(), p)
+        }
+        _ => a.dist_to_shape(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::{circ, path, poly, pt, rt, ShapeOps};
+
+    use super::{
+        cap_rt_dist, line_pt_dist, pt_seg_dist, rt_path_dist, rt_seg_dist, seg_nearest_pt,
+        seg_seg_dist, shape_dist,
+    };
+    use crate::geom::math::{f64_eq, lerp, pt_eq};
+    use crate::geom::{Cap, Line, Seg};
+
+    #[test]
+    fn line_distance_is_perpendicular() {
+        let l = Line::new(pt(0.0, 0.0), pt(1.0, 0.0));
+        assert!(f64_eq(line_pt_dist(&l, pt(5.0, 2.0)), 2.0));
+        assert!(f64_eq(line_pt_dist(&l, pt(-5.0, -3.0)), 3.0));
+    }
+
+    #[test]
+    fn segment_distance_clamps_to_ends() {
+        let s = Seg::new(pt(0.0, 0.0), pt(2.0, 0.0));
+        assert!(pt_eq(seg_nearest_pt(s.st, s.en, pt(1.0, 1.0)), pt(1.0, 0.0)));
+        assert!(pt_eq(seg_nearest_pt(s.st, s.en, pt(5.0, 4.0)), pt(2.0, 0.0)));
+        assert!(f64_eq(pt_seg_dist(pt(5.0, 4.0), &s), 5.0));
+        let p = Seg::new(pt(1.0, 1.0), pt(1.0, 1.0));
+        assert!(f64_eq(pt_seg_dist(pt(4.0, 5.0), &p), 5.0));
+    }
+
+    #[test]
+    fn segment_segment() {
+        let a = Seg::new(pt(0.0, 0.0), pt(2.0, 0.0));
+        let d = |x0, y0, x1, y1| seg_seg_dist(&a, &Seg::new(pt(x0, y0), pt(x1, y1)));
+        assert!(f64_eq(d(0.0, 1.0, 2.0, 1.0), 1.0));
+        assert!(f64_eq(d(1.0, -1.0, 1.0, 1.0), 0.0));
+        assert!(f64_eq(d(1.0, 0.5, 1.0, 3.0), 0.5));
+        assert!(f64_eq(d(5.0, 4.0, 6.0, 4.0), 5.0));
+        assert!(f64_eq(d(3.0, 0.0, 4.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn rect_segment() {
+        let r = rt(0.0, 0.0, 2.0, 1.0);
+        let d = |x0, y0, x1, y1| rt_seg_dist(&r, &Seg::new(pt(x0, y0), pt(x1, y1)));
+        assert!(f64_eq(d(-1.0, 0.5, 3.0, 0.5), 0.0));
+        assert!(f64_eq(d(0.5, 0.5, 0.6, 0.6), 0.0));
+        assert!(f64_eq(d(0.0, 3.0, 2.0, 3.0), 2.0));
+        assert!(f64_eq(d(5.0, 5.0, 6.0, 5.0), 5.0));
+    }
+
+    #[test]
+    fn rect_path() {
+        let r = rt(0.0, 0.0, 2.0, 2.0);
+        // Turning around the corner at (2, 2), 1 away along both arms, and
+        // passing it diagonally.
+        let pts = [pt(0.0, 3.0), pt(3.0, 3.0), pt(3.0, 0.0)];
+        assert!(f64_eq(rt_path_dist(&r, &path(&pts, 0.25)), 0.75));
+        let pts = [pt(-1.0, 6.0), pt(6.0, -1.0)];
+        assert!(f64_eq(rt_path_dist(&r, &path(&pts, 0.5)), 0.5_f64.sqrt() - 0.5));
+        // Clipping through, and only reaching it because of its width.
+        let pts = [pt(-1.0, 1.0), pt(1.0, 3.0), pt(3.0, 1.0)];
+        assert!(f64_eq(rt_path_dist(&r, &path(&pts, 0.1)), 0.0));
+        let pts = [pt(0.0, 2.5), pt(2.0, 2.5)];
+        assert!(f64_eq(rt_path_dist(&r, &path(&pts, 0.5)), 0.0));
+        assert!(f64_eq(rt_path_dist(&r, &path(&[pt(5.0, 2.0)], 1.0)), 2.0));
+    }
+
+    #[test]
+    fn cap_rect_matches_sampling() {
+        let r = rt(0.0, 0.0, 2.0, 1.0);
+        // Distance from |p| to the rect, 0 inside it.
+        let pt_dist = |p: Pt| {
+            let dx = (r.l() - p.x).max(p.x - r.r()).max(0.0);
+            let dy = (r.b() - p.y).max(p.y - r.t()).max(0.0);
+            dx.hypot(dy)
+        };
+        let caps = [
+            Cap::new(Seg::new(pt(-3.0, 2.0), pt(5.0, 4.0)), 0.5),
+            Cap::new(Seg::new(pt(3.0, -2.0), pt(4.0, 3.0)), 0.25),
+            Cap::new(Seg::new(pt(-1.0, -1.5), pt(-2.0, -1.0)), 0.1),
+            Cap::new(Seg::new(pt(2.5, 0.5), pt(2.5, 0.5)), 0.3),
+            Cap::new(Seg::new(pt(-1.0, 0.5), pt(3.0, 2.5)), 0.5),
+            Cap::new(Seg::new(pt(1.0, 1.5), pt(1.0, 3.0)), 0.6),
+        ];
+        const N: usize = 10000;
+        for cap in caps {
+            let sampled = (0..=N)
+                .map(|i| pt_dist(lerp(cap.seg.st, cap.seg.en, i as f64 / N as f64)))
+                .fold(f64::INFINITY, f64::min);
+            let want = (sampled - cap.r).max(0.0);
+            let got = cap_rt_dist(&cap, &r);
+            assert!((got - want).abs() < 1e-3, "{cap:?}: {got} vs {want}");
+            assert!(got <= want + 1e-9, "{cap:?}: {got} vs {want}");
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let r = rt(0.0, 0.0, 2.0, 1.0).shape();
+        let diamond = poly(&[pt(4.0, 0.5), pt(5.0, -0.5), pt(6.0, 0.5), pt(5.0, 1.5)]).shape();
+        let tests = [
+            (r.clone(), circ(pt(3.0, 0.5), 0.5).shape(), 0.5),
+            (r.clone(), path(&[pt(0.0, 3.0), pt(2.0, 3.0)], 0.5).shape(), 1.5),
+            (r.clone(), diamond.clone(), 2.0),
+            (r.clone(), rt(1.0, 0.5, 3.0, 3.0).shape(), 0.0),
+            (rt(0.0, 0.0, 1.0, 1.0).shape(), rt(4.0, 5.0, 6.0, 6.0).shape(), 5.0),
+            // Inside the diamond, off its right corner, and above its top one.
+            (diamond.clone(), circ(pt(5.0, 0.5), 0.1).shape(), 0.0),
+            (diamond.clone(), circ(pt(7.0, 0.5), 0.5).shape(), 0.5),
+            (diamond, path(&[pt(5.0, 3.0), pt(8.0, 3.0)], 0.5).shape(), 1.0),
+            (circ(pt(0.0, 0.0), 1.0).shape(), circ(pt(3.0, 4.0), 1.0).shape(), 3.0),
+            (
+                path(&[pt(0.0, 0.0), pt(4.0, 0.0)], 0.5).shape(),
+                path(&[pt(2.0, 2.0), pt(2.0, 5.0)], 0.5).shape(),
+                1.0,
+            ),
+        ];
+        for (a, b, want) in tests {
+            assert!(f64_eq(shape_dist(&a, &b), want), "{a:?} {b:?}: {}", shape_dist(&a, &b));
+            assert!(f64_eq(shape_dist(&b, &a), want), "{b:?} {a:?}: {}", shape_dist(&b, &a));
+        }
+    }
+}
@@ -0,0 +1,654 @@
+use memegeom::primitive::circle::Circle;
+use memegeom::primitive::path_shape::Path;
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{pt, ShapeOps};
+
+use crate::geom::contains::pt_in_rt;
+use crate::geom::distance::{
+    cap_rt_dist, line_pt_dist, path_poly_dist, pt_seg_dist, rt_path_dist, seg_seg_dist,
+};
+use crate::geom::math::{cross, dot, f64_eq, f64_le, orientation, pt_eq};
+use crate::geom::polygon::{is_convex, triangulate};
+use crate::geom::{circ_cap, path_caps, Cap, Line, Seg, Tri};
+
+// Points within |EP| of each other intersect.
+#[must_use]
+pub fn pt_intersects_pt(a: Pt, b: Pt) -> bool {
+    pt_eq(a, b)
+}
+
+#[must_use]
+pub fn pt_intersects_line(p: Pt, l: &Line) -> bool {
+    f64_eq(line_pt_dist(l, p), 0.0)
+}
+
+// Infinite lines intersect unless they are parallel and distinct.
+#[must_use]
+pub fn line_intersects_line(a: &Line, b: &Line) -> bool {
+    let (da, db) = (a.dir(), b.dir());
+    let len = da.x.hypot(da.y) * db.x.hypot(db.y);
+    if len > 0.0 && !f64_eq(cross(da, db) / len, 0.0) {
+        return true;
+    }
+    f64_eq(line_pt_dist(a, b.st), 0.0)
+}
+
+// Tangent lines count as intersecting.
+#[must_use]
+pub fn circ_intersects_line(c: &Circle, l: &Line) -> bool {
+    f64_le(line_pt_dist(l, c.p()), c.r())
+}
+
+// Touching circles intersect.
+#[must_use]
+pub fn circ_intersects_circ(a: &Circle, b: &Circle) -> bool {
+    f64_le(a.p().dist(b.p()), a.r() + b.r())
+}
+
+#[must_use]
+pub fn circ_intersects_seg(c: &Circle, s: &Seg) -> bool {
+    f64_le(pt_seg_dist(c.p(), s), c.r())
+}
+
+// A path is the set of points within its radius of its segments, so this is
+// |circ_intersects_seg| with the radii added.
+#[must_use]
+pub fn circ_intersects_path(c: &Circle, p: &Path) -> bool {
+    let r = c.r() + p.r();
+    match p.pts() {
+        [] => false,
+        [a] => f64_le(c.p().dist(*a), r),
+        pts => pts.windows(2).any(|v| f64_le(pt_seg_dist(c.p(), &Seg::new(v[0], v[1])), r)),
+    }
+}
+
+// Proper crossing: each segment's ends are strictly on both sides of the
+// other, so they meet at a single point away from all four ends.
+#[must_use]
+pub fn seg_crosses_seg(a: &Seg, b: &Seg) -> bool {
+    let straddles =
+        |a: &Seg, b: &Seg| orientation(&a.line(), b.st) * orientation(&a.line(), b.en) < 0;
+    straddles(a, b) && straddles(b, a)
+}
+
+// Touching and overlapping collinear segments intersect.
+#[must_use]
+pub fn seg_intersects_seg(a: &Seg, b: &Seg) -> bool {
+    let on = |p: Pt, s: &Seg| f64_eq(pt_seg_dist(p, s), 0.0);
+    // Segments that don't cross can only meet at an end.
+    seg_crosses_seg(a, b) || on(a.st, b) || on(a.en, b) || on(b.st, a) || on(b.en, a)
+}
+
+// Segments inside the rect or touching its edges intersect.
+#[must_use]
+pub fn rt_intersects_seg(r: &Rt, s: &Seg) -> bool {
+    if pt_in_rt(s.st, r) || pt_in_rt(s.en, r) {
+        return true;
+    }
+    let pts = r.pts();
+    (0..4).any(|i| seg_intersects_seg(&Seg::new(pts[i], pts[(i + 1) % 4]), s))
+}
+
+// Capsules overlap when their segments are within the sum of their radii.
+// Two zero width capsules are plain segments.
+#[must_use]
+pub fn cap_intersects_cap(a: &Cap, b: &Cap) -> bool {
+    if a.r == 0.0 && b.r == 0.0 {
+        return seg_intersects_seg(&a.seg, &b.seg);
+    }
+    f64_le(seg_seg_dist(&a.seg, &b.seg), a.r + b.r)
+}
+
+#[must_use]
+pub fn path_intersects_path(a: &Path, b: &Path) -> bool {
+    path_caps(a).any(|ca| path_caps(b).any(|cb| cap_intersects_cap(&ca, &cb)))
+}
+
+// Touching rects intersect.
+#[must_use]
+pub fn rt_intersects_rt(a: &Rt, b: &Rt) -> bool {
+    f64_le(a.l(), b.r()) && f64_le(b.l(), a.r()) && f64_le(a.b(), b.t()) && f64_le(b.b(), a.t())
+}
+
+// Separating axis test: convex polygons are disjoint iff their projections
+// onto some edge normal don't overlap. The x and y axes are also tried, which
+// never gives a wrong answer and covers degenerate polygons with no edges.
+#[must_use]
+pub fn convex_intersects_convex(a: &[Pt], b: &[Pt]) -> bool {
+    fn normals(pts: &[Pt]) -> impl Iterator<Item = Pt> + '_ {
+        (0..pts.len()).map(move |i| (pts[(i + 1) % pts.len()] - pts[i]).perp())
+    }
+    fn proj(pts: &[Pt], axis: Pt) -> (f64, f64) {
+        pts.iter()
+            .map(|&p| dot(p, axis))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(l, r), d| (l.min(d), r.max(d)))
+    }
+    let axes = normals(a).chain(normals(b)).chain([pt(1.0, 0.0), pt(0.0, 1.0)]);
+    axes.filter(|axis| axis.x.is_finite() && axis.y.is_finite() && (axis.x != 0.0 || axis.y != 0.0))
+        .all(|axis| {
+            let ((al, ar), (bl, br)) = (proj(a, axis), proj(b, axis));
+            f64_le(bl, ar) && f64_le(al, br)
+        })
+}
+
+// Triangles are convex, so these are separating axis tests too. Collinear
+// triangles have edge normals that are all the same or zero, which the axis
+// test skips or handles as for a segment, so nothing divides by zero.
+
+// Touching, including at a single vertex, counts as intersecting.
+#[must_use]
+pub fn tri_intersects_tri(a: &Tri, b: &Tri) -> bool {
+    convex_intersects_convex(&a.pts, &b.pts)
+}
+
+#[must_use]
+pub fn tri_intersects_circ(t: &Tri, c: &Circle) -> bool {
+    let [a, b, d] = t.pts;
+    t.contains(c.p())
+        || [Seg::new(a, b), Seg::new(b, d), Seg::new(d, a)]
+            .iter()
+            .any(|s| circ_intersects_seg(c, s))
+}
+
+// |a| and |b| are simple polygons as for |triangulate|. Their triangles cover
+// them, so this also catches one polygon nested inside the other, where no
+// edges cross. Touching counts as intersecting.
+#[must_use]
+pub fn poly_intersects_poly(a: &[Pt], b: &[Pt]) -> bool {
+    let (ta, tb) = (triangulate(a), triangulate(b));
+    ta.iter().any(|x| tb.iter().any(|y| tri_intersects_tri(x, y)))
+}
+
+// Pads and rotated rects are convex, which skips triangulating them.
+fn region_intersects_region(a: &[Pt], b: &[Pt]) -> bool {
+    if is_convex(a) && is_convex(b) {
+        convex_intersects_convex(a, b)
+    } else {
+        poly_intersects_poly(a, b)
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    // Pairs |shape_intersects_shape| got past the broad-phase with.
+    static NARROW_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// Intersection of the shapes used on a board. Circles and paths are capsules
+// around their points, and rects and polygons are filled regions, so each
+// pair comes down to one of the tests above. Anything else is left to
+// memegeom.
+#[must_use]
+pub fn shape_intersects_shape(a: &Shape, b: &Shape) -> bool {
+    // Most pairs on a board are far apart, which comparing bounds rules out
+    // before any of the exact tests.
+    if !rt_intersects_rt(&a.bounds(), &b.bounds()) {
+        return false;
+    }
+    #[cfg(test)]
+    NARROW_CALLS.with(|n| n.set(n.get() + 1));
+    match (a, b) {
+        (Shape::Circle(a), Shape::Circle(b)) => circ_intersects_circ(a, b),
+        (Shape::Circle(c), Shape::Path(p)) | (Shape::Path(p), Shape::Circle(c)) => {
+            circ_intersects_path(c, p)
+        }
+        (Shape::Path(a), Shape::Path(b)) => path_intersects_path(a, b),
+        (Shape::Rect(a), Shape::Rect(b)) => rt_intersects_rt(a, b),
+        (Shape::Rect(r), Shape::Circle(c)) | (Shape::Circle(c), Shape::Rect(r)) => {
+            f64_le(cap_rt_dist(&circ_cap(c), r), 0.0)
+        }
+        (Shape::Rect(r), Shape::Path(p)) | (Shape::Path(p), Shape::Rect(r)) => {
+            f64_le(rt_path_dist(r, p), 0.0)
+        }
+        (Shape::Polygon(a), Shape::Polygon(b)) => region_intersects_region(a.pts(), b.pts()),
+        (Shape::Polygon(a), Shape::Rect(r)) | (Shape::Rect(r), Shape::Polygon(a)) => {
+            region_intersects_region(a.pts(), &r.pts())
+        }
+        (Shape::Polygon(a), Shape::Circle(c)) | (Shape::Circle(c), Shape::Polygon(a)) => {
+            triangulate(a.pts()).iter().any(|t| tri_intersects_circ(t, c))
+        }
+        (Shape::Polygon(a), Shape::Path(p)) | (Shape::Path(p), Shape::Polygon(a)) => {
+            f64_le(path_poly_dist(a.pts(), p), 0.0)
+        }
+        _ => a.intersects_shape(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::{circ, path, poly, pt, rt, ShapeOps};
+
+    use super::{
+        circ_intersects_circ, circ_intersects_line, circ_intersects_path, circ_intersects_seg,
+        convex_intersects_convex, line_intersects_line, path_intersects_path, poly_intersects_poly,
+        pt_intersects_line, pt_intersects_pt, rt_intersects_rt, rt_intersects_seg,
+        seg_intersects_seg, shape_intersects_shape, tri_intersects_circ, tri_intersects_tri,
+        NARROW_CALLS,
+    };
+    use crate::geom::distance::{pt_seg_dist, seg_seg_dist};
+    use crate::geom::math::EP;
+    use crate::geom::{Line, Seg, Tri};
+
+    // Circle centre and radius, segment, and whether they intersect.
+    #[allow(clippy::type_complexity)]
+    const CIRC_SEG_TESTS: &[((f64, f64, f64), (f64, f64, f64, f64), bool)] = &[
+        // Crossing, ending inside, and tangent to the side.
+        ((0.0, 0.0, 1.0), (-2.0, 0.5, 2.0, 0.5), true),
+        ((0.0, 0.0, 1.0), (0.5, 0.0, 3.0, 0.0), true),
+        ((0.0, 0.0, 1.0), (-2.0, 1.0, 2.0, 1.0), true),
+        // Touching at an end, and just short of it.
+        ((0.0, 0.0, 1.0), (1.0, 0.0, 3.0, 0.0), true),
+        ((0.0, 0.0, 1.0), (1.1, 0.0, 3.0, 0.0), false),
+        // Passing by, and the line would hit but the segment stops short.
+        ((0.0, 0.0, 1.0), (-2.0, 1.1, 2.0, 1.1), false),
+        ((0.0, 0.0, 1.0), (2.0, 0.0, 3.0, 0.0), false),
+        // Zero radius circles are points.
+        ((1.0, 0.0, 0.0), (0.0, 0.0, 2.0, 0.0), true),
+        ((1.0, 0.1, 0.0), (0.0, 0.0, 2.0, 0.0), false),
+        // Zero length segments are points.
+        ((0.0, 0.0, 1.0), (0.5, 0.5, 0.5, 0.5), true),
+        ((0.0, 0.0, 1.0), (1.0, 1.0, 1.0, 1.0), false),
+    ];
+
+    const SEG_SEG_TESTS: &[((f64, f64, f64, f64), (f64, f64, f64, f64), bool)] = &[
+        // Crossing, T junction, and sharing an end.
+        ((0.0, 0.0, 2.0, 2.0), (0.0, 2.0, 2.0, 0.0), true),
+        ((0.0, 0.0, 2.0, 0.0), (1.0, 0.0, 1.0, 2.0), true),
+        ((0.0, 0.0, 2.0, 0.0), (2.0, 0.0, 3.0, 1.0), true),
+        // Collinear overlapping, collinear touching, and collinear apart.
+        ((0.0, 0.0, 2.0, 0.0), (1.0, 0.0, 3.0, 0.0), true),
+        ((0.0, 0.0, 2.0, 0.0), (2.0, 0.0, 3.0, 0.0), true),
+        ((0.0, 0.0, 2.0, 0.0), (2.5, 0.0, 3.0, 0.0), false),
+        // Parallel, and the lines cross but the segments stop short.
+        ((0.0, 0.0, 2.0, 0.0), (0.0, 1.0, 2.0, 1.0), false),
+        ((0.0, 0.0, 2.0, 0.0), (1.0, 0.5, 1.0, 2.0), false),
+        // Far apart, with bounds that don't overlap on either axis.
+        ((0.0, 0.0, 2.0, 0.0), (5.0, 3.0, 6.0, 4.0), false),
+        // Zero length segments are points.
+        ((0.0, 0.0, 2.0, 0.0), (1.0, 0.0, 1.0, 0.0), true),
+        ((0.0, 0.0, 2.0, 0.0), (1.0, 0.1, 1.0, 0.1), false),
+    ];
+
+    type TriPts = (f64, f64, f64, f64, f64, f64);
+
+    // Triangle, segment, and whether they intersect.
+    const TRI_SEG_TESTS: &[(TriPts, (f64, f64, f64, f64), bool)] = &[
+        // Inside, crossing two edges, and poking in through one.
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (0.5, 0.5, 1.0, 1.0), true),
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (-1.0, 1.0, 5.0, 1.0), true),
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (1.0, 1.0, 5.0, 5.0), true),
+        // Ending on the hypotenuse, along an edge, and touching a vertex.
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (3.0, 3.0, 2.0, 2.0), true),
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (1.0, 0.0, 6.0, 0.0), true),
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (4.0, 0.0, 5.0, 1.0), true),
+        // Just past the hypotenuse, parallel to it, and below the base.
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (2.1, 2.1, 3.0, 3.0), false),
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (0.0, 4.1, 4.1, 0.0), false),
+        ((0.0, 0.0, 4.0, 0.0, 0.0, 4.0), (0.0, -0.1, 4.0, -0.1), false),
+        // A collinear triangle is a segment: crossed, and missed.
+        ((0.0, 0.0, 2.0, 0.0, 4.0, 0.0), (1.0, -1.0, 1.0, 1.0), true),
+        ((0.0, 0.0, 2.0, 0.0, 4.0, 0.0), (5.0, -1.0, 5.0, 1.0), false),
+        // All three corners in one place make a point.
+        ((1.0, 1.0, 1.0, 1.0, 1.0, 1.0), (0.0, 0.0, 2.0, 2.0), true),
+        ((1.0, 1.0, 1.0, 1.0, 1.0, 1.0), (0.0, 0.1, 2.0, 2.1), false),
+    ];
+
+    // Rotates |p| by a multiple of 90 degrees, which keeps rects axis aligned.
+    fn rot90(p: Pt, turns: usize) -> Pt {
+        (0..turns).fold(p, |p, _| pt(-p.y, p.x))
+    }
+
+    #[test]
+    fn points() {
+        assert!(pt_intersects_pt(pt(1.0, 2.0), pt(1.0, 2.0)));
+        assert!(pt_intersects_pt(pt(1.0, 2.0), pt(1.0 + EP / 2.0, 2.0 - EP / 2.0)));
+        assert!(!pt_intersects_pt(pt(1.0, 2.0), pt(1.0 + 2.0 * EP, 2.0)));
+    }
+
+    #[test]
+    fn point_line() {
+        let l = Line::new(pt(0.0, 0.0), pt(1.0, 1.0));
+        assert!(pt_intersects_line(pt(5.0, 5.0), &l));
+        assert!(pt_intersects_line(pt(-3.0, -3.0), &l));
+        assert!(!pt_intersects_line(pt(5.0, 5.0 + 4.0 * EP), &l));
+    }
+
+    #[test]
+    fn lines() {
+        let a = Line::new(pt(0.0, 0.0), pt(1.0, 0.0));
+        let parallel = Line::new(pt(0.0, 1.0), pt(3.0, 1.0));
+        let same = Line::new(pt(5.0, 0.0), pt(7.0, 0.0));
+        let crossing = Line::new(pt(10.0, -1.0), pt(11.0, 1.0));
+        assert!(!line_intersects_line(&a, &parallel));
+        assert!(line_intersects_line(&a, &same));
+        assert!(line_intersects_line(&a, &crossing));
+        assert!(line_intersects_line(&crossing, &a));
+    }
+
+    #[test]
+    fn circle_line() {
+        let c = circ(pt(0.0, 0.0), 1.0);
+        assert!(circ_intersects_line(&c, &Line::new(pt(-5.0, 0.5), pt(5.0, 0.5))));
+        assert!(circ_intersects_line(&c, &Line::new(pt(-5.0, 1.0), pt(-4.0, 1.0))));
+        assert!(!circ_intersects_line(&c, &Line::new(pt(-5.0, 1.1), pt(5.0, 1.1))));
+    }
+
+    #[test]
+    fn circles() {
+        let c = circ(pt(0.0, 0.0), 1.0);
+        // Overlapping, tangent, concentric, and disjoint.
+        assert!(circ_intersects_circ(&c, &circ(pt(1.5, 0.0), 1.0)));
+        assert!(circ_intersects_circ(&c, &circ(pt(0.0, 3.0), 2.0)));
+        assert!(circ_intersects_circ(&c, &circ(pt(0.0, 0.0), 0.5)));
+        assert!(!circ_intersects_circ(&c, &circ(pt(3.0, 0.0), 1.9)));
+        // Zero radius circles are points.
+        assert!(circ_intersects_circ(&c, &circ(pt(1.0, 0.0), 0.0)));
+        assert!(!circ_intersects_circ(&c, &circ(pt(1.0 + 2.0 * EP, 0.0), 0.0)));
+        assert!(circ_intersects_circ(&circ(pt(1.0, 1.0), 0.0), &circ(pt(1.0, 1.0), 0.0)));
+    }
+
+    #[test]
+    fn circle_segment() {
+        for &((x, y, r), (x0, y0, x1, y1), want) in CIRC_SEG_TESTS {
+            let c = circ(pt(x, y), r);
+            let s = Seg::new(pt(x0, y0), pt(x1, y1));
+            assert_eq!(circ_intersects_seg(&c, &s), want, "{c:?} {s:?}");
+            let rev = Seg::new(s.en, s.st);
+            assert_eq!(circ_intersects_seg(&c, &rev), want, "{c:?} {rev:?}");
+            // Translating both doesn't matter.
+            let d = pt(3.5, -2.25);
+            let c = circ(c.p() + d, r);
+            assert_eq!(circ_intersects_seg(&c, &Seg::new(s.st + d, s.en + d)), want);
+        }
+    }
+
+    #[test]
+    fn circle_path() {
+        let c = circ(pt(0.0, 0.0), 1.0);
+        // An L around the circle, 1.5 away; its radius decides.
+        let pts = [pt(-3.0, 1.5), pt(1.5, 1.5), pt(1.5, -3.0)];
+        assert!(!circ_intersects_path(&c, &path(&pts, 0.4)));
+        assert!(circ_intersects_path(&c, &path(&pts, 0.5)));
+        // The corner is further away than the sides.
+        let corner = [pt(1.5, 1.5)];
+        assert!(!circ_intersects_path(&c, &path(&corner, 1.1)));
+        assert!(circ_intersects_path(&c, &path(&corner, 1.2)));
+    }
+
+    #[test]
+    fn segments() {
+        for &((ax0, ay0, ax1, ay1), (bx0, by0, bx1, by1), want) in SEG_SEG_TESTS {
+            for turns in 0..4 {
+                for d in [pt(0.0, 0.0), pt(-7.5, 3.25)] {
+                    let tf = |x, y| rot90(pt(x, y), turns) + d;
+                    let a = Seg::new(tf(ax0, ay0), tf(ax1, ay1));
+                    let b = Seg::new(tf(bx0, by0), tf(bx1, by1));
+                    assert_eq!(seg_intersects_seg(&a, &b), want, "{a:?} {b:?}");
+                    assert_eq!(seg_intersects_seg(&b, &a), want, "{b:?} {a:?}");
+                    let rev = Seg::new(b.en, b.st);
+                    assert_eq!(seg_intersects_seg(&a, &rev), want, "{a:?} {rev:?}");
+                    // |seg_seg_dist| skips the intersection test for far apart
+                    // bounds, which must not change its result.
+                    let full = if want {
+                        0.0
+                    } else {
+                        [
+                            pt_seg_dist(a.st, &b),
+                            pt_seg_dist(a.en, &b),
+                            pt_seg_dist(b.st, &a),
+                            pt_seg_dist(b.en, &a),
+                        ]
+                        .into_iter()
+                        .fold(f64::INFINITY, f64::min)
+                    };
+                    assert_eq!(seg_seg_dist(&a, &b).to_bits(), full.to_bits(), "{a:?} {b:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rect_segment() {
+        // Segment, and whether it intersects the rect (0, 0)-(2, 1).
+        let tests = [
+            // Inside, poking out, and through with both ends outside.
+            ((0.5, 0.5, 1.5, 0.5), true),
+            ((1.0, 0.5, 3.0, 0.5), true),
+            ((-1.0, -1.0, 3.0, 2.0), true),
+            // Along an edge, and touching a corner from outside.
+            ((0.0, 1.0, 2.0, 1.0), true),
+            ((2.0, 1.0, 3.0, 2.0), true),
+            // Outside, and past a corner.
+            ((3.0, 0.0, 3.0, 1.0), false),
+            ((1.5, 2.0, 3.0, 0.5), false),
+        ];
+        for ((x0, y0, x1, y1), want) in tests {
+            for turns in 0..4 {
+                for d in [pt(0.0, 0.0), pt(4.0, -2.5)] {
+                    let [c0, c1] = [pt(0.0, 0.0), pt(2.0, 1.0)].map(|p| rot90(p, turns) + d);
+                    let r = rt(c0.x.min(c1.x), c0.y.min(c1.y), c0.x.max(c1.x), c0.y.max(c1.y));
+                    let s = Seg::new(rot90(pt(x0, y0), turns) + d, rot90(pt(x1, y1), turns) + d);
+                    assert_eq!(rt_intersects_seg(&r, &s), want, "{r:?} {s:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn polygons() {
+        // A C shape opening to the right around a hole from (1, 1) to (3, 3).
+        let c = [
+            pt(0.0, 0.0),
+            pt(4.0, 0.0),
+            pt(4.0, 1.0),
+            pt(1.0, 1.0),
+            pt(1.0, 3.0),
+            pt(4.0, 3.0),
+            pt(4.0, 4.0),
+            pt(0.0, 4.0),
+        ];
+        let square = |l: f64, b: f64, r: f64, t: f64| [pt(l, b), pt(r, b), pt(r, t), pt(l, t)];
+        // In the hole, and poking out of its open side, without touching.
+        assert!(!poly_intersects_poly(&c, &square(1.5, 1.5, 2.5, 2.5)));
+        assert!(!poly_intersects_poly(&c, &square(2.0, 1.5, 6.0, 2.5)));
+        // Overlapping an arm, and touching the inside edge.
+        assert!(poly_intersects_poly(&c, &square(2.0, 2.5, 3.0, 3.5)));
+        assert!(poly_intersects_poly(&c, &square(1.0, 1.5, 2.0, 2.5)));
+        // Nested inside the solid part with no edges crossing, either way round.
+        let inner = square(0.25, 0.25, 0.75, 3.75);
+        assert!(poly_intersects_poly(&c, &inner));
+        assert!(poly_intersects_poly(&inner, &c));
+        // Entirely enclosing it.
+        assert!(poly_intersects_poly(&square(-1.0, -1.0, 5.0, 5.0), &c));
+        // Off to the side.
+        assert!(!poly_intersects_poly(&c, &square(5.0, 0.0, 6.0, 4.0)));
+    }
+
+    #[test]
+    fn rects() {
+        let a = rt(0.0, 0.0, 2.0, 1.0);
+        assert!(rt_intersects_rt(&a, &rt(1.0, 0.5, 3.0, 3.0)));
+        assert!(rt_intersects_rt(&a, &rt(2.0, 1.0, 3.0, 3.0)));
+        assert!(rt_intersects_rt(&a, &rt(-1.0, -1.0, 3.0, 3.0)));
+        assert!(!rt_intersects_rt(&a, &rt(2.1, 0.0, 3.0, 1.0)));
+        assert!(!rt_intersects_rt(&a, &rt(0.0, 1.1, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn rotated_rects() {
+        // A 2 x 1 pad turned 30 degrees about its centre, as |shape_tf| gives
+        // it. Its top right edge runs from (0.616, 0.933) to (1.116, 0.067).
+        let (sin, cos) = 30.0_f64.to_radians().sin_cos();
+        let pad = rt(-1.0, -0.5, 1.0, 0.5)
+            .pts()
+            .map(|p| pt(p.x * cos - p.y * sin, p.x * sin + p.y * cos));
+        let pad = poly(&pad).shape();
+        let keepout = |l: f64, b: f64| rt(l, b, 3.0, 3.0).shape();
+        // The keepout corner pokes 0.06 into the pad, or stays 0.08 clear of
+        // it although the pad's bounds overlap the keepout either way.
+        assert!(shape_intersects_shape(&pad, &keepout(0.8, 0.5)));
+        assert!(!shape_intersects_shape(&pad, &keepout(0.9, 0.6)));
+        assert!(!shape_intersects_shape(&keepout(0.9, 0.6), &pad));
+        // Clear of the bounds entirely, and overlapping the middle.
+        assert!(!shape_intersects_shape(&pad, &keepout(1.2, -3.0)));
+        assert!(shape_intersects_shape(&pad, &rt(-0.1, -0.1, 0.1, 0.1).shape()));
+    }
+
+    #[test]
+    fn shapes() {
+        let r = rt(0.0, 0.0, 2.0, 1.0).shape();
+        let c = circ(pt(3.0, 0.5), 1.0).shape();
+        let p = path(&[pt(-1.0, 2.0), pt(3.0, 2.0)], 1.0).shape();
+        // An L around the top right corner of |r|, concave at (2.5, 1.5).
+        let l = poly(&[
+            pt(1.0, 1.5),
+            pt(2.5, 1.5),
+            pt(2.5, -1.0),
+            pt(3.5, -1.0),
+            pt(3.5, 2.5),
+            pt(1.0, 2.5),
+        ])
+        .shape();
+        let tests = [
+            (&r, &c, true),
+            (&r, &p, true),
+            (&c, &p, true),
+            (&r, &l, false),
+            (&c, &l, true),
+            (&p, &l, true),
+            (&r, &path(&[pt(0.0, 1.6), pt(2.0, 1.6)], 0.5).shape(), false),
+            (&r, &circ(pt(3.1, 0.5), 1.0).shape(), false),
+            (&l, &circ(pt(2.0, 0.5), 0.4).shape(), false),
+            (&l, &circ(pt(2.0, 0.5), 0.5).shape(), true),
+            (&l, &path(&[pt(1.5, 0.0), pt(1.5, 1.0)], 0.4).shape(), false),
+            // Inside the polygon without touching its edges.
+            (&l, &circ(pt(3.0, 2.0), 0.1).shape(), true),
+            (&l, &path(&[pt(3.0, 0.0), pt(3.0, 2.0)], 0.1).shape(), true),
+        ];
+        for (a, b, want) in tests {
+            assert_eq!(shape_intersects_shape(a, b), want, "{a:?} {b:?}");
+            assert_eq!(shape_intersects_shape(b, a), want, "{b:?} {a:?}");
+        }
+    }
+
+    #[test]
+    fn broad_phase_skips_exact_tests() {
+        let narrow = || NARROW_CALLS.with(std::cell::Cell::get);
+        let pad = rt(0.0, 0.0, 1.0, 1.0).shape();
+        let trace = path(&[pt(3.0, -2.0), pt(3.0, 4.0), pt(6.0, 4.0)], 0.1).shape();
+        let outline = poly(&[pt(-5.0, 3.5), pt(5.0, 3.5), pt(0.0, 9.0)]).shape();
+        let start = narrow();
+        assert!(!shape_intersects_shape(&pad, &trace));
+        assert!(!shape_intersects_shape(&outline, &pad));
+        assert!(!shape_intersects_shape(&circ(pt(5.0, 5.0), 1.0).shape(), &pad));
+        assert_eq!(narrow(), start);
+        // Overlapping bounds go on to the exact test, whatever it finds.
+        assert!(!shape_intersects_shape(&pad, &circ(pt(1.9, 1.9), 1.0).shape()));
+        assert!(shape_intersects_shape(&trace, &outline));
+        assert_eq!(narrow(), start + 2);
+    }
+
+    #[test]
+    fn paths() {
+        // Crossing at a shallow angle, with and without width.
+        let a = [pt(0.0, 0.0), pt(10.0, 0.0)];
+        let b = [pt(0.0, -0.5), pt(10.0, 0.5)];
+        assert!(path_intersects_path(&path(&a, 0.0), &path(&b, 0.0)));
+        assert!(path_intersects_path(&path(&a, 0.1), &path(&b, 0.1)));
+        // Parallel 0.5 apart: overlapping only once the radii add up to it.
+        let c = [pt(2.0, 0.5), pt(5.0, 0.5), pt(5.0, 3.0)];
+        assert!(!path_intersects_path(&path(&a, 0.2), &path(&c, 0.2)));
+        assert!(path_intersects_path(&path(&a, 0.25), &path(&c, 0.25)));
+        assert!(path_intersects_path(&path(&c, 0.3), &path(&a, 0.2)));
+        // Zero width paths touching end to end, and a single point path.
+        assert!(path_intersects_path(&path(&a, 0.0), &path(&[pt(10.0, 0.0), pt(11.0, 1.0)], 0.0)));
+        assert!(path_intersects_path(&path(&a, 0.5), &path(&[pt(5.0, 1.0)], 0.5)));
+        assert!(!path_intersects_path(&path(&a, 0.4), &path(&[pt(5.0, 1.0)], 0.5)));
+    }
+
+    #[test]
+    fn zero_width_path() {
+        // A zero width path is its segments.
+        let seg = |x0, y0, x1, y1| path(&[pt(x0, y0), pt(x1, y1)], 0.0);
+        let p = path(&[pt(0.0, 0.0), pt(4.0, 0.0), pt(4.0, 4.0)], 0.5);
+        assert!(path_intersects_path(&seg(1.0, 0.5, 3.0, 2.0), &p));
+        assert!(path_intersects_path(&seg(2.0, 2.0, 3.5, 2.0), &p));
+        assert!(!path_intersects_path(&seg(1.0, 0.6, 3.0, 2.0), &p));
+        assert!(!path_intersects_path(&seg(2.0, 2.0, 3.4, 2.0), &p));
+        let thin = seg(0.0, 0.0, 4.0, 0.0);
+        assert!(path_intersects_path(&seg(2.0, -1.0, 2.0, 1.0), &thin));
+        assert!(!path_intersects_path(&seg(2.0, 0.1, 2.0, 1.0), &thin));
+    }
+
+    #[test]
+    fn triangle_segment() {
+        for &((x0, y0, x1, y1, x2, y2), (sx0, sy0, sx1, sy1), want) in TRI_SEG_TESTS {
+            for turns in 0..4 {
+                for d in [pt(0.0, 0.0), pt(-7.5, 3.25)] {
+                    let tf = |x, y| rot90(pt(x, y), turns) + d;
+                    let t = Tri::new(tf(x0, y0), tf(x1, y1), tf(x2, y2));
+                    let s = Seg::new(tf(sx0, sy0), tf(sx1, sy1));
+                    let seg = [s.st, s.en];
+                    assert_eq!(convex_intersects_convex(&t.pts, &seg), want, "{t:?} {s:?}");
+                    let rev = [t.pts[2], t.pts[1], t.pts[0]];
+                    assert_eq!(convex_intersects_convex(&rev, &seg), want, "{rev:?} {s:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangles() {
+        let t = |x0, y0, x1, y1, x2, y2| Tri::new(pt(x0, y0), pt(x1, y1), pt(x2, y2));
+        let a = t(0.0, 0.0, 2.0, 0.0, 0.0, 2.0);
+        let tests = [
+            // Sharing only a vertex, sharing an edge, and overlapping.
+            (t(2.0, 0.0, 4.0, 0.0, 3.0, -2.0), true),
+            (t(2.0, 0.0, 0.0, 2.0, 2.0, 2.0), true),
+            (t(0.5, 0.5, 3.0, 0.5, 0.5, 3.0), true),
+            // Nested, and apart across the hypotenuse.
+            (t(0.2, 0.2, 0.8, 0.2, 0.2, 0.8), true),
+            (t(1.1, 1.1, 3.0, 1.1, 1.1, 3.0), false),
+            // Collinear triangles: through |a|, and lying along its base.
+            (t(-1.0, 1.0, 0.5, 1.0, 3.0, 1.0), true),
+            (t(3.0, 0.0, 4.0, 0.0, 5.0, 0.0), false),
+        ];
+        for (b, want) in tests {
+            for turns in 0..4 {
+                let tf = |t: &Tri| Tri { pts: t.pts.map(|p| rot90(p, turns)) };
+                let (a, b) = (tf(&a), tf(&b));
+                assert_eq!(tri_intersects_tri(&a, &b), want, "{a:?} {b:?}");
+                assert_eq!(tri_intersects_tri(&b, &a), want, "{b:?} {a:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_circle() {
+        let t = Tri::new(pt(0.0, 0.0), pt(4.0, 0.0), pt(0.0, 4.0));
+        for turns in 0..4 {
+            let tf = |p| rot90(p, turns);
+            let t = Tri { pts: t.pts.map(tf) };
+            // Inside without reaching an edge, tangent to the hypotenuse from
+            // outside, and just clear of it.
+            assert!(tri_intersects_circ(&t, &circ(tf(pt(1.0, 1.0)), 0.5)));
+            let h = 2.0 + 0.5 / 2.0_f64.sqrt();
+            assert!(tri_intersects_circ(&t, &circ(tf(pt(h, h)), 0.5)));
+            assert!(!tri_intersects_circ(&t, &circ(tf(pt(h, h)), 0.49)));
+            // Around a corner from outside, and containing the whole triangle.
+            assert!(tri_intersects_circ(&t, &circ(tf(pt(-1.0, -1.0)), 1.5)));
+            assert!(!tri_intersects_circ(&t, &circ(tf(pt(-1.0, -1.0)), 1.4)));
+            assert!(tri_intersects_circ(&t, &circ(tf(pt(1.0, 1.0)), 10.0)));
+        }
+        // Collinear, and all one point.
+        let flat = Tri::new(pt(0.0, 0.0), pt(1.0, 0.0), pt(2.0, 0.0));
+        assert!(tri_intersects_circ(&flat, &circ(pt(1.0, 0.5), 0.5)));
+        assert!(!tri_intersects_circ(&flat, &circ(pt(1.0, 0.6), 0.5)));
+        let dot = Tri::new(pt(1.0, 1.0), pt(1.0, 1.0), pt(1.0, 1.0));
+        assert!(tri_intersects_circ(&dot, &circ(pt(1.0, 1.5), 0.5)));
+        assert!(!tri_intersects_circ(&dot, &circ(pt(1.0, 1.6), 0.5)));
+    }
+}
@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+
+use memegeom::primitive::point::Pt;
+
+use crate::geom::Line;
+
+// Tolerance for comparisons, in mm.
+pub const EP: f64 = 1e-6;
+
+#[must_use]
+pub fn f64_eq(a: f64, b: f64) -> bool {
+    (a - b).abs() < EP
+}
+
+// The orderings below all go through |f64_eq|, so values within |EP| of each
+// other are equal for every comparison.
+
+#[must_use]
+pub fn f64_lt(a: f64, b: f64) -> bool {
+    a < b && !f64_eq(a, b)
+}
+
+#[must_use]
+pub fn f64_le(a: f64, b: f64) -> bool {
+    a < b || f64_eq(a, b)
+}
+
+#[must_use]
+pub fn f64_gt(a: f64, b: f64) -> bool {
+    f64_lt(b, a)
+}
+
+#[must_use]
+pub fn f64_ge(a: f64, b: f64) -> bool {
+    f64_le(b, a)
+}
+
+// Total order for sorting and |min_by|. Unlike the comparisons above this
+// doesn't use |EP|: ties within |EP| aren't transitive, which sorts can't take.
+#[must_use]
+pub fn f64_cmp(a: &f64, b: &f64) -> Ordering {
+    a.total_cmp(b)
+}
+
+#[must_use]
+pub fn pt_eq(a: Pt, b: Pt) -> bool {
+    f64_eq(a.x, b.x) && f64_eq(a.y, b.y)
+}
+
+#[must_use]
+pub fn dot(a: Pt, b: Pt) -> f64 {
+    a.x * b.x + a.y * b.y
+}
+
+// Z component of the cross product of |a| and |b|.
+#[must_use]
+pub fn cross(a: Pt, b: Pt) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+// Cross product of |a| - |o| and |b| - |o|. Positive if |o|, |a|, |b| turn
+// counterclockwise.
+#[must_use]
+pub fn cross_at(o: Pt, a: Pt, b: Pt) -> f64 {
+    cross(a - o, b - o)
+}
+
+// Which side of |l| |p| is on: 1 for left, -1 for right, and 0 if within |EP|
+// of it. A degenerate line is a point, which everything is on.
+#[must_use]
+pub fn orientation(l: &Line, p: Pt) -> i32 {
+    let len = l.st.dist(l.en);
+    if len <= 0.0 {
+        return 0;
+    }
+    let dist = cross_at(l.st, l.en, p) / len;
+    if f64_eq(dist, 0.0) {
+        0
+    } else if dist > 0.0 {
+        1
+    } else {
+        -1
+    }
+}
+
+// Whether |b| is within |EP| of the line through |a| and |c|. Coincident |a|
+// and |c| don't define a line, so any |b| counts.
+#[must_use]
+pub fn is_collinear(a: Pt, b: Pt, c: Pt) -> bool {
+    orientation(&Line::new(a, c), b) == 0
+}
+
+// Point a fraction |t| of the way from |a| to |b|. |t| outside [0, 1]
+// extrapolates.
+#[must_use]
+pub fn lerp(a: Pt, b: Pt, t: f64) -> Pt {
+    a + t * (b - a)
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+
+    use super::{
+        cross_at, f64_eq, f64_ge, f64_gt, f64_le, f64_lt, is_collinear, lerp, orientation, pt_eq,
+        EP,
+    };
+    use crate::geom::intersects::{seg_crosses_seg, seg_intersects_seg};
+    use crate::geom::{Line, Seg};
+
+    #[test]
+    fn comparisons_use_epsilon() {
+        assert!(f64_eq(1.0, 1.0 + EP / 2.0));
+        assert!(!f64_eq(1.0, 1.0 + 2.0 * EP));
+        assert!(f64_le(1.0 + EP / 2.0, 1.0));
+        assert!(pt_eq(pt(1.0, 2.0), pt(1.0 + EP / 2.0, 2.0)));
+    }
+
+    #[test]
+    fn orderings_at_epsilon() {
+        // Exactly |EP| apart is far enough to be ordered; |EP| / 2 isn't.
+        for (d, apart) in [(EP, true), (EP / 2.0, false)] {
+            assert_eq!(f64_lt(0.0, d), apart, "{d}");
+            assert_eq!(f64_gt(d, 0.0), apart, "{d}");
+            assert_eq!(f64_le(d, 0.0), !apart, "{d}");
+            assert_eq!(f64_ge(0.0, d), !apart, "{d}");
+            // The other way round is ordered regardless.
+            assert!(!f64_lt(d, 0.0) && !f64_gt(0.0, d), "{d}");
+            assert!(f64_le(0.0, d) && f64_ge(d, 0.0), "{d}");
+        }
+        for v in [0.0, 1.0, -3.5] {
+            assert!(!f64_lt(v, v) && !f64_gt(v, v) && f64_le(v, v) && f64_ge(v, v));
+        }
+    }
+
+    #[test]
+    fn cross_at_sign() {
+        let o = pt(0.0, 0.0);
+        assert!(cross_at(o, pt(1.0, 0.0), pt(0.0, 1.0)) > 0.0);
+        assert!(cross_at(o, pt(0.0, 1.0), pt(1.0, 0.0)) < 0.0);
+        assert!(f64_eq(cross_at(o, pt(1.0, 1.0), pt(2.0, 2.0)), 0.0));
+    }
+
+    #[test]
+    fn lerp_ends_and_middle() {
+        let (a, b) = (pt(1.0, 2.0), pt(3.0, 6.0));
+        assert!(pt_eq(lerp(a, b, 0.0), a));
+        assert!(pt_eq(lerp(a, b, 1.0), b));
+        assert!(pt_eq(lerp(a, b, 0.5), pt(2.0, 4.0)));
+        assert!(pt_eq(lerp(a, b, 2.0), pt(5.0, 10.0)));
+    }
+
+    #[test]
+    fn collinear_within_ep() {
+        let (a, c) = (pt(0.0, 0.0), pt(4.0, 2.0));
+        assert!(is_collinear(a, pt(2.0, 1.0), c));
+        assert!(is_collinear(a, pt(6.0, 3.0), c));
+        assert!(is_collinear(a, pt(2.0, 1.0 + EP / 2.0), c));
+        assert!(!is_collinear(a, pt(2.0, 1.0 + 4.0 * EP), c));
+        assert!(is_collinear(a, pt(5.0, -1.0), a));
+    }
+
+    #[test]
+    fn orientation_sides() {
+        let l = Line::new(pt(0.0, 0.0), pt(4.0, 2.0));
+        assert_eq!(orientation(&l, pt(0.0, 1.0)), 1);
+        assert_eq!(orientation(&l, pt(1.0, 0.0)), -1);
+        assert_eq!(orientation(&l, pt(8.0, 4.0)), 0);
+        assert_eq!(orientation(&Line::new(l.st, l.st), pt(1.0, 0.0)), 0);
+    }
+
+    #[test]
+    fn nearly_collinear_agrees() {
+        // |b| is |EP| / 10 off the line through |a| and |c|, which every test
+        // built on |orientation| treats as on it.
+        let (a, c) = (pt(0.0, 0.0), pt(4.0, 2.0));
+        for b in [pt(2.0, 1.0 + EP / 10.0), pt(2.0, 1.0 - EP / 10.0), pt(6.0, 3.0 + EP / 10.0)] {
+            assert!(is_collinear(a, b, c), "{b:?}");
+            assert_eq!(orientation(&Line::new(a, c), b), 0, "{b:?}");
+            let s = Seg::new(a, c);
+            // A segment ending at |b| only touches |s|, so doesn't cross it.
+            let up = Seg::new(b, b + pt(0.0, 3.0));
+            assert!(!seg_crosses_seg(&s, &up), "{b:?}");
+            assert_eq!(seg_intersects_seg(&s, &up), b.x <= c.x, "{b:?}");
+            // Nor does one running along the line through it.
+            let along = Seg::new(b, b + pt(2.0, 1.0));
+            assert!(!seg_crosses_seg(&s, &along), "{b:?}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// Geometry the router needs on top of memegeom's primitives. Everything here
+// works on plain points, so it can be used with memegeom shapes directly.
+use memegeom::primitive::circle::Circle;
+use memegeom::primitive::path_shape::Path;
+use memegeom::primitive::point::Pt;
+
+use crate::geom::math::{cross_at, f64_le, lerp, orientation};
+
+pub mod contains;
+pub mod distance;
+pub mod intersects;
+pub mod math;
+pub mod offset;
+pub mod polygon;
+#[cfg(test)]
+mod proptests;
+pub mod quadtree;
+pub mod transform;
+
+// Infinite line through |st| and |en|.
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Line {
+    pub st: Pt,
+    pub en: Pt,
+}
+
+impl Line {
+    pub const fn new(st: Pt, en: Pt) -> Self {
+        Self { st, en }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> Pt {
+        self.en - self.st
+    }
+}
+
+// Line segment from |st| to |en|.
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Seg {
+    pub st: Pt,
+    pub en: Pt,
+}
+
+impl Seg {
+    pub const fn new(st: Pt, en: Pt) -> Self {
+        Self { st, en }
+    }
+
+    pub const fn line(&self) -> Line {
+        Line::new(self.st, self.en)
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> Pt {
+        self.en - self.st
+    }
+
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.st.dist(self.en)
+    }
+
+    // Point a fraction |t| of the way along the segment.
+    #[must_use]
+    pub fn point_at(&self, t: f64) -> Pt {
+        lerp(self.st, self.en, t)
+    }
+}
+
+// Segment grown by |r| in every direction, i.e. one segment of a path.
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cap {
+    pub seg: Seg,
+    pub r: f64,
+}
+
+impl Cap {
+    pub const fn new(seg: Seg, r: f64) -> Self {
+        Self { seg, r }
+    }
+}
+
+// The capsules making up |p|. A single point path is one zero length capsule.
+pub fn path_caps(p: &Path) -> impl Iterator<Item = Cap> + '_ {
+    let pts = p.pts();
+    let single = match pts {
+        [a] => Some(Cap::new(Seg::new(*a, *a), p.r())),
+        _ => None,
+    };
+    single.into_iter().chain(pts.windows(2).map(|v| Cap::new(Seg::new(v[0], v[1]), p.r())))
+}
+
+// A circle is a zero length capsule.
+pub fn circ_cap(c: &Circle) -> Cap {
+    Cap::new(Seg::new(c.p(), c.p()), c.r())
+}
+
+// Edges of the polygon |pts|, including the one closing it.
+pub fn poly_edges(pts: &[Pt]) -> impl Iterator<Item = Seg> + '_ {
+    (0..pts.len()).map(|i| Seg::new(pts[i], pts[(i + 1) % pts.len()]))
+}
+
+// Point |dist| along the polyline |pts|, measured from its start. Clamps to
+// the ends, so e.g. sampling at a fixed spacing can overshoot the length.
+// Takes points so it works with memegeom paths via |Path::pts|.
+#[must_use]
+pub fn path_point_at(pts: &[Pt], dist: f64) -> Pt {
+    let mut left = dist.max(0.0);
+    for v in pts.windows(2) {
+        let seg = Seg::new(v[0], v[1]);
+        let len = seg.len();
+        if left <= len {
+            return if len > 0.0 { seg.point_at(left / len) } else { seg.st };
+        }
+        left -= len;
+    }
+    *pts.last().expect("path has no points")
+}
+
+// Triangle with corners |pts|, in either winding.
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tri {
+    pub pts: [Pt; 3],
+}
+
+impl Tri {
+    pub const fn new(a: Pt, b: Pt, c: Pt) -> Self {
+        Self { pts: [a, b, c] }
+    }
+
+    #[must_use]
+    pub fn area(&self) -> f64 {
+        let [a, b, c] = self.pts;
+        cross_at(a, b, c).abs() / 2.0
+    }
+
+    // Points on the boundary are contained.
+    #[must_use]
+    pub fn contains(&self, p: Pt) -> bool {
+        let [a, b, c] = self.pts;
+        // |p| is inside if it isn't on opposite sides of any two edges.
+        let d = [Line::new(a, b), Line::new(b, c), Line::new(c, a)].map(|l| orientation(&l, p));
+        if d.contains(&1) && d.contains(&-1) {
+            return false;
+        }
+        // Degenerate triangles are segments, so also check |p| is within
+        // their extent.
+        let (l, r) = (a.x.min(b.x).min(c.x), a.x.max(b.x).max(c.x));
+        let (bt, t) = (a.y.min(b.y).min(c.y), a.y.max(b.y).max(c.y));
+        f64_le(l, p.x) && f64_le(p.x, r) && f64_le(bt, p.y) && f64_le(p.y, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+
+    use super::{path_point_at, Seg, Tri};
+    use crate::geom::math::{f64_eq, pt_eq};
+
+    #[test]
+    fn tri_area_either_winding() {
+        let t = Tri::new(pt(0.0, 0.0), pt(4.0, 0.0), pt(0.0, 3.0));
+        assert!(f64_eq(t.area(), 6.0));
+        let t = Tri::new(pt(0.0, 0.0), pt(0.0, 3.0), pt(4.0, 0.0));
+        assert!(f64_eq(t.area(), 6.0));
+    }
+
+    #[test]
+    fn tri_contains_boundary() {
+        let t = Tri::new(pt(0.0, 0.0), pt(4.0, 0.0), pt(0.0, 4.0));
+        assert!(t.contains(pt(1.0, 1.0)));
+        assert!(t.contains(pt(2.0, 0.0)));
+        assert!(t.contains(pt(2.0, 2.0)));
+        assert!(t.contains(pt(4.0, 0.0)));
+        assert!(!t.contains(pt(3.0, 3.0)));
+        assert!(!t.contains(pt(-0.1, 1.0)));
+    }
+
+    #[test]
+    fn degenerate_tri_is_a_segment() {
+        let t = Tri::new(pt(0.0, 0.0), pt(2.0, 0.0), pt(1.0, 0.0));
+        assert!(t.contains(pt(1.5, 0.0)));
+        assert!(!t.contains(pt(3.0, 0.0)));
+        assert!(f64_eq(t.area(), 0.0));
+    }
+
+    #[test]
+    fn seg_point_at_midpoint() {
+        let s = Seg::new(pt(1.0, 1.0), pt(3.0, 5.0));
+        assert!(pt_eq(s.point_at(0.5), pt(2.0, 3.0)));
+        assert!(pt_eq(s.point_at(0.0), s.st));
+        assert!(pt_eq(s.point_at(1.0), s.en));
+    }
+
+    #[test]
+    fn path_point_at_crosses_segments() {
+        // 3 along x, then 4 up, with a zero length segment at the corner.
+        let pts = [pt(0.0, 0.0), pt(3.0, 0.0), pt(3.0, 0.0), pt(3.0, 4.0)];
+        assert!(pt_eq(path_point_at(&pts, 1.5), pt(1.5, 0.0)));
+        assert!(pt_eq(path_point_at(&pts, 3.0), pt(3.0, 0.0)));
+        assert!(pt_eq(path_point_at(&pts, 5.0), pt(3.0, 2.0)));
+        assert!(pt_eq(path_point_at(&pts, 7.0), pt(3.0, 4.0)));
+        // Clamped to the ends.
+        assert!(pt_eq(path_point_at(&pts, -1.0), pt(0.0, 0.0)));
+        assert!(pt_eq(path_point_at(&pts, 10.0), pt(3.0, 4.0)));
+        assert!(pt_eq(path_point_at(&[pt(1.0, 1.0)], 2.0), pt(1.0, 1.0)));
+    }
+}
@@ -0,0 +1,139 @@
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{circ, path, poly, pt, ShapeOps};
+
+use crate::geom::math::pt_eq;
+use crate::geom::polygon::signed_poly_area;
+
+// Grows |s| outward by |d|, e.g. by a clearance. Rects keep square corners
+// and polygon vertices are mitred, so both cover slightly more than the exact
+// offset at corners.
+#[must_use]
+pub fn offset(s: &Shape, d: f64) -> Shape {
+    match s {
+        Shape::Circle(s) => circ(s.p(), s.r() + d).shape(),
+        Shape::Path(s) => path(s.pts(), s.r() + d).shape(),
+        Shape::Rect(s) => Shape::Rect(s.inset(-d, -d)),
+        Shape::Polygon(s) => poly(&offset_polygon(s.pts(), d)).shape(),
+        s => s.clone(),
+    }
+}
+
+// Moves each edge of the polygon |pts| outward by |d|, joining adjacent edges
+// at their intersection.
+#[must_use]
+pub fn offset_polygon(pts: &[Pt], d: f64) -> Vec<Pt> {
+    let n = pts.len();
+    // Outward normals point right of each edge for counterclockwise polygons.
+    let sign = if signed_poly_area(pts) >= 0.0 { 1.0 } else { -1.0 };
+    let normal = |a: Pt, b: Pt| {
+        let e = b - a;
+        let len = a.dist(b);
+        if len <= 0.0 {
+            return Pt::zero();
+        }
+        pt(sign * e.y / len, -sign * e.x / len)
+    };
+    (0..n)
+        .map(|i| {
+            let (prev, p, next) = (pts[(i + n - 1) % n], pts[i], pts[(i + 1) % n]);
+            let (n1, n2) = (normal(prev, p), normal(p, next));
+            // The mitre point is along n1 + n2, at distance |d| from both edges.
+            let denom = 1.0 + n1.x * n2.x + n1.y * n2.y;
+            if denom <= 0.0 {
+                p + d * n1
+            } else {
+                p + (d / denom) * (n1 + n2)
+            }
+        })
+        .collect()
+}
+
+// Moves each segment of the open path |pts| |d| to its left, or right for
+// negative |d|, joining adjacent segments at their intersection. Every point
+// of the result is |d| from the nearest segment of |pts| as long as segments
+// are longer than the mitres. Repeated points are dropped.
+#[must_use]
+pub fn offset_polyline(pts: &[Pt], d: f64) -> Vec<Pt> {
+    let mut pts = pts.to_vec();
+    pts.dedup_by(|a, b| pt_eq(*a, *b));
+    if pts.len() < 2 {
+        return pts;
+    }
+    let normals: Vec<_> = pts
+        .windows(2)
+        .map(|v| {
+            let e = v[1] - v[0];
+            (1.0 / v[0].dist(v[1])) * pt(-e.y, e.x)
+        })
+        .collect();
+    let last = normals.len() - 1;
+    (0..pts.len())
+        .map(|i| {
+            let (n1, n2) = (normals[i.saturating_sub(1)], normals[i.min(last)]);
+            let denom = 1.0 + n1.x * n2.x + n1.y * n2.y;
+            if denom <= 0.0 {
+                pts[i] + d * n2
+            } else {
+                pts[i] + (d / denom) * (n1 + n2)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{circ, poly, pt, rt, ShapeOps};
+
+    use super::{offset, offset_polyline};
+    use crate::geom::math::{f64_eq, pt_eq};
+    use crate::geom::polygon::poly_area;
+
+    #[test]
+    fn circle_grows_radius() {
+        let Shape::Circle(c) = offset(&circ(pt(1.0, 2.0), 0.5).shape(), 0.25) else {
+            panic!("expected a circle");
+        };
+        assert!(pt_eq(c.p(), pt(1.0, 2.0)));
+        assert!(f64_eq(c.r(), 0.75));
+    }
+
+    #[test]
+    fn rect_grows_each_side() {
+        let Shape::Rect(r) = offset(&rt(0.0, 0.0, 2.0, 1.0).shape(), 0.5) else {
+            panic!("expected a rect");
+        };
+        assert!(f64_eq(r.w() * r.h(), 3.0 * 2.0));
+    }
+
+    #[test]
+    fn polygon_edges_move_out_either_winding() {
+        let square = [pt(0.0, 0.0), pt(2.0, 0.0), pt(2.0, 2.0), pt(0.0, 2.0)];
+        let reversed: Vec<_> = square.iter().rev().copied().collect();
+        for pts in [square.to_vec(), reversed] {
+            let Shape::Polygon(p) = offset(&poly(&pts).shape(), 1.0) else {
+                panic!("expected a polygon");
+            };
+            assert!(f64_eq(poly_area(p.pts()), 16.0));
+        }
+    }
+
+    #[test]
+    fn polyline_keeps_segments_parallel() {
+        // Right, then up and left at 45 degrees, then up.
+        let pts = [pt(0.0, 0.0), pt(4.0, 0.0), pt(4.0, 0.0), pt(2.0, 2.0), pt(2.0, 5.0)];
+        let left = offset_polyline(&pts, 0.5);
+        let right = offset_polyline(&pts, -0.5);
+        assert_eq!(left.len(), 4);
+        assert!(pt_eq(left[0], pt(0.0, 0.5)));
+        assert!(pt_eq(right[0], pt(0.0, -0.5)));
+        assert!(pt_eq(left[3], pt(1.5, 5.0)));
+        assert!(pt_eq(right[3], pt(2.5, 5.0)));
+        // Mitred corners sit where the offset segments meet.
+        let h = 0.5 * 2.0_f64.sqrt();
+        assert!(pt_eq(left[1], pt(3.5 - h, 0.5)));
+        assert!(pt_eq(left[2], pt(1.5, 2.5 - h)));
+        assert_eq!(offset_polyline(&[pt(1.0, 1.0)], 0.5), vec![pt(1.0, 1.0)]);
+    }
+}
@@ -0,0 +1,150 @@
+use memegeom::primitive::point::Pt;
+
+use crate::geom::math::{cross_at, f64_eq};
+use crate::geom::Tri;
+
+#[must_use]
+pub fn poly_area(pts: &[Pt]) -> f64 {
+    signed_poly_area(pts).abs()
+}
+
+// Shoelace formula. Positive for counterclockwise polygons.
+#[must_use]
+pub fn signed_poly_area(pts: &[Pt]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..pts.len() {
+        let a = pts[i];
+        let b = pts[(i + 1) % pts.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+// Whether every corner of |pts| turns the same way. Collinear corners are
+// allowed, so a square with a point in the middle of a side is convex.
+#[must_use]
+pub fn is_convex(pts: &[Pt]) -> bool {
+    let n = pts.len();
+    let (mut pos, mut neg) = (false, false);
+    for i in 0..n {
+        let c = cross_at(pts[i], pts[(i + 1) % n], pts[(i + 2) % n]);
+        if f64_eq(c, 0.0) {
+            continue;
+        }
+        pos |= c > 0.0;
+        neg |= c < 0.0;
+    }
+    !(pos && neg)
+}
+
+// Splits a simple polygon into triangles. Convex polygons are fanned from
+// their first point; others are ear clipped.
+#[must_use]
+pub fn triangulate(pts: &[Pt]) -> Vec<Tri> {
+    if pts.len() < 3 {
+        return Vec::new();
+    }
+    if is_convex(pts) {
+        return (1..pts.len() - 1).map(|i| Tri::new(pts[0], pts[i], pts[i + 1])).collect();
+    }
+    let tris = ear_clip(pts);
+    // Triangles inside the polygon cover exactly its area.
+    debug_assert!(
+        f64_eq(tris.iter().map(Tri::area).sum(), poly_area(pts)),
+        "triangulation leaves the polygon"
+    );
+    tris
+}
+
+fn ear_clip(pts: &[Pt]) -> Vec<Tri> {
+    // Work counterclockwise so ears are the left turns.
+    let mut idx: Vec<usize> = (0..pts.len()).collect();
+    if signed_poly_area(pts) < 0.0 {
+        idx.reverse();
+    }
+    let mut tris = Vec::new();
+    while idx.len() > 3 {
+        let n = idx.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (pts[idx[(i + n - 1) % n]], pts[idx[i]], pts[idx[(i + 1) % n]]);
+            if cross_at(a, b, c) <= 0.0 {
+                return false;
+            }
+            let t = Tri::new(a, b, c);
+            // No other remaining vertex may be inside the ear.
+            idx.iter().all(|&j| {
+                let p = pts[j];
+                p == a || p == b || p == c || !t.contains(p)
+            })
+        });
+        // Only degenerate input has no ear; clip anything to make progress.
+        let i = ear.unwrap_or(0);
+        tris.push(Tri::new(pts[idx[(i + n - 1) % n]], pts[idx[i]], pts[idx[(i + 1) % n]]));
+        idx.remove(i);
+    }
+    tris.push(Tri::new(pts[idx[0]], pts[idx[1]], pts[idx[2]]));
+    tris
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+
+    use super::{is_convex, poly_area, signed_poly_area, triangulate};
+    use crate::geom::math::{cross_at, f64_eq};
+
+    #[test]
+    fn convexity() {
+        let square = [pt(0.0, 0.0), pt(1.0, 0.0), pt(2.0, 0.0), pt(2.0, 2.0), pt(0.0, 2.0)];
+        assert!(is_convex(&square));
+        let notch = [pt(0.0, 0.0), pt(4.0, 0.0), pt(4.0, 4.0), pt(2.0, 2.0), pt(0.0, 4.0)];
+        assert!(!is_convex(&notch));
+    }
+
+    #[test]
+    fn convex_polygon_is_fanned() {
+        let hex =
+            [pt(1.0, 0.0), pt(2.0, 0.0), pt(3.0, 1.0), pt(2.0, 2.0), pt(1.0, 2.0), pt(0.0, 1.0)];
+        let tris = triangulate(&hex);
+        assert_eq!(tris.len(), 4);
+        assert!(tris.iter().all(|t| t.pts[0] == hex[0]));
+        let area: f64 = tris.iter().map(super::Tri::area).sum();
+        assert!(f64_eq(area, poly_area(&hex)));
+    }
+
+    #[test]
+    fn concave_polygon_triangles_stay_inside() {
+        // A "U" shape, clockwise, so a fan from any corner would leave it.
+        let u = [
+            pt(0.0, 0.0),
+            pt(0.0, 3.0),
+            pt(1.0, 3.0),
+            pt(1.0, 1.0),
+            pt(2.0, 1.0),
+            pt(2.0, 3.0),
+            pt(3.0, 3.0),
+            pt(3.0, 0.0),
+        ];
+        let tris = triangulate(&u);
+        assert_eq!(tris.len(), u.len() - 2);
+        // Triangles that all wind counterclockwise and sum to the polygon's
+        // area can't stick out of it or overlap.
+        for t in &tris {
+            let [a, b, c] = t.pts;
+            assert!(cross_at(a, b, c) > 0.0, "bad triangle {t:?}");
+            // The notch between the arms must not be covered.
+            assert!(!t.contains(pt(1.5, 2.0)), "triangle {t:?} covers the notch");
+        }
+        let area: f64 = tris.iter().map(super::Tri::area).sum();
+        assert!(f64_eq(area, poly_area(&u)));
+    }
+
+    #[test]
+    fn area_sign_follows_winding() {
+        let ccw = [pt(0.0, 0.0), pt(2.0, 0.0), pt(2.0, 3.0), pt(0.0, 3.0)];
+        let cw: Vec<_> = ccw.iter().rev().copied().collect();
+        assert!(f64_eq(signed_poly_area(&ccw), 6.0));
+        assert!(f64_eq(signed_poly_area(&cw), -6.0));
+        assert!(f64_eq(poly_area(&cw), 6.0));
+    }
+}
@@ -0,0 +1,63 @@
+// Checks that each intersection test agrees with the matching distance: two
+// shapes intersect exactly when the distance between them is zero, up to
+// |EP|. Coordinates are on a quarter unit grid so touching and collinear
+// cases come up often.
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::{circ, pt};
+use proptest::prelude::*;
+
+use crate::geom::distance::line_pt_dist;
+use crate::geom::intersects::{
+    circ_intersects_line, line_intersects_line, pt_intersects_line, pt_intersects_pt,
+};
+use crate::geom::math::{f64_eq, f64_le};
+use crate::geom::Line;
+
+fn coord() -> impl Strategy<Value = f64> {
+    (-16_i32..=16).prop_map(|v| f64::from(v) / 4.0)
+}
+
+fn point() -> impl Strategy<Value = Pt> {
+    (coord(), coord()).prop_map(|(x, y)| pt(x, y))
+}
+
+fn line() -> impl Strategy<Value = Line> {
+    (point(), point())
+        .prop_filter("degenerate line", |(a, b)| a != b)
+        .prop_map(|(a, b)| Line::new(a, b))
+}
+
+fn radius() -> impl Strategy<Value = f64> {
+    (1_i32..=12).prop_map(|v| f64::from(v) / 4.0)
+}
+
+proptest! {
+    #[test]
+    fn pt_pt(a in point(), b in point()) {
+        prop_assert_eq!(pt_intersects_pt(a, b), f64_eq(a.dist(b), 0.0));
+    }
+
+    #[test]
+    fn pt_line(p in point(), l in line()) {
+        prop_assert_eq!(pt_intersects_line(p, &l), f64_eq(line_pt_dist(&l, p), 0.0));
+    }
+
+    #[test]
+    fn circ_line(p in point(), r in radius(), l in line()) {
+        let c = circ(p, r);
+        let dist = (line_pt_dist(&l, p) - r).max(0.0);
+        prop_assert_eq!(circ_intersects_line(&c, &l), f64_le(dist, 0.0));
+    }
+
+    #[test]
+    fn line_line(a in line(), b in line()) {
+        // Lines that don't intersect are parallel and a positive distance
+        // apart everywhere.
+        if !line_intersects_line(&a, &b) {
+            let dist = line_pt_dist(&a, b.st);
+            prop_assert!(!f64_le(dist, 0.0));
+            prop_assert!(f64_eq(dist, line_pt_dist(&a, b.en)));
+        }
+        prop_assert_eq!(line_intersects_line(&a, &b), line_intersects_line(&b, &a));
+    }
+}
@@ -0,0 +1,293 @@
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{rt, ShapeOps};
+
+use crate::geom::distance::shape_dist;
+use crate::geom::intersects::rt_intersects_rt;
+use crate::geom::math::f64_le;
+
+// Leaves split once they hold more shapes than this.
+const MAX_ITEMS: usize = 8;
+const MAX_DEPTH: usize = 10;
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Rt,
+    // Shapes that don't fit in a single child.
+    items: Vec<usize>,
+    // Index of the first of four consecutive children.
+    children: Option<usize>,
+}
+
+impl Node {
+    fn new(bounds: Rt) -> Self {
+        Self { bounds, items: Vec::new(), children: None }
+    }
+}
+
+// Shapes indexed by their bounds. Each shape lives in the smallest quadrant
+// that holds all of it, so queries only visit quadrants that overlap the
+// query's reach. Shapes outside |bounds| are kept at the root.
+// Unlike memegeom's |Compound| this hands back which shape matched, which
+// callers need to report it.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct Quadtree {
+    // Removed shapes leave a gap so other indices stay the same.
+    shapes: Vec<Option<(Shape, Rt)>>,
+    nodes: Vec<Node>,
+    len: usize,
+}
+
+impl Quadtree {
+    pub fn new(bounds: Rt) -> Self {
+        Self { shapes: Vec::new(), nodes: vec![Node::new(bounds)], len: 0 }
+    }
+
+    // Adds |s| and returns its index, which counts up from 0.
+    pub fn insert(&mut self, s: Shape) -> usize {
+        let idx = self.shapes.len();
+        let bounds = s.bounds();
+        self.shapes.push(Some((s, bounds)));
+        self.len += 1;
+        self.insert_at(0, idx, 0);
+        idx
+    }
+
+    // Removes the shape at |idx|, if it is still there.
+    pub fn remove(&mut self, idx: usize) {
+        let Some((_, b)) = self.shapes.get_mut(idx).and_then(Option::take) else {
+            return;
+        };
+        self.len -= 1;
+        // Follow the path |insert_at| would take now, which ends at the node
+        // holding it.
+        let mut n = 0;
+        loop {
+            if let Some(pos) = self.nodes[n].items.iter().position(|&i| i == idx) {
+                self.nodes[n].items.swap_remove(pos);
+                return;
+            }
+            let child = self.nodes[n].children.and_then(|first| {
+                (first..first + 4).find(|&c| encloses(&self.nodes[c].bounds, &b))
+            });
+            let Some(c) = child else {
+                return;
+            };
+            n = c;
+        }
+    }
+
+    // Panics if the shape at |idx| was removed.
+    #[must_use]
+    pub fn shape(&self, idx: usize) -> &Shape {
+        &self.shapes[idx].as_ref().expect("shape was removed").0
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Bounds of every quadrant, for drawing the tree.
+    #[must_use]
+    pub fn rts(&self) -> Vec<Rt> {
+        self.nodes.iter().map(|n| n.bounds).collect()
+    }
+
+    // Nearest shape to |s| no further than |radius| from it, and the distance
+    // to it. Ties go to the shape inserted first.
+    #[must_use]
+    pub fn nearest(&self, s: &Shape, radius: f64) -> Option<(usize, f64)> {
+        let mut best: Option<(usize, f64)> = None;
+        for idx in self.candidates(s, radius) {
+            let d = shape_dist(s, self.shape(idx));
+            if !f64_le(d, radius) {
+                continue;
+            }
+            if best.map_or(true, |(b, bd)| d.total_cmp(&bd).then(idx.cmp(&b)).is_lt()) {
+                best = Some((idx, d));
+            }
+        }
+        best
+    }
+
+    // Whether any shape is within |clearance| of |s|, including touching or
+    // overlapping it.
+    #[must_use]
+    pub fn any_within(&self, s: &Shape, clearance: f64) -> bool {
+        self.candidates(s, clearance)
+            .into_iter()
+            .any(|idx| f64_le(shape_dist(s, self.shape(idx)), clearance))
+    }
+
+    // Indices of all shapes within |dist| of |s|, in insertion order.
+    #[must_use]
+    pub fn within(&self, s: &Shape, dist: f64) -> Vec<usize> {
+        let mut idxs: Vec<_> = self
+            .candidates(s, dist)
+            .into_iter()
+            .filter(|&idx| f64_le(shape_dist(s, self.shape(idx)), dist))
+            .collect();
+        idxs.sort_unstable();
+        idxs
+    }
+
+    // Shapes whose bounds come within |dist| of the bounds of |s|, in no
+    // particular order. Callers do the exact test.
+    #[must_use]
+    pub fn candidates(&self, s: &Shape, dist: f64) -> Vec<usize> {
+        let reach = s.bounds().inset(-dist, -dist);
+        let mut idxs = Vec::new();
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            idxs.extend(
+                node.items.iter().copied().filter(|&i| rt_intersects_rt(&self.bounds(i), &reach)),
+            );
+            if let Some(first) = node.children {
+                stack.extend(
+                    (first..first + 4).filter(|&c| rt_intersects_rt(&self.nodes[c].bounds, &reach)),
+                );
+            }
+        }
+        idxs
+    }
+
+    fn insert_at(&mut self, n: usize, idx: usize, depth: usize) {
+        if let Some(first) = self.nodes[n].children {
+            let b = self.bounds(idx);
+            if let Some(c) = (first..first + 4).find(|&c| encloses(&self.nodes[c].bounds, &b)) {
+                self.insert_at(c, idx, depth + 1);
+            } else {
+                self.nodes[n].items.push(idx);
+            }
+            return;
+        }
+        self.nodes[n].items.push(idx);
+        if self.nodes[n].items.len() > MAX_ITEMS && depth < MAX_DEPTH {
+            self.split(n, depth);
+        }
+    }
+
+    fn bounds(&self, idx: usize) -> Rt {
+        self.shapes[idx].as_ref().expect("shape was removed").1
+    }
+
+    fn split(&mut self, n: usize, depth: usize) {
+        let b = self.nodes[n].bounds;
+        let c = b.center();
+        let first = self.nodes.len();
+        self.nodes.extend(
+            [
+                rt(b.l(), b.b(), c.x, c.y),
+                rt(c.x, b.b(), b.r(), c.y),
+                rt(b.l(), c.y, c.x, b.t()),
+                rt(c.x, c.y, b.r(), b.t()),
+            ]
+            .into_iter()
+            .map(Node::new),
+        );
+        self.nodes[n].children = Some(first);
+        for idx in std::mem::take(&mut self.nodes[n].items) {
+            self.insert_at(n, idx, depth);
+        }
+    }
+}
+
+fn encloses(a: &Rt, b: &Rt) -> bool {
+    a.l() <= b.l() && b.r() <= a.r() && a.b() <= b.b() && b.t() <= a.t()
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{circ, path, pt, rt, ShapeOps};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::Quadtree;
+    use crate::geom::distance::shape_dist;
+    use crate::geom::math::{f64_eq, f64_le};
+
+    // Small circles and short traces scattered over a 20 mm square, with a
+    // few landing outside it.
+    fn random_shapes(rng: &mut StdRng, n: usize) -> Vec<Shape> {
+        let mut p = || pt(rng.gen_range(-2.0..22.0), rng.gen_range(-2.0..22.0));
+        (0..n)
+            .map(|i| {
+                if i % 2 == 0 {
+                    circ(p(), 0.1 + (i % 5) as f64 * 0.2).shape()
+                } else {
+                    let st = p();
+                    let en = st + pt((i % 7) as f64 - 3.0, (i % 3) as f64);
+                    path(&[st, en], 0.1).shape()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let shapes = random_shapes(&mut rng, 300);
+        let mut qt = Quadtree::new(rt(0.0, 0.0, 20.0, 20.0));
+        for (i, s) in shapes.iter().enumerate() {
+            assert_eq!(qt.insert(s.clone()), i);
+        }
+        assert_eq!(qt.len(), shapes.len());
+
+        for q in random_shapes(&mut rng, 100) {
+            for d in [0.0, 0.3, 1.5] {
+                let dists: Vec<_> = shapes.iter().map(|s| shape_dist(&q, s)).collect();
+                let want: Vec<_> = (0..shapes.len()).filter(|&i| f64_le(dists[i], d)).collect();
+                assert_eq!(qt.within(&q, d), want, "{q:?} {d}");
+                assert_eq!(qt.any_within(&q, d), !want.is_empty(), "{q:?} {d}");
+                let nearest = want.iter().copied().min_by(|&a, &b| dists[a].total_cmp(&dists[b]));
+                assert_eq!(qt.nearest(&q, d).map(|v| v.0), nearest, "{q:?} {d}");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_and_single() {
+        let mut qt = Quadtree::new(rt(0.0, 0.0, 10.0, 10.0));
+        let q = circ(pt(5.0, 5.0), 1.0).shape();
+        assert!(qt.is_empty());
+        assert_eq!(qt.nearest(&q, 100.0), None);
+        assert!(!qt.any_within(&q, 100.0));
+
+        qt.insert(circ(pt(8.0, 5.0), 1.0).shape());
+        assert!(!qt.any_within(&q, 0.5));
+        assert!(qt.any_within(&q, 1.0));
+        let (idx, d) = qt.nearest(&q, 2.0).unwrap();
+        assert_eq!(idx, 0);
+        assert!(f64_eq(d, 1.0));
+    }
+
+    #[test]
+    fn removed_shapes_are_not_found() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let shapes = random_shapes(&mut rng, 100);
+        let mut qt = Quadtree::new(rt(0.0, 0.0, 20.0, 20.0));
+        for s in &shapes {
+            qt.insert(s.clone());
+        }
+        for idx in (0..shapes.len()).step_by(3) {
+            qt.remove(idx);
+        }
+        qt.remove(0);
+        assert_eq!(qt.len(), shapes.len() - shapes.len().div_ceil(3));
+
+        let q = rt(-5.0, -5.0, 25.0, 25.0).shape();
+        let want: Vec<_> = (0..shapes.len()).filter(|i| i % 3 != 0).collect();
+        assert_eq!(qt.within(&q, 0.0), want);
+        // Indices keep counting up after removals.
+        assert_eq!(qt.insert(shapes[0].clone()), shapes.len());
+    }
+}
@@ -0,0 +1,72 @@
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::polygon::Poly;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{poly, ShapeOps};
+use memegeom::tf::Tf;
+
+use crate::geom::math::f64_eq;
+
+// Corners of |r| under |tf|, as a polygon. Unlike |Tf::shape|, this keeps
+// the exact outline of a rect rotated by something other than a multiple of
+// 90 degrees.
+pub fn rt_tf(tf: &Tf, r: &Rt) -> Poly {
+    let pts: Vec<_> = r.pts().iter().map(|&p| tf.pt(p)).collect();
+    poly(&pts)
+}
+
+// Whether every edge of the polygon |pts| is horizontal or vertical.
+#[must_use]
+pub fn is_axis_aligned(pts: &[Pt]) -> bool {
+    (0..pts.len()).all(|i| {
+        let (a, b) = (pts[i], pts[(i + 1) % pts.len()]);
+        f64_eq(a.x, b.x) || f64_eq(a.y, b.y)
+    })
+}
+
+// Applies |tf| to |s|. Rects that are no longer axis aligned become polygons;
+// everything else is transformed as |Tf::shape| does.
+#[must_use]
+pub fn shape_tf(tf: &Tf, s: &Shape) -> Shape {
+    if let Shape::Rect(r) = s {
+        let p = rt_tf(tf, r);
+        if !is_axis_aligned(p.pts()) {
+            return p.shape();
+        }
+    }
+    tf.shape(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{pt, rt, ShapeOps};
+    use memegeom::tf::Tf;
+
+    use super::shape_tf;
+    use crate::geom::math::pt_eq;
+
+    #[test]
+    fn rotated_square_is_a_diamond() {
+        let s = rt(-0.5, -0.5, 0.5, 0.5).shape();
+        let Shape::Polygon(p) = shape_tf(&Tf::rotate(45.0), &s) else {
+            panic!("expected a polygon");
+        };
+        let h = 0.5_f64.sqrt();
+        let want = [pt(0.0, -h), pt(h, 0.0), pt(0.0, h), pt(-h, 0.0)];
+        assert_eq!(p.pts().len(), 4);
+        for w in want {
+            assert!(p.pts().iter().any(|&v| pt_eq(v, w)), "missing corner {w:?}");
+        }
+    }
+
+    #[test]
+    fn quarter_turn_keeps_rect() {
+        let s = rt(0.0, 0.0, 2.0, 1.0).shape();
+        let Shape::Rect(r) = shape_tf(&Tf::rotate(90.0), &s) else {
+            panic!("expected a rect");
+        };
+        assert!(pt_eq(r.bl(), pt(-1.0, 0.0)));
+        assert!(pt_eq(r.tr(), pt(0.0, 2.0)));
+    }
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::Path;
+
+use memedsn::lexer::Lexer;
+use memedsn::parser::Parser;
+
+use crate::dsn::design_to_pcb::DesignToPcb;
+use crate::dsn::pcb_to_session::PcbToSession;
+use crate::dsn::session_to_pcb::SessionToPcb;
+use crate::dsn::sexp;
+use crate::error::MemerouteError;
+use crate::model::pcb::Pcb;
+use crate::route::router::{apply_route_result, GaParams, RouteResult, Router};
+
+#[must_use]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct RouteOptions {
+    pub seed: Option<u64>,
+    pub ga: bool, // Search net orders with the GA instead of routing one random order.
+    pub clearance: Option<f64>, // Replaces every clearance rule in the design.
+    // Grid pitch in mm. Finer is slower but gets through tighter gaps. If
+    // unset, it's derived from the smallest pad spacing and clearance.
+    pub grid: Option<f64>,
+}
+
+// Parses a DSN file and converts it to a Pcb. Errors name the file and the
+// stage that failed.
+pub fn load_pcb<P: AsRef<Path>>(path: P) -> Result<Pcb, MemerouteError> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path).map_err(MemerouteError::io(path))?;
+    let dsn = |e: eyre::Report, stage: &str| {
+        MemerouteError::Dsn(e.wrap_err(format!("{stage} {}", path.display())))
+    };
+    // memedsn doesn't say where in the file it went wrong, so check the
+    // nesting first to give the line and column of any mismatched ( or ).
+    sexp::parse(&data).map_err(|e| dsn(e, "parsing"))?;
+    let lexer = Lexer::new(&data).map_err(|e| dsn(e, "lexing"))?;
+    let tokens = lexer.lex().map_err(|e| dsn(e, "lexing"))?;
+    let pcb = Parser::new(&tokens).parse().map_err(|e| dsn(e, "parsing"))?;
+    DesignToPcb::new(pcb)
+        .convert()
+        .map_err(|e| e.wrap_err(format!("converting {}", path.display())))
+}
+
+// Adds the wires and vias of a session file, e.g. one written by |route_file|,
+// to |pcb|.
+pub fn load_session<P: AsRef<Path>>(pcb: Pcb, path: P) -> Result<Pcb, MemerouteError> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path).map_err(MemerouteError::io(path))?;
+    SessionToPcb::new(pcb, data)
+        .convert()
+        .map_err(|e| e.wrap_err(format!("loading {}", path.display())))
+}
+
+// Routes the DSN file |input| and writes the routed wires and vias to
+// |output| as a session file.
+pub fn route_file(
+    input: &Path,
+    output: &Path,
+    opts: RouteOptions,
+) -> Result<RouteResult, MemerouteError> {
+    let mut pcb = load_pcb(input)?;
+    if let Some(clearance) = opts.clearance {
+        pcb.override_clearance(clearance);
+    }
+    let mut router = match opts.seed {
+        Some(seed) => Router::with_seed(pcb.clone(), seed),
+        None => Router::new(pcb.clone()),
+    };
+    router.set_grid_pitch(opts.grid);
+    let mut res = if opts.ga {
+        router
+            .run_ga(&GaParams { seed: opts.seed, ..GaParams::default() })
+            .map_err(MemerouteError::Route)?
+    } else {
+        router.route(router.rand_net_order())?
+    };
+    res.simplify();
+    apply_route_result(&mut pcb, &res);
+    fs::write(output, PcbToSession::new(pcb).convert()?).map_err(MemerouteError::io(output))?;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use memegeom::primitive::pt;
+
+    use crate::dsn::pcb_to_session::PcbToSession;
+    use crate::dsn::session_to_pcb::SessionToPcb;
+    use crate::error::MemerouteError;
+    use crate::headless::{load_pcb, load_session, route_file, RouteOptions};
+    use crate::route::router::Router;
+    use crate::testing::{add_component, add_net, board, dsn, wire, UM};
+
+    fn temp_file(name: &str, data: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn route_file_writes_session() {
+        let body = "(placement
+            (component part (place U1 2000 5000 front 0) (place U2 8000 5000 front 0)))
+          (library
+            (image part (pin pad 1 0 0))
+            (padstack pad (shape (circle F.Cu 600)) (attach off))
+            (padstack via (shape (circle signal 600)) (attach off)))
+          (network (net N1 (pins U1-1 U2-1)))
+          (wiring)";
+        let input = temp_file("route.dsn", &dsn(UM, "(via via)", body));
+        let output = std::env::temp_dir().join(format!("route.ses-{}", std::process::id()));
+        let res = route_file(&input, &output, RouteOptions { seed: Some(1), ..Default::default() });
+        let pcb = load_pcb(&input).unwrap();
+        let loaded = load_session(pcb.clone(), &output);
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+        let res = res.unwrap();
+        assert!(!res.failed);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+
+        // The session holds the route, and puts it back on the board as is.
+        let loaded = loaded.unwrap();
+        let n1 = pcb.to_id("N1");
+        assert!(!loaded.wires().is_empty());
+        assert_eq!(loaded.wires().len(), res.wires.len());
+        assert!(loaded.wires().iter().all(|w| w.net_id == n1));
+        assert_eq!(loaded.vias().len(), res.vias.len());
+        assert!(loaded.drc().is_empty(), "{:?}", loaded.drc());
+    }
+
+    #[test]
+    fn missing_file_is_io() {
+        let path = Path::new("/nonexistent/board.dsn");
+        let err = load_pcb(path).unwrap_err();
+        assert!(matches!(&err, MemerouteError::Io { path: p, .. } if p == path));
+        assert!(err.report().is_none());
+        assert!(load_session(board(10.0, 10.0), path).is_err());
+    }
+
+    #[test]
+    fn malformed_dsn_is_dsn() {
+        let path = temp_file("malformed.dsn", "(pcb test.dsn (structure");
+        let err = load_pcb(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, MemerouteError::Dsn(_)));
+        assert!(err.to_string().contains("malformed.dsn"));
+        assert!(err.to_string().contains("unterminated (structure at line 1, column 15"), "{err}");
+
+        // A ( left open a few lines into a full design.
+        let data = dsn(UM, "(keepout (rect F.Cu 0 0 10 10)", "(library) (network) (wiring)");
+        let line = data.lines().position(|l| l.contains("(keepout")).unwrap() + 1;
+        let path = temp_file("unterminated.dsn", &data);
+        let err = load_pcb(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        let want = format!("(keepout at line {line}, column 5 may be missing its )");
+        assert!(err.to_string().contains(&want), "{err}");
+
+        // Parses fine, but the placement refers to an image that isn't there.
+        let data = dsn(
+            UM,
+            "",
+            "(placement (component missing (place U1 0 0 front 0))) (library) (network) (wiring)",
+        );
+        let path = temp_file("unknown-image.dsn", &data);
+        let err = load_pcb(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, MemerouteError::Dsn(_)));
+    }
+
+    #[test]
+    fn bad_session_is_session() {
+        let err = SessionToPcb::new(board(10.0, 10.0), "(design)".to_owned()).convert();
+        assert!(matches!(err, Err(MemerouteError::Session(_))));
+
+        let path = temp_file("bad.ses", "(session test");
+        let err = load_session(board(10.0, 10.0), &path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, MemerouteError::Session(_)));
+        assert!(err.to_string().contains("bad.ses"));
+    }
+
+    #[test]
+    fn unnamed_layer_is_export() {
+        let mut pcb = board(10.0, 10.0);
+        let net_id = pcb.to_id("N1");
+        // Layer 5 has no name, so the session can't refer to it.
+        pcb.add_wire(wire(net_id, 5, &[pt(1.0, 1.0), pt(2.0, 1.0)]));
+        let err = PcbToSession::new(pcb).convert();
+        assert!(matches!(err, Err(MemerouteError::Export(_))));
+    }
+
+    #[test]
+    fn invalid_pcb_is_route() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let router = Router::new(pcb);
+        let err = router.route(router.rand_net_order()).unwrap_err();
+        assert!(matches!(err, MemerouteError::Route(_)));
+    }
+}
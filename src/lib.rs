@@ -39,6 +39,13 @@
 )]
 
 pub mod dsn;
+pub mod error;
+pub mod geom;
+pub mod headless;
 pub mod model;
 pub mod name;
 pub mod route;
+#[cfg(test)]
+mod testing;
+
+pub use headless::{route_file, RouteOptions};
@@ -1,21 +1,30 @@
 use std::collections::hash_map::Values;
 use std::collections::HashMap;
+use std::f64::consts::PI;
 use std::iter::FromIterator;
 use std::sync::RwLock;
 
 use auto_ops::{impl_op_ex, impl_op_ex_commutative};
-use enumset::{enum_set, EnumSet, EnumSetType};
+use enumset::{EnumSet, EnumSetType};
 use eyre::{eyre, Result};
 use memegeom::geom::bounds::rt_cloud_bounds;
-use memegeom::geom::qt::query::Kinds;
 use memegeom::primitive::point::Pt;
 use memegeom::primitive::rect::Rt;
 use memegeom::primitive::shape::Shape;
-use memegeom::primitive::{pt, ShapeOps};
+use memegeom::primitive::{circ, pt, rt, ShapeOps};
 use memegeom::tf::Tf;
 use rust_dense_bitset::{BitSet, DenseBitSet};
+use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
+use crate::error::PcbError;
+use crate::geom::distance::{seg_nearest_pt, shape_dist};
+use crate::geom::intersects::rt_intersects_rt;
+use crate::geom::math::{f64_le, f64_lt};
+use crate::geom::offset::offset;
+use crate::geom::polygon::poly_area;
+use crate::geom::quadtree::Quadtree;
+use crate::geom::transform::shape_tf;
 use crate::name::{Id, NameMap};
 
 // File-format independent representation of a PCB.
@@ -25,22 +34,43 @@ use crate::name::{Id, NameMap};
 pub type LayerId = usize;
 
 #[must_use]
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, EnumIter)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
 pub enum LayerKind {
     All,
     Signal,
-    Power,
+    Power, // Planes, which aren't routed on.
     Mixed,
     Jumper,
 }
 
+impl LayerKind {
+    // Whether the router may place wires on layers of this kind.
+    #[must_use]
+    pub fn is_routable(self) -> bool {
+        matches!(self, LayerKind::Signal | LayerKind::Mixed | LayerKind::Jumper)
+    }
+}
+
 // Support up to 64 layers.
 #[must_use]
-#[derive(Debug, Default, Hash, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, Default, Hash, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
 pub struct LayerSet {
     l: DenseBitSet,
 }
 
+impl From<u64> for LayerSet {
+    fn from(v: u64) -> Self {
+        Self { l: DenseBitSet::from_integer(v) }
+    }
+}
+
+impl From<LayerSet> for u64 {
+    fn from(v: LayerSet) -> Self {
+        v.l.to_integer()
+    }
+}
+
 impl_op_ex!(| |a: &LayerSet, b: &LayerSet| -> LayerSet {LayerSet {l: a.l | b.l}});
 impl_op_ex_commutative!(| |a: &LayerSet, b: &LayerId| -> LayerSet {let mut copy = *a; copy |= b; copy});
 impl_op_ex!(|= |a: &mut LayerSet, b: &LayerSet| {a.l |= b.l;});
@@ -152,7 +182,7 @@ impl Iterator for BitSetIterator {
 // Describes a layer in a PCB. Layers should be numbered from 0 up, contiguously.
 // Layers should be in order of physical stackup.
 #[must_use]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Layer {
     pub name_id: Id,
     pub layer_id: LayerId, // Should be less than 64.
@@ -160,7 +190,7 @@ pub struct Layer {
 }
 
 #[must_use]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LayerShape {
     pub layers: LayerSet,
     pub shape: Shape,
@@ -176,7 +206,7 @@ impl LayerShape {
 // ViaKeepout: No vias.
 // WireKeepout: No wires.
 #[must_use]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum KeepoutType {
     Keepout,
     ViaKeepout,
@@ -185,7 +215,7 @@ pub enum KeepoutType {
 
 // Describes a keepout area.
 #[must_use]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Keepout {
     pub kind: KeepoutType,
     pub shape: LayerShape,
@@ -199,7 +229,7 @@ impl Keepout {
 
 // Describes a pin.
 #[must_use]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Pin {
     pub id: Id,
     pub padstack: Padstack,
@@ -220,7 +250,7 @@ impl Pin {
 
 // Describes a component at a location.
 #[must_use]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Component {
     pub id: Id,
     // Id of the footprint for this component. Only used in exporting currently.
@@ -248,6 +278,8 @@ impl Component {
         self.pins.get(&id)
     }
 
+    // Maps image coordinates to world coordinates. The image is mirrored first
+    // if the component is on the back, then rotated, then translated.
     #[must_use]
     pub fn tf(&self) -> Tf {
         // Being on the back mirrors, i.e. horizontal flip.
@@ -255,6 +287,54 @@ impl Component {
         Tf::translate(self.p) * Tf::rotate(self.rotation) * side_tf
     }
 
+    // Maps pin-local coordinates of |pin| to world coordinates.
+    #[must_use]
+    pub fn pin_tf(&self, pin: &Pin) -> Tf {
+        self.tf() * pin.tf()
+    }
+
+    // World position of the origin of |pin|.
+    #[must_use]
+    pub fn pin_pt(&self, pin: &Pin) -> Pt {
+        self.pin_tf(pin).pt(Pt::zero())
+    }
+
+    // Point on the copper of |pin| nearest to |target|, kept at least |inset|
+    // inside the pad edge so a wire ending there still overlaps the pad. Falls
+    // back to the pin origin for pad shapes other than rects, circles, and
+    // paths.
+    #[must_use]
+    pub fn pin_connection_pt(&self, pin: &Pin, target: Pt, inset: f64) -> Pt {
+        let tf = self.pin_tf(pin);
+        let origin = tf.pt(Pt::zero());
+        let mut best: Option<Pt> = None;
+        for ls in &pin.padstack.shapes {
+            let p = match shape_tf(&tf, &ls.shape) {
+                Shape::Rect(r) => {
+                    let (dx, dy) = ((r.w() / 2.0).min(inset), (r.h() / 2.0).min(inset));
+                    pt(
+                        target.x.max(r.l() + dx).min(r.r() - dx),
+                        target.y.max(r.b() + dy).min(r.t() - dy),
+                    )
+                }
+                Shape::Circle(c) => toward(c.p(), target, c.r() - inset),
+                Shape::Path(p) => {
+                    let nearest = p
+                        .pts()
+                        .windows(2)
+                        .map(|w| seg_nearest_pt(w[0], w[1], target))
+                        .min_by(|a, b| a.dist(target).total_cmp(&b.dist(target)));
+                    nearest.map_or(origin, |q| toward(q, target, p.r() - inset))
+                }
+                _ => origin,
+            };
+            if best.map_or(true, |b| p.dist(target) < b.dist(target)) {
+                best = Some(p);
+            }
+        }
+        best.unwrap_or(origin)
+    }
+
     pub fn flip(&mut self, num_layers: usize) {
         self.flipped = !self.flipped;
         for v in &mut self.outlines {
@@ -276,7 +356,7 @@ impl Component {
 
 // Describes a padstack.
 #[must_use]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Padstack {
     pub id: Id,
     pub shapes: Vec<LayerShape>,
@@ -296,7 +376,7 @@ impl Padstack {
 }
 
 #[must_use]
-#[derive(Debug, Hash, PartialEq, Eq, Default, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, Default, Clone, Serialize, Deserialize)]
 pub struct PinRef {
     pub component: Id,
     pub pin: Id,
@@ -309,15 +389,47 @@ impl PinRef {
 }
 
 #[must_use]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Net {
     pub id: Id,
     pub pins: Vec<PinRef>,
 }
 
+// Two nets that should be routed together, |spacing| apart.
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffPair {
+    pub net_a: Id,
+    pub net_b: Id,
+    pub spacing: f64,
+}
+
+impl DiffPair {
+    // The other net in the pair, if |net_id| is in it.
+    #[must_use]
+    pub fn partner(&self, net_id: Id) -> Option<Id> {
+        if net_id == self.net_a {
+            Some(self.net_b)
+        } else if net_id == self.net_b {
+            Some(self.net_a)
+        } else {
+            None
+        }
+    }
+}
+
+// Nets whose routed lengths should all be within |tolerance| of |target|.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LengthRule {
+    pub group: Vec<Id>,
+    pub target: f64,
+    pub tolerance: f64,
+}
+
 // Describes a route.
 #[must_use]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Wire {
     pub shape: LayerShape,
     pub net_id: Id,
@@ -325,7 +437,7 @@ pub struct Wire {
 
 // Describes a via.
 #[must_use]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Via {
     pub p: Pt,
     pub padstack: Padstack,
@@ -339,6 +451,30 @@ impl Via {
     }
 }
 
+// Refers to a physical object on a PCB.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectRef {
+    Pin(PinRef),
+    Wire(usize),    // Index into Pcb::wires.
+    Via(usize),     // Index into Pcb::vias.
+    Keepout(usize), // Index into Pcb::keepouts.
+    ComponentKeepout { component: Id, idx: usize },
+}
+
+// A pair of objects on different nets that are too close together.
+#[must_use]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrcViolation {
+    pub a: ObjectRef,
+    pub b: ObjectRef,
+    pub layer: LayerId,
+    pub measured: f64,
+    pub required: f64,
+    // Roughly where the two objects are too close, for drawing a marker.
+    pub p: Pt,
+}
+
 // Object kinds
 #[must_use]
 #[derive(Debug, EnumSetType, EnumIter)]
@@ -350,17 +486,10 @@ pub enum ObjectKind {
     Wire, // Wires
 }
 
-impl ObjectKind {
-    #[must_use]
-    pub fn query(&self) -> Kinds {
-        Kinds(DenseBitSet::from_integer(enum_set!(self).as_u64()))
-    }
-}
-
 // TODO: If multiple clearances with overlapping object kinds, which one select?
 // Or apply all of them?
 #[must_use]
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Clearance {
     amount: f64,
     area_kinds: EnumSet<ObjectKind>,
@@ -382,13 +511,13 @@ impl Clearance {
 
     // Returns set of ObjectKind that |kind| has a clearance rule with.
     #[must_use]
-    pub fn subset_for(&self, kind: ObjectKind) -> Kinds {
+    pub fn subset_for(&self, kind: ObjectKind) -> EnumSet<ObjectKind> {
         match kind {
-            ObjectKind::Area => Kinds(DenseBitSet::from_integer(self.area_kinds.as_u64())),
-            ObjectKind::Pin => Kinds(DenseBitSet::from_integer(self.pin_kinds.as_u64())),
-            ObjectKind::Smd => Kinds(DenseBitSet::from_integer(self.smd_kinds.as_u64())),
-            ObjectKind::Via => Kinds(DenseBitSet::from_integer(self.via_kinds.as_u64())),
-            ObjectKind::Wire => Kinds(DenseBitSet::from_integer(self.wire_kinds.as_u64())),
+            ObjectKind::Area => self.area_kinds,
+            ObjectKind::Pin => self.pin_kinds,
+            ObjectKind::Smd => self.smd_kinds,
+            ObjectKind::Via => self.via_kinds,
+            ObjectKind::Wire => self.wire_kinds,
         }
     }
 
@@ -402,6 +531,12 @@ impl Clearance {
         }
     }
 
+    // Whether this clearance applies between objects of kinds |a| and |b|.
+    #[must_use]
+    pub fn applies(&self, a: ObjectKind, b: ObjectKind) -> bool {
+        self.subset_for(a).contains(b)
+    }
+
     #[must_use]
     pub fn amount(&self) -> f64 {
         self.amount
@@ -419,7 +554,7 @@ pub enum Rule {
 
 // Collection of rules that e.g. may apply to a given net.
 #[must_use]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RuleSet {
     pub id: Id,
     radius: Option<f64>,
@@ -457,6 +592,11 @@ impl RuleSet {
         self.radius.unwrap()
     }
 
+    #[must_use]
+    pub fn radius_opt(&self) -> Option<f64> {
+        self.radius
+    }
+
     pub fn clearances(&self) -> &[Clearance] {
         &self.clearances
     }
@@ -469,12 +609,17 @@ impl RuleSet {
 
 // Describes an overall PCB.
 #[must_use]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Pcb {
     id: Id,
     name_map: RwLock<NameMap>,
 
     // Physical structure:
+    // Reference point of the board, e.g. the corner of its outline. Geometry
+    // is kept in the absolute coordinates of the source file so exported
+    // coordinates match it; the origin is used to anchor things like the
+    // routing grid.
+    origin: Pt,
     layers: Vec<Layer>,
     boundaries: Vec<LayerShape>,
     keepouts: Vec<Keepout>,
@@ -485,22 +630,49 @@ pub struct Pcb {
     wires: Vec<Wire>,
     vias: Vec<Via>,
     nets: HashMap<Id, Net>,
+    #[serde(with = "pin_ref_map")]
     pin_ref_to_net: HashMap<PinRef, Id>, // Map PinRef to net ID.
 
     // Rules:
     rulesets: HashMap<Id, RuleSet>,
     net_to_ruleset: HashMap<Id, Id>,
     default_net_ruleset: Id,
+    diff_pairs: Vec<DiffPair>,
+    length_rules: Vec<LengthRule>,
 
     // Debug:
     debug_rts: Vec<Rt>,
 }
 
+// JSON object keys have to be strings, so store the map as a list of pairs.
+mod pin_ref_map {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::model::pcb::PinRef;
+    use crate::name::Id;
+
+    pub(super) fn serialize<S: Serializer>(
+        m: &HashMap<PinRef, Id>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        m.iter().collect::<Vec<_>>().serialize(s)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<HashMap<PinRef, Id>, D::Error> {
+        Ok(Vec::<(PinRef, Id)>::deserialize(d)?.into_iter().collect())
+    }
+}
+
 impl Clone for Pcb {
     fn clone(&self) -> Self {
         Self {
             id: self.id,
             name_map: RwLock::new(self.name_map.read().unwrap().clone()),
+            origin: self.origin,
             layers: self.layers.clone(),
             boundaries: self.boundaries.clone(),
             keepouts: self.keepouts.clone(),
@@ -513,16 +685,35 @@ impl Clone for Pcb {
             rulesets: self.rulesets.clone(),
             net_to_ruleset: self.net_to_ruleset.clone(),
             default_net_ruleset: self.default_net_ruleset,
+            diff_pairs: self.diff_pairs.clone(),
+            length_rules: self.length_rules.clone(),
             debug_rts: self.debug_rts.clone(),
         }
     }
 }
 
 impl Pcb {
+    // Saves the board as JSON, e.g. as a golden file. Floats are written with
+    // the shortest representation that parses back to the same value, so a
+    // round trip is exact.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+
     pub fn to_name(&self, id: Id) -> String {
         self.name_map.read().unwrap().name(id).to_string()
     }
 
+    // Whether |id| was handed out by |to_id|, so it has a name.
+    #[must_use]
+    pub fn has_name(&self, id: Id) -> bool {
+        self.name_map.read().unwrap().contains_id(id)
+    }
+
     pub fn to_id(&self, name: &str) -> Id {
         self.name_map.write().unwrap().name_to_id(name)
     }
@@ -535,6 +726,11 @@ impl Pcb {
         }
     }
 
+    // Layers which can be routed on, i.e. not planes.
+    pub fn signal_layers(&self) -> LayerSet {
+        self.layers().iter().filter(|l| l.kind.is_routable()).map(|v| v.layer_id).collect()
+    }
+
     pub fn layer_by_id(&self, lid: LayerId) -> &Layer {
         self.layers().iter().find(|l| l.layer_id == lid).unwrap()
     }
@@ -557,6 +753,273 @@ impl Pcb {
         // Assumes boundaries are valid.
         rt_cloud_bounds(self.boundaries().iter().map(|v| v.shape.bounds()))
     }
+
+    // Groups overlapping boundaries into physically separate regions, e.g. the
+    // boards of a panel, and returns the bounds of each region.
+    pub fn boundary_regions(&self) -> Vec<Rt> {
+        let mut regions: Vec<Rt> = Vec::new();
+        for boundary in self.boundaries() {
+            let mut r = boundary.shape.bounds();
+            // Merging can make |r| overlap regions it didn't before, so recheck.
+            while let Some(idx) = regions.iter().position(|v| rt_intersects_rt(v, &r)) {
+                r = r.united(&regions.swap_remove(idx));
+            }
+            regions.push(r);
+        }
+        regions
+    }
+
+    // Finds the closest object to |p| on any layer, ignoring objects on
+    // |exclude_net|, and returns it along with its distance from |p|.
+    pub fn nearest_obstacle(&self, p: Pt, exclude_net: Option<Id>) -> Option<(ObjectRef, f64)> {
+        let target = circ(p, 0.0).shape();
+        let excluded = |net_id: Option<Id>| net_id.is_some() && net_id == exclude_net;
+        let dist = |tf: &Tf, ls: &LayerShape| shape_dist(&shape_tf(tf, &ls.shape), &target);
+        let mut best: Option<(ObjectRef, f64)> = None;
+        let mut consider = |obj: ObjectRef, d: f64| {
+            if best.as_ref().map_or(true, |v| d < v.1) {
+                best = Some((obj, d));
+            }
+        };
+
+        for c in self.components() {
+            let tf = c.tf();
+            for pin in c.pins() {
+                let r = PinRef::new(c, pin);
+                if excluded(self.pin_ref_net(&r)) {
+                    continue;
+                }
+                let tf = tf * pin.tf();
+                let d = pin.padstack.shapes.iter().map(|s| dist(&tf, s)).fold(f64::MAX, f64::min);
+                consider(ObjectRef::Pin(r), d);
+            }
+            for (idx, keepout) in c.keepouts.iter().enumerate() {
+                consider(
+                    ObjectRef::ComponentKeepout { component: c.id, idx },
+                    dist(&tf, &keepout.shape),
+                );
+            }
+        }
+        for (idx, wire) in self.wires().iter().enumerate() {
+            if !excluded(Some(wire.net_id)) {
+                consider(ObjectRef::Wire(idx), dist(&Tf::identity(), &wire.shape));
+            }
+        }
+        for (idx, via) in self.vias().iter().enumerate() {
+            if !excluded(Some(via.net_id)) {
+                let tf = via.tf();
+                let d = via.padstack.shapes.iter().map(|s| dist(&tf, s)).fold(f64::MAX, f64::min);
+                consider(ObjectRef::Via(idx), d);
+            }
+        }
+        for (idx, keepout) in self.keepouts().iter().enumerate() {
+            consider(ObjectRef::Keepout(idx), dist(&Tf::identity(), &keepout.shape));
+        }
+        best
+    }
+
+    // Checks clearances between pins, wires, and vias on different nets,
+    // using the larger of the two nets' clearance rules. Objects touching
+    // count as a violation even if no clearance rule applies. Clearances
+    // between pins are left to the footprint design and not checked.
+    #[must_use]
+    pub fn drc(&self) -> Vec<DrcViolation> {
+        struct Object {
+            obj: ObjectRef,
+            kind: ObjectKind,
+            net_id: Option<Id>,
+            shapes: Vec<LayerShape>,
+        }
+        let world = |tf: &Tf, shapes: &[LayerShape]| -> Vec<LayerShape> {
+            shapes
+                .iter()
+                .map(|s| LayerShape { layers: s.layers, shape: shape_tf(tf, &s.shape) })
+                .collect()
+        };
+
+        let mut objs = Vec::new();
+        for c in self.components() {
+            for pin in c.pins() {
+                let r = PinRef::new(c, pin);
+                let shapes = world(&c.pin_tf(pin), &pin.padstack.shapes);
+                objs.push(Object {
+                    net_id: self.pin_ref_net(&r),
+                    obj: ObjectRef::Pin(r),
+                    kind: ObjectKind::Pin,
+                    shapes,
+                });
+            }
+        }
+        for (idx, wire) in self.wires().iter().enumerate() {
+            objs.push(Object {
+                obj: ObjectRef::Wire(idx),
+                kind: ObjectKind::Wire,
+                net_id: Some(wire.net_id),
+                shapes: vec![wire.shape.clone()],
+            });
+        }
+        for (idx, via) in self.vias().iter().enumerate() {
+            objs.push(Object {
+                obj: ObjectRef::Via(idx),
+                kind: ObjectKind::Via,
+                net_id: Some(via.net_id),
+                shapes: world(&via.tf(), &via.padstack.shapes),
+            });
+        }
+
+        let required = |a: &Object, b: &Object| {
+            [a.net_id, b.net_id]
+                .iter()
+                .flatten()
+                .flat_map(|&net_id| self.net_ruleset(net_id).clearances())
+                .filter(|c| c.applies(a.kind, b.kind))
+                .map(Clearance::amount)
+                .fold(0.0, f64::max)
+        };
+
+        // No pair needs more clearance than the largest rule of any net, so
+        // only objects within that of each other are compared.
+        let reach = objs
+            .iter()
+            .filter_map(|o| o.net_id)
+            .flat_map(|net_id| self.net_ruleset(net_id).clearances())
+            .map(Clearance::amount)
+            .fold(0.0, f64::max);
+        let mut index = Quadtree::new(self.bounds());
+        let mut owner = Vec::new();
+        for (i, o) in objs.iter().enumerate() {
+            for s in &o.shapes {
+                index.insert(s.shape.clone());
+                owner.push(i);
+            }
+        }
+
+        let mut violations = Vec::new();
+        for (i, a) in objs.iter().enumerate() {
+            let mut near: Vec<_> = a
+                .shapes
+                .iter()
+                .flat_map(|s| index.within(&s.shape, reach))
+                .map(|idx| owner[idx])
+                .filter(|&j| j > i)
+                .collect();
+            near.sort_unstable();
+            near.dedup();
+            for b in near.into_iter().map(|j| &objs[j]) {
+                let same_net = a.net_id.is_some() && a.net_id == b.net_id;
+                if same_net || (a.kind == ObjectKind::Pin && b.kind == ObjectKind::Pin) {
+                    continue;
+                }
+                let required = required(a, b);
+                let found = a.shapes.iter().find_map(|sa| {
+                    let reach = sa.shape.bounds().inset(-required, -required);
+                    b.shapes.iter().find_map(|sb| {
+                        let layer = (sa.layers & sb.layers).iter().next()?;
+                        if !rt_intersects_rt(&reach, &sb.shape.bounds()) {
+                            return None;
+                        }
+                        let measured = shape_dist(&sa.shape, &sb.shape);
+                        if !f64_lt(measured, required) && !f64_le(measured, 0.0) {
+                            return None;
+                        }
+                        // The middle of where the grown bounds overlap lies
+                        // between the two shapes.
+                        let grown = sb.shape.bounds().inset(-required, -required);
+                        let overlap = rt(
+                            reach.l().max(grown.l()),
+                            reach.b().max(grown.b()),
+                            reach.r().min(grown.r()),
+                            reach.t().min(grown.t()),
+                        );
+                        Some((layer, measured, overlap.center()))
+                    })
+                });
+                if let Some((layer, measured, p)) = found {
+                    violations.push(DrcViolation {
+                        a: a.obj.clone(),
+                        b: b.obj.clone(),
+                        layer,
+                        measured,
+                        required,
+                        p,
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    #[must_use]
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    // Ratio of copper area to board area on the given layer. Overlapping
+    // copper is counted multiple times, so this is an upper bound.
+    #[must_use]
+    pub fn net_density(&self, layer: LayerId) -> f64 {
+        let board: f64 = self
+            .boundaries()
+            .iter()
+            .filter(|v| v.layers.contains(layer))
+            .map(|v| shape_area(&v.shape))
+            .sum();
+        if board <= 0.0 {
+            return 0.0;
+        }
+
+        let mut copper = 0.0;
+        let padstack_area = |ps: &Padstack| -> f64 {
+            ps.shapes
+                .iter()
+                .filter(|v| v.layers.contains(layer))
+                .map(|v| shape_area(&v.shape))
+                .sum()
+        };
+        for c in self.components() {
+            for pin in c.pins() {
+                copper += padstack_area(&pin.padstack);
+            }
+        }
+        for wire in self.wires() {
+            if wire.shape.layers.contains(layer) {
+                copper += shape_area(&wire.shape.shape);
+            }
+        }
+        for via in self.vias() {
+            copper += padstack_area(&via.padstack);
+        }
+        copper / board
+    }
+}
+
+// Point at most |d| from |p| in the direction of |target|.
+fn toward(p: Pt, target: Pt, d: f64) -> Pt {
+    let len = p.dist(target);
+    if len <= d {
+        target
+    } else if d <= 0.0 {
+        p
+    } else {
+        p + (d / len) * (target - p)
+    }
+}
+
+// Area of a shape. Transforms used for placement are rigid (or mirrors), so
+// this is the same in local and world coordinates.
+fn shape_area(s: &Shape) -> f64 {
+    match s {
+        Shape::Circle(s) => PI * s.r() * s.r(),
+        Shape::Rect(s) => s.w() * s.h(),
+        Shape::Polygon(s) => poly_area(s.pts()),
+        Shape::Path(s) => {
+            // Sum of capsules, ignoring overlap at the joints.
+            let r = s.r();
+            let len: f64 = s.pts().windows(2).map(|v| v[0].dist(v[1])).sum();
+            len * 2.0 * r + PI * r * r
+        }
+        _ => 0.0,
+    }
 }
 
 // Getting and setting
@@ -581,11 +1044,171 @@ impl Pcb {
         self.net_to_ruleset.insert(net_id, ruleset_id);
     }
 
+    // Sets the amount of every clearance rule to |amount|.
+    pub fn override_clearance(&mut self, amount: f64) {
+        for rs in self.rulesets.values_mut() {
+            for c in &mut rs.clearances {
+                c.amount = amount;
+            }
+        }
+    }
+
     pub fn net_ruleset(&self, net_id: Id) -> &RuleSet {
         let ruleset_id = self.net_to_ruleset.get(&net_id).unwrap_or(&self.default_net_ruleset);
         self.rulesets.get(ruleset_id).unwrap()
     }
 
+    // Checks that nets only refer to pins which exist, pins and copper only
+    // refer to nets which exist, components come from a named image, pins
+    // have pads, vias use one of the board's via padstacks, and every shape is
+    // on a layer of the board. Returns all problems found.
+    pub fn validate(&self) -> Result<(), Vec<PcbError>> {
+        let mut errors = Vec::new();
+        let mut nets: Vec<_> = self.nets().collect();
+        nets.sort_unstable_by_key(|n| n.id);
+        for net in nets {
+            for p in &net.pins {
+                match self.component(p.component) {
+                    None => errors.push(PcbError::UnknownComponent {
+                        net_id: net.id,
+                        component: p.component,
+                    }),
+                    Some(c) if c.pin(p.pin).is_none() => errors.push(PcbError::UnknownPin {
+                        net_id: net.id,
+                        component: p.component,
+                        pin: p.pin,
+                    }),
+                    Some(_) => {}
+                }
+            }
+        }
+        let mut pin_nets: Vec<_> = self.pin_ref_to_net.iter().collect();
+        pin_nets.sort_unstable_by_key(|(p, _)| (p.component, p.pin));
+        for (p, &net_id) in pin_nets {
+            if self.net(net_id).is_none() {
+                let what = format!("pin {} of component {}", p.pin, p.component);
+                errors.push(PcbError::UnknownNet { net_id, what });
+            }
+        }
+
+        let valid: LayerSet = self.layers().iter().map(|l| l.layer_id).collect();
+        let check = |errors: &mut Vec<PcbError>, ls: &LayerShape, what: &dyn Fn() -> String| {
+            for layer in ls.layers.iter() {
+                if !valid.contains(layer) {
+                    errors.push(PcbError::UnknownLayer { layer, what: what() });
+                }
+            }
+        };
+        for (i, v) in self.boundaries().iter().enumerate() {
+            check(&mut errors, v, &|| format!("boundary {i}"));
+        }
+        for (i, v) in self.keepouts().iter().enumerate() {
+            check(&mut errors, &v.shape, &|| format!("keepout {i}"));
+        }
+        let mut components: Vec<_> = self.components().collect();
+        components.sort_unstable_by_key(|c| c.id);
+        for c in components {
+            if !self.has_name(c.footprint_id) {
+                errors.push(PcbError::UnknownImage { component: c.id, image: c.footprint_id });
+            }
+            for (i, v) in c.outlines.iter().chain(c.keepouts.iter().map(|k| &k.shape)).enumerate() {
+                check(&mut errors, v, &|| format!("outline or keepout {i} of component {}", c.id));
+            }
+            let mut pins: Vec<_> = c.pins().collect();
+            pins.sort_unstable_by_key(|p| p.id);
+            for pin in pins {
+                if pin.padstack.shapes.is_empty() {
+                    errors.push(PcbError::EmptyPadstack { component: c.id, pin: pin.id });
+                }
+                for v in &pin.padstack.shapes {
+                    check(&mut errors, v, &|| format!("pin {} of component {}", pin.id, c.id));
+                }
+            }
+        }
+        for (i, v) in self.wires().iter().enumerate() {
+            if self.net(v.net_id).is_none() {
+                errors.push(PcbError::UnknownNet { net_id: v.net_id, what: format!("wire {i}") });
+            }
+            check(&mut errors, &v.shape, &|| format!("wire {i}"));
+        }
+        for (i, v) in self.vias().iter().enumerate() {
+            if self.net(v.net_id).is_none() {
+                errors.push(PcbError::UnknownNet { net_id: v.net_id, what: format!("via {i}") });
+            }
+            if !self.via_padstacks().iter().any(|p| p.id == v.padstack.id) {
+                errors.push(PcbError::UnknownViaPadstack { via: i, padstack: v.padstack.id });
+            }
+            for s in &v.padstack.shapes {
+                check(&mut errors, s, &|| format!("via {i}"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Bounds of the pads of all pins on the given net.
+    #[must_use]
+    pub fn net_bounds(&self, net_id: Id) -> Rt {
+        let mut bounds = Rt::empty();
+        let Some(net) = self.net(net_id) else { return bounds };
+        for p in &net.pins {
+            if let Ok((component, pin)) = self.pin_ref(p) {
+                let tf = component.pin_tf(pin);
+                for s in &pin.padstack.shapes {
+                    bounds = bounds.united(&shape_tf(&tf, &s.shape).bounds());
+                }
+            }
+        }
+        bounds
+    }
+
+    // Every pad, keepout, via, and wire on |layer|, grown outward by
+    // |clearance|, so the router can treat them as hard blockers.
+    #[must_use]
+    pub fn inflated_obstacles(&self, clearance: f64, layer: LayerId) -> Vec<Shape> {
+        let mut shapes = Vec::new();
+        let mut add = |tf: &Tf, ls: &LayerShape| {
+            if ls.layers.contains(layer) {
+                shapes.push(offset(&shape_tf(tf, &ls.shape), clearance));
+            }
+        };
+        for c in self.components() {
+            for pin in c.pins() {
+                let tf = c.pin_tf(pin);
+                pin.padstack.shapes.iter().for_each(|ls| add(&tf, ls));
+            }
+            c.keepouts.iter().for_each(|k| add(&c.tf(), &k.shape));
+        }
+        self.keepouts().iter().for_each(|k| add(&Tf::identity(), &k.shape));
+        for via in self.vias() {
+            via.padstack.shapes.iter().for_each(|ls| add(&via.tf(), ls));
+        }
+        self.wires().iter().for_each(|w| add(&Tf::identity(), &w.shape));
+        shapes
+    }
+
+    // Half-width of wires on the given net. Uses the width from the net's
+    // class, falling back to the default class if it doesn't specify one.
+    #[must_use]
+    pub fn net_radius(&self, net_id: Id) -> f64 {
+        self.net_ruleset(net_id)
+            .radius_opt()
+            .or_else(|| self.rulesets.get(&self.default_net_ruleset).and_then(RuleSet::radius_opt))
+            .unwrap()
+    }
+
+    pub fn set_origin(&mut self, p: Pt) {
+        self.origin = p;
+    }
+
+    pub fn origin(&self) -> Pt {
+        self.origin
+    }
+
     pub fn add_layer(&mut self, l: Layer) {
         self.layers.push(l);
     }
@@ -646,6 +1269,12 @@ impl Pcb {
         &self.vias
     }
 
+    // Removes the wires and vias of nets for which |keep| is false.
+    pub fn retain_copper(&mut self, keep: impl Fn(Id) -> bool) {
+        self.wires.retain(|w| keep(w.net_id));
+        self.vias.retain(|v| keep(v.net_id));
+    }
+
     pub fn add_net(&mut self, n: Net) {
         for p in &n.pins {
             self.pin_ref_to_net.insert(p.clone(), n.id);
@@ -661,6 +1290,43 @@ impl Pcb {
         self.nets.get(&id)
     }
 
+    pub fn add_diff_pair(&mut self, p: DiffPair) -> Result<()> {
+        for net_id in [p.net_a, p.net_b] {
+            if self.net(net_id).is_none() {
+                return Err(eyre!("unknown net id {net_id} in diff pair"));
+            }
+            if self.diff_pair(net_id).is_some() {
+                return Err(eyre!("net id {net_id} is already in a diff pair"));
+            }
+        }
+        if p.net_a == p.net_b {
+            return Err(eyre!("diff pair needs two different nets"));
+        }
+        self.diff_pairs.push(p);
+        Ok(())
+    }
+
+    pub fn diff_pairs(&self) -> &[DiffPair] {
+        &self.diff_pairs
+    }
+
+    // The diff pair containing |net_id|, if any.
+    pub fn diff_pair(&self, net_id: Id) -> Option<&DiffPair> {
+        self.diff_pairs.iter().find(|p| p.partner(net_id).is_some())
+    }
+
+    pub fn add_length_rule(&mut self, r: LengthRule) -> Result<()> {
+        if let Some(id) = r.group.iter().find(|&&id| self.net(id).is_none()) {
+            return Err(eyre!("unknown net id {id} in length rule"));
+        }
+        self.length_rules.push(r);
+        Ok(())
+    }
+
+    pub fn length_rules(&self) -> &[LengthRule] {
+        &self.length_rules
+    }
+
     pub fn add_debug_rt(&mut self, r: Rt) {
         self.debug_rts.push(r);
     }
@@ -669,3 +1335,300 @@ impl Pcb {
         &self.debug_rts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{circ, path, pt, rt, ShapeOps};
+    use serde_json::Value;
+
+    use crate::error::PcbError;
+    use crate::geom::contains::pt_in_shape;
+    use crate::geom::math::{f64_eq, pt_eq};
+    use crate::model::pcb::{
+        Component, DiffPair, Keepout, KeepoutType, Layer, LayerKind, LayerSet, LayerShape,
+        LengthRule, ObjectRef, Padstack, Pcb, Pin, PinRef, Via,
+    };
+    use crate::name::NO_ID;
+    use crate::testing::{
+        add_component, add_net, add_smd_component, board, wire, CLEARANCE, PAD_R, RADIUS,
+    };
+
+    #[test]
+    fn net_density_counts_copper_per_layer() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(5.0, 2.0), &[pt(0.0, 0.0)]);
+        let net_id = pcb.to_id("N1");
+        pcb.add_wire(wire(net_id, 0, &[pt(1.0, 5.0), pt(9.0, 5.0)]));
+
+        assert_eq!(pcb.layer_count(), 2);
+        let pad = PI * PAD_R * PAD_R;
+        let trace = 8.0 * 2.0 * RADIUS + PI * RADIUS * RADIUS;
+        assert!(f64_eq(pcb.net_density(0), (pad + trace) / 100.0));
+        assert!(f64_eq(pcb.net_density(1), pad / 100.0));
+    }
+
+    #[test]
+    fn net_density_without_boundary_is_zero() {
+        let mut pcb = board(10.0, 10.0);
+        pcb.add_wire(wire(pcb.to_id("N1"), 0, &[pt(1.0, 5.0), pt(9.0, 5.0)]));
+        assert!(f64_eq(pcb.net_density(2), 0.0));
+    }
+
+    #[test]
+    fn drc_marks_between_close_wires() {
+        let mut pcb = board(10.0, 10.0);
+        let (n1, n2) = (pcb.to_id("N1"), pcb.to_id("N2"));
+        pcb.add_wire(wire(n1, 0, &[pt(1.0, 5.0), pt(9.0, 5.0)]));
+        pcb.add_wire(wire(n2, 0, &[pt(5.0, 5.25), pt(5.0, 9.0)]));
+        // Different layers don't interact.
+        pcb.add_wire(wire(n2, 1, &[pt(1.0, 5.1), pt(9.0, 5.1)]));
+
+        let drc = pcb.drc();
+        assert_eq!(drc.len(), 1);
+        let v = &drc[0];
+        assert_eq!((&v.a, &v.b), (&ObjectRef::Wire(0), &ObjectRef::Wire(1)));
+        assert_eq!(v.layer, 0);
+        assert!(f64_eq(v.measured, 0.05));
+        assert!(f64_eq(v.required, CLEARANCE));
+        // The marker sits in the gap, not at the middle of the long wire.
+        assert!(v.p.dist(pt(5.0, 5.125)) < 0.2, "{:?}", v.p);
+    }
+
+    #[test]
+    fn drc_reports_short_to_other_nets_pad() {
+        let mut pcb = board(10.0, 10.0);
+        let u1 = add_component(&mut pcb, "U1", pt(5.0, 5.0), &[pt(0.0, 0.0)]);
+        let n1 = add_net(&mut pcb, "N1", &[("U1", "1")]);
+        let n2 = pcb.to_id("N2");
+        // Same net copper over the pad is fine; another net's is a short.
+        pcb.add_wire(wire(n1, 0, &[pt(5.0, 5.0), pt(5.0, 9.0)]));
+        pcb.add_wire(wire(n2, 0, &[pt(2.0, 5.0), pt(8.0, 5.0)]));
+
+        let drc = pcb.drc();
+        assert_eq!(drc.len(), 2, "{drc:?}");
+        let pin = ObjectRef::Pin(PinRef { component: u1, pin: pcb.to_id("1") });
+        assert!(drc.iter().all(|v| v.b == ObjectRef::Wire(1) && f64_eq(v.measured, 0.0)));
+        assert!(drc.iter().any(|v| v.a == pin));
+        assert!(drc.iter().any(|v| v.a == ObjectRef::Wire(0)));
+    }
+
+    #[test]
+    fn drc_checks_vias() {
+        let mut pcb = board(10.0, 10.0);
+        let padstack = pcb.via_padstacks()[0].clone();
+        let (n1, n2) = (pcb.to_id("N1"), pcb.to_id("N2"));
+        let mut via = |x: f64, net_id| {
+            pcb.add_via(Via { p: pt(x, 5.0), padstack: padstack.clone(), net_id });
+        };
+        // 2 * PAD_R + CLEARANCE apart just clears; closer doesn't.
+        via(1.0, n1);
+        via(1.0 + 2.0 * PAD_R + CLEARANCE + 0.01, n2);
+        via(5.0, n1);
+        via(5.0 + 2.0 * PAD_R + CLEARANCE / 2.0, n2);
+        // Vias of the same net may overlap.
+        via(8.0, n1);
+        via(8.1, n1);
+
+        let drc = pcb.drc();
+        assert_eq!(drc.len(), 1, "{drc:?}");
+        let v = &drc[0];
+        assert_eq!((&v.a, &v.b), (&ObjectRef::Via(2), &ObjectRef::Via(3)));
+        assert!(f64_eq(v.measured, CLEARANCE / 2.0));
+    }
+
+    #[test]
+    fn boundary_regions_merge_overlapping_outlines() {
+        // Two 10 mm boards of a panel, the first with a tab sticking out of
+        // its right edge.
+        let mut pcb = board(10.0, 10.0);
+        let layers = pcb.layers_by_kind(LayerKind::All);
+        for r in [rt(20.0, 0.0, 30.0, 10.0), rt(8.0, 4.0, 14.0, 6.0)] {
+            pcb.add_boundary(LayerShape { layers, shape: r.shape() });
+        }
+        let mut regions = pcb.boundary_regions();
+        regions.sort_by(|a, b| a.l().total_cmp(&b.l()));
+        assert_eq!(regions.len(), 2, "{regions:?}");
+        assert!(pt_eq(regions[0].bl(), pt(0.0, 0.0)) && pt_eq(regions[0].tr(), pt(14.0, 10.0)));
+        assert!(pt_eq(regions[1].bl(), pt(20.0, 0.0)) && pt_eq(regions[1].tr(), pt(30.0, 10.0)));
+    }
+
+    #[test]
+    fn nearest_obstacle_skips_excluded_net() {
+        let mut pcb = board(10.0, 10.0);
+        let (n1, n2) = (pcb.to_id("N1"), pcb.to_id("N2"));
+        pcb.add_wire(wire(n1, 0, &[pt(1.0, 5.0), pt(9.0, 5.0)]));
+        let padstack = pcb.via_padstacks()[0].clone();
+        pcb.add_via(Via { p: pt(5.0, 7.0), padstack, net_id: n2 });
+        // 1.2 mm from the wire's centreline and 0.8 mm from the via's centre.
+        let p = pt(5.0, 6.2);
+        let check = |exclude_net, want: ObjectRef, dist: f64| {
+            let (obj, d) = pcb.nearest_obstacle(p, exclude_net).unwrap();
+            assert_eq!(obj, want);
+            assert!(f64_eq(d, dist), "{d}");
+        };
+        check(None, ObjectRef::Via(0), 0.8 - PAD_R);
+        check(Some(n1), ObjectRef::Via(0), 0.8 - PAD_R);
+        check(Some(n2), ObjectRef::Wire(0), 1.2 - RADIUS);
+    }
+
+    #[test]
+    fn pin_connection_pt_stays_inset_on_pad() {
+        // Component at (5, 5) with a single pin of pad shape |shape|.
+        let pad = |shape: Shape| {
+            let mut c = Component { p: pt(5.0, 5.0), ..Component::default() };
+            let shapes = vec![LayerShape { layers: LayerSet::one(0), shape }];
+            let padstack = Padstack { id: 0, shapes, attach: false };
+            c.add_pin(Pin { id: 1, padstack, rotation: 0.0, p: pt(0.0, 0.0) });
+            c
+        };
+        let check = |c: &Component, target, inset, want| {
+            let got = c.pin_connection_pt(c.pin(1).unwrap(), target, inset);
+            assert!(pt_eq(got, want), "{got:?} vs {want:?}");
+        };
+
+        let rect = pad(rt(-1.0, -0.5, 1.0, 0.5).shape());
+        check(&rect, pt(10.0, 5.0), 0.2, pt(5.8, 5.0));
+        check(&rect, pt(10.0, 10.0), 0.2, pt(5.8, 5.3));
+        // An inset past the middle of the pad clamps to the middle.
+        check(&rect, pt(5.0, 10.0), 0.8, pt(5.0, 5.0));
+
+        let round = pad(circ(pt(0.0, 0.0), 0.5).shape());
+        check(&round, pt(10.0, 5.0), 0.1, pt(5.4, 5.0));
+        check(&round, pt(5.1, 5.0), 0.1, pt(5.1, 5.0));
+        check(&round, pt(10.0, 5.0), 0.6, pt(5.0, 5.0));
+
+        let oblong = pad(path(&[pt(-1.0, 0.0), pt(1.0, 0.0)], 0.25).shape());
+        check(&oblong, pt(5.5, 8.0), 0.05, pt(5.5, 5.2));
+        check(&oblong, pt(10.0, 5.0), 0.05, pt(6.2, 5.0));
+    }
+
+    #[test]
+    fn signal_layers_skip_power_planes() {
+        let mut pcb = board(10.0, 10.0);
+        for (layer_id, name, kind) in
+            [(2, "In1.Cu", LayerKind::Power), (3, "In2.Cu", LayerKind::Mixed)]
+        {
+            let name_id = pcb.to_id(name);
+            pcb.add_layer(Layer { name_id, layer_id, kind });
+        }
+        let want = LayerSet::one(0) | LayerSet::one(1) | LayerSet::one(3);
+        assert_eq!(pcb.signal_layers(), want);
+        assert_eq!(pcb.layers_by_kind(LayerKind::Power), LayerSet::one(2));
+        assert!(!LayerKind::Power.is_routable());
+        assert!(!LayerKind::All.is_routable());
+        assert!(LayerKind::Jumper.is_routable());
+    }
+
+    #[test]
+    fn inflated_obstacles_grow_by_clearance() {
+        let mut pcb = board(10.0, 10.0);
+        let f = LayerSet::one(0);
+        add_smd_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)], f);
+        let shape = LayerShape { layers: f, shape: rt(6.0, 2.0, 8.0, 3.0).shape() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        let net_id = pcb.to_id("N1");
+        pcb.add_wire(wire(net_id, 1, &[pt(1.0, 8.0), pt(9.0, 8.0)]));
+
+        let front = pcb.inflated_obstacles(CLEARANCE, 0);
+        assert_eq!(front.len(), 2);
+        let Shape::Circle(pad) = &front[0] else { panic!("expected a circle") };
+        assert!(f64_eq(pad.r(), PAD_R + CLEARANCE));
+        let in_any = |shapes: &[Shape], p| shapes.iter().any(|s| pt_in_shape(p, s));
+        // Half the clearance off the pad and the keepout is inside; any
+        // further than the clearance is clear.
+        let near = PAD_R + CLEARANCE / 2.0;
+        let far = PAD_R + CLEARANCE * 1.01;
+        assert!(in_any(&front, pt(2.0 + near, 5.0)));
+        assert!(!in_any(&front, pt(2.0 + far, 5.0)));
+        assert!(in_any(&front, pt(7.0, 3.0 + CLEARANCE / 2.0)));
+        assert!(!in_any(&front, pt(7.0, 3.0 + CLEARANCE * 1.01)));
+
+        // Only the wire is on B.Cu, and it grows its radius.
+        let back = pcb.inflated_obstacles(CLEARANCE, 1);
+        assert_eq!(back.len(), 1);
+        let Shape::Path(w) = &back[0] else { panic!("expected a path") };
+        assert!(f64_eq(w.r(), RADIUS + CLEARANCE));
+        assert!(in_any(&back, pt(5.0, 8.0 + RADIUS + CLEARANCE / 2.0)));
+        assert!(!in_any(&back, pt(5.0, 8.0 + RADIUS + CLEARANCE * 1.01)));
+    }
+
+    #[test]
+    fn json_round_trip_is_exact() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(0.1 + 0.2, 5.0), &[pt(0.0, 0.0), pt(1.0 / 3.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0), pt(0.0, 1.0)]);
+        let n1 = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let n2 = add_net(&mut pcb, "N2", &[("U1", "2"), ("U2", "2")]);
+        pcb.add_wire(wire(n1, 0, &[pt(0.3, 5.0), pt(8.0, 5.0)]));
+        let padstack = pcb.via_padstacks()[0].clone();
+        pcb.add_via(Via { p: pt(5.0, 7.0), padstack, net_id: n2 });
+        pcb.add_diff_pair(DiffPair { net_a: n1, net_b: n2, spacing: 0.25 }).unwrap();
+        pcb.add_length_rule(LengthRule { group: vec![n1, n2], target: 9.0, tolerance: 0.1 })
+            .unwrap();
+
+        let back = Pcb::from_json(&pcb.to_json().unwrap()).unwrap();
+        // Maps become JSON objects, which compare without order, but the pin
+        // to net map is written as a list in whatever order it iterates.
+        let value = |p: &Pcb| {
+            let mut v: Value = serde_json::from_str(&p.to_json().unwrap()).unwrap();
+            let Value::Array(pins) = &mut v["pin_ref_to_net"] else { panic!("expected a list") };
+            pins.sort_by_key(ToString::to_string);
+            v
+        };
+        assert_eq!(value(&back), value(&pcb));
+        for net in pcb.nets() {
+            for p in &net.pins {
+                assert_eq!(back.pin_ref_net(p), Some(net.id));
+            }
+        }
+        assert_eq!(back.to_id("N2"), n2);
+    }
+
+    #[test]
+    fn validate_accepts_consistent_board() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)]);
+        let n1 = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        pcb.add_wire(wire(n1, 0, &[pt(2.0, 5.0), pt(8.0, 5.0)]));
+        let padstack = pcb.via_padstacks()[0].clone();
+        pcb.add_via(Via { p: pt(5.0, 5.0), padstack, net_id: n1 });
+        assert_eq!(pcb.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_dangling_refs() {
+        let mut pcb = board(10.0, 10.0);
+        let u1 = add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        let n1 = add_net(&mut pcb, "N1", &[("U1", "1"), ("U1", "9"), ("U9", "1")]);
+        let (pin9, u9) = (pcb.to_id("9"), pcb.to_id("U9"));
+        // Component whose image was never named.
+        let mut c = Component::default();
+        c.id = pcb.to_id("U3");
+        c.footprint_id = NO_ID;
+        pcb.add_component(c);
+        // Copper on a net that doesn't exist, and a via padstack that isn't
+        // one of the board's.
+        let n9 = pcb.to_id("N9");
+        pcb.add_wire(wire(n9, 0, &[pt(1.0, 1.0), pt(2.0, 1.0)]));
+        let pad = pcb.to_id("pad");
+        let padstack = Padstack { id: pad, ..pcb.via_padstacks()[0].clone() };
+        pcb.add_via(Via { p: pt(5.0, 5.0), padstack, net_id: n1 });
+
+        let errors = pcb.validate().unwrap_err();
+        let want = [
+            PcbError::UnknownPin { net_id: n1, component: u1, pin: pin9 },
+            PcbError::UnknownComponent { net_id: n1, component: u9 },
+            PcbError::UnknownImage { component: pcb.to_id("U3"), image: NO_ID },
+            PcbError::UnknownNet { net_id: n9, what: "wire 0".to_owned() },
+            PcbError::UnknownViaPadstack { via: 0, padstack: pad },
+        ];
+        assert_eq!(errors.len(), want.len(), "{errors:?}");
+        for e in &want {
+            assert!(errors.contains(e), "{e:?} missing from {errors:?}");
+        }
+    }
+}
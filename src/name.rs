@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 // Integer IDs representing names. Readable names used in PCB.
 // Note that IDs represent strings, not objects. They don't uniquely identify an object.
 pub type Id = usize;
 pub const NO_ID: Id = Id::MAX;
 
 #[must_use]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct NameMap {
     name_to_id: HashMap<String, Id>, // Name to ID.
     id_to_name: HashMap<Id, String>, // ID to name.
@@ -19,6 +21,11 @@ impl NameMap {
         self.id_to_name.get(&id).unwrap()
     }
 
+    #[must_use]
+    pub fn contains_id(&self, id: Id) -> bool {
+        self.id_to_name.contains_key(&id)
+    }
+
     pub fn name_to_id(&mut self, name: &str) -> Id {
         if let Some(id) = self.name_to_id.get(name) {
             *id
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::SQRT_2;
+
+use eyre::{eyre, Result};
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::{path, pt, ShapeOps};
+use ordered_float::OrderedFloat;
+use priority_queue::PriorityQueue;
+
+use crate::geom::math::pt_eq;
+use crate::model::pcb::{LayerId, LayerSet, LayerShape, Pcb, Wire};
+use crate::name::Id;
+use crate::route::diff_pair::route_pair;
+use crate::route::grid::GridRouter;
+use crate::route::layers::routing_layers;
+use crate::route::place_model::PlaceModel;
+use crate::route::ratsnest::mst;
+use crate::route::router::{RouteResult, RouteStrategy};
+
+// Default cost of changing direction, in mm of wire.
+const DEFAULT_TURN_COST: f64 = 0.1;
+
+// Moves between grid points and their length in grid steps.
+const DIRS: [((i64, i64), f64); 8] = [
+    ((-1, 0), 1.0),
+    ((1, 0), 1.0),
+    ((0, -1), 1.0),
+    ((0, 1), 1.0),
+    ((1, 1), SQRT_2),
+    ((1, -1), SQRT_2),
+    ((-1, 1), SQRT_2),
+    ((-1, -1), SQRT_2),
+];
+// Direction index for the start of a search, before any move.
+const NO_DIR: usize = DIRS.len();
+
+// Estimate of the remaining cost for |AStarRouter|. Manhattan overestimates
+// diagonal moves, so it expands fewer states but may not find the shortest
+// path.
+#[must_use]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Heuristic {
+    #[default]
+    Euclidean,
+    Manhattan,
+}
+
+impl Heuristic {
+    fn dist(self, a: Pt, b: Pt) -> f64 {
+        match self {
+            Self::Euclidean => a.dist(b),
+            Self::Manhattan => (a.x - b.x).abs() + (a.y - b.y).abs(),
+        }
+    }
+}
+
+// Grid point and the direction of the move that reached it.
+type State = ((i64, i64), usize);
+
+// Routes each net with a plain A* search over a grid, one layer per
+// connection and without vias. Simpler than |GridRouter|, with no rip-up or
+// layer assignment, which makes it a useful baseline to compare against.
+// Diff pairs on the board are routed side by side, see |route_pair|.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct AStarRouter {
+    place: PlaceModel,
+    net_order: Vec<Id>,
+    routing_layers: LayerSet,
+    bounds: Rt,
+    pitch: f64,
+    heuristic: Heuristic,
+    turn_cost: f64,
+}
+
+impl AStarRouter {
+    // Uses the grid pitch |GridRouter| would pick for |pcb|.
+    pub fn new(pcb: Pcb, net_order: Vec<Id>) -> Self {
+        let pitch = GridRouter::derive_resolution(&pcb);
+        let routing_layers = routing_layers(&pcb);
+        let bounds = pcb.bounds();
+        Self {
+            place: PlaceModel::new(pcb),
+            net_order,
+            routing_layers,
+            bounds,
+            pitch,
+            heuristic: Heuristic::default(),
+            turn_cost: DEFAULT_TURN_COST,
+        }
+    }
+
+    pub fn set_pitch(&mut self, pitch: f64) {
+        self.pitch = pitch;
+    }
+
+    pub fn set_heuristic(&mut self, heuristic: Heuristic) {
+        self.heuristic = heuristic;
+    }
+
+    // Cost of each change of direction, in mm of wire. Higher values give
+    // straighter wires at the cost of length.
+    pub fn set_turn_cost(&mut self, turn_cost: f64) {
+        self.turn_cost = turn_cost;
+    }
+
+    fn grid_pt(&self, (x, y): (i64, i64)) -> Pt {
+        self.bounds.bl() + pt(x as f64 * self.pitch, y as f64 * self.pitch)
+    }
+
+    fn grid_size(&self) -> (i64, i64) {
+        let n = |len: f64| (len / self.pitch).floor() as i64;
+        (n(self.bounds.w()), n(self.bounds.h()))
+    }
+
+    // Nearest grid point to |p|.
+    fn snap(&self, p: Pt) -> (i64, i64) {
+        let (w, h) = self.grid_size();
+        let n = |v: f64, max: i64| ((v / self.pitch).round() as i64).clamp(0, max);
+        (n(p.x - self.bounds.l(), w), n(p.y - self.bounds.b(), h))
+    }
+
+    pub(crate) fn place(&self) -> &PlaceModel {
+        &self.place
+    }
+
+    pub(crate) fn place_mut(&mut self) -> &mut PlaceModel {
+        &mut self.place
+    }
+
+    pub(crate) fn routing_layers(&self) -> LayerSet {
+        self.routing_layers
+    }
+
+    fn is_blocked(&self, net_id: Id, layer: LayerId, r: f64, a: Pt, b: Pt) -> bool {
+        let shape = LayerShape { layers: LayerSet::one(layer), shape: path(&[a, b], r).shape() };
+        self.place.is_wire_blocked(&Wire { shape, net_id })
+    }
+
+    // Path from |src| to |dst| on |layer| for a wire of radius |r|, through the
+    // grid points nearest them. Only the points where the path turns are
+    // returned.
+    pub(crate) fn search(
+        &self,
+        net_id: Id,
+        layer: LayerId,
+        r: f64,
+        src: Pt,
+        dst: Pt,
+    ) -> Option<Vec<Pt>> {
+        let (from, to) = (self.snap(src), self.snap(dst));
+        let (sp, dp) = (self.grid_pt(from), self.grid_pt(to));
+        let blocked = |a: Pt, b: Pt| self.is_blocked(net_id, layer, r, a, b);
+        if blocked(src, sp) || blocked(dp, dst) {
+            return None;
+        }
+        let (w, h) = self.grid_size();
+        let mut q: PriorityQueue<State, OrderedFloat<f64>> = PriorityQueue::new();
+        let mut cost: HashMap<State, (f64, State)> = HashMap::new();
+        let start = (from, NO_DIR);
+        cost.insert(start, (0.0, start));
+        q.push(start, OrderedFloat(0.0));
+        let mut end = None;
+        while let Some((cur, _)) = q.pop() {
+            if cur.0 == to {
+                end = Some(cur);
+                break;
+            }
+            let cur_cost = cost[&cur].0;
+            let ((x, y), cur_dir) = cur;
+            let p = self.grid_pt((x, y));
+            for (dir, &((dx, dy), steps)) in DIRS.iter().enumerate() {
+                let next = (x + dx, y + dy);
+                if next.0 < 0 || next.1 < 0 || next.0 > w || next.1 > h {
+                    continue;
+                }
+                let turn = if cur_dir == NO_DIR || cur_dir == dir { 0.0 } else { self.turn_cost };
+                let c = cur_cost + steps * self.pitch + turn;
+                if cost.get(&(next, dir)).map_or(false, |v| v.0 <= c) {
+                    continue;
+                }
+                let np = self.grid_pt(next);
+                if blocked(p, np) {
+                    continue;
+                }
+                cost.insert((next, dir), (c, cur));
+                q.push((next, dir), OrderedFloat(-(c + self.heuristic.dist(np, dp))));
+            }
+        }
+
+        // Walk back, keeping the first point of each straight run.
+        let mut cur = end?;
+        let mut pts = vec![dst, dp];
+        while cur != start {
+            let prev = cost[&cur].1;
+            if prev.1 != cur.1 {
+                pts.push(self.grid_pt(prev.0));
+            }
+            cur = prev;
+        }
+        pts.push(src);
+        pts.reverse();
+        pts.dedup_by(|a, b| pt_eq(*a, *b));
+        Some(pts)
+    }
+
+    // Connects each pair of pins in the spanning tree of the net's pins on
+    // the layer both pins are on that gives the shortest wire.
+    pub(crate) fn route_net(&mut self, net_id: Id) -> Result<RouteResult> {
+        let pcb = self.place.pcb();
+        let r = pcb.net_radius(net_id);
+        let net = pcb.net(net_id).ok_or_else(|| eyre!("missing net {}", net_id))?;
+        let mut pins = Vec::new();
+        for p in &net.pins {
+            let (c, pin) = pcb.pin_ref(p)?;
+            pins.push((c.pin_pt(pin), pin.padstack.layers() & self.routing_layers));
+        }
+        let pts: Vec<_> = pins.iter().map(|v| v.0).collect();
+
+        let mut res = RouteResult::default();
+        for (a, b) in mst(&pts) {
+            let layers = pins[a].1 & pins[b].1;
+            let best = layers
+                .iter()
+                .filter_map(|layer| Some((layer, self.search(net_id, layer, r, pts[a], pts[b])?)))
+                .min_by_key(|(_, path)| {
+                    OrderedFloat(path.windows(2).map(|v| v[0].dist(v[1])).sum::<f64>())
+                });
+            let Some((layer, path)) = best else {
+                res.failed = true;
+                continue;
+            };
+            let wire = self.place.create_wire(net_id, layer, &path);
+            self.place.add_wire(&wire);
+            res.wires.push(wire);
+        }
+        Ok(res)
+    }
+}
+
+impl RouteStrategy for AStarRouter {
+    fn route(&mut self) -> Result<RouteResult> {
+        let mut res = RouteResult::default();
+        let mut done = HashSet::new();
+        for net_id in self.net_order.clone() {
+            if !done.insert(net_id) {
+                continue;
+            }
+            // Diff pairs are routed together when the first of the two comes
+            // up, as long as both are being routed.
+            let pair = self
+                .place
+                .pcb()
+                .diff_pair(net_id)
+                .copied()
+                .filter(|p| p.partner(net_id).map_or(false, |id| self.net_order.contains(&id)));
+            if let Some(pair) = pair {
+                done.insert(pair.net_a);
+                done.insert(pair.net_b);
+                res.merge(route_pair(self, &pair)?);
+                continue;
+            }
+            let route = self.route_net(net_id)?;
+            if route.failed {
+                res.failed_nets.push(net_id);
+            }
+            res.merge(route);
+        }
+        res.num_nets = self.net_order.len();
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::{pt, rt, ShapeOps};
+
+    use super::{AStarRouter, Heuristic};
+    use crate::model::pcb::{Keepout, KeepoutType, LayerKind, LayerShape, Pcb};
+    use crate::route::place_model::PlaceModel;
+    use crate::route::router::Router;
+    use crate::testing::{add_component, add_net, board};
+
+    // Pins either side of a keepout |h| high in the middle of the board.
+    fn around_keepout(h: f64) -> Pcb {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)]);
+        add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let layers = pcb.layers_by_kind(LayerKind::All);
+        let (b, t) = (5.0 - h / 2.0, 5.0 + h / 2.0);
+        let shape = LayerShape { layers, shape: rt(4.0, b, 6.0, t).shape() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        pcb
+    }
+
+    #[test]
+    fn routes_around_keepout() {
+        let pcb = around_keepout(4.0);
+        let router = Router::new(pcb.clone());
+        for heuristic in [Heuristic::Euclidean, Heuristic::Manhattan] {
+            let mut astar = AStarRouter::new(pcb.clone(), router.rand_net_order());
+            astar.set_heuristic(heuristic);
+            let res = router.route_with(&mut astar).unwrap();
+            assert!(!res.failed, "{heuristic:?}");
+            assert!(res.vias.is_empty());
+            assert!(res.verify_connectivity(&pcb).is_empty(), "{heuristic:?}");
+            let place = PlaceModel::new(pcb.clone());
+            assert!(res.wires.iter().all(|w| !place.is_wire_blocked(w)), "{heuristic:?}");
+            // Going around the keepout is longer than the straight line.
+            assert!(res.wire_length() > 6.0);
+        }
+    }
+
+    #[test]
+    fn fails_when_walled_off() {
+        let pcb = around_keepout(10.0);
+        let router = Router::new(pcb.clone());
+        let mut astar = AStarRouter::new(pcb.clone(), router.rand_net_order());
+        let res = router.route_with(&mut astar).unwrap();
+        assert!(res.failed);
+        assert_eq!(res.failed_nets, vec![pcb.to_id("N1")]);
+        assert!(res.wires.is_empty());
+    }
+}
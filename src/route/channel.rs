@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+use eyre::{eyre, Result};
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use ordered_float::OrderedFloat;
+use priority_queue::PriorityQueue;
+
+use crate::geom::math::{orientation, pt_eq};
+use crate::geom::polygon::signed_poly_area;
+use crate::geom::transform::shape_tf;
+use crate::geom::{Line, Seg};
+use crate::model::pcb::{Clearance, LayerId, LayerSet, Pcb};
+use crate::name::Id;
+use crate::route::layers::routing_layers;
+use crate::route::place_model::PlaceModel;
+use crate::route::ratsnest::mst;
+use crate::route::router::{RouteResult, RouteStrategy};
+
+// Delaunay triangulation of a set of points, as indices into |pts|.
+#[must_use]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Triangulation {
+    pub pts: Vec<Pt>,
+    pub tris: Vec<[usize; 3]>,
+}
+
+impl Triangulation {
+    // Bowyer-Watson. Points within |EP| of an earlier point are dropped.
+    pub fn new(pts: &[Pt]) -> Self {
+        let mut uniq: Vec<Pt> = Vec::new();
+        for &p in pts {
+            if !uniq.iter().any(|&q| pt_eq(p, q)) {
+                uniq.push(p);
+            }
+        }
+        let n = uniq.len();
+        if n < 3 {
+            return Self { pts: uniq, tris: Vec::new() };
+        }
+
+        // Start with a triangle around everything, removed at the end.
+        let (mut l, mut b, mut r, mut t) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        for p in &uniq {
+            (l, b, r, t) = (l.min(p.x), b.min(p.y), r.max(p.x), t.max(p.y));
+        }
+        let size = (r - l).max(t - b).max(1.0) * 20.0;
+        let (cx, cy) = ((l + r) / 2.0, (b + t) / 2.0);
+        let mut all = uniq.clone();
+        all.extend([pt(cx - size, cy - size), pt(cx + size, cy - size), pt(cx, cy + size)]);
+        let mut tris = vec![[n, n + 1, n + 2]];
+
+        for i in 0..n {
+            let p = all[i];
+            let (bad, good): (Vec<_>, Vec<_>) =
+                tris.into_iter().partition(|tri| in_circumcircle(&all, *tri, p));
+            // Edges of the hole left by the bad triangles are the ones only
+            // one of them has.
+            let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+            for tri in &bad {
+                for k in 0..3 {
+                    let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                    *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+                }
+            }
+            tris = good;
+            for tri in &bad {
+                for k in 0..3 {
+                    let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                    if edges[&(a.min(b), a.max(b))] == 1 {
+                        tris.push([a, b, i]);
+                    }
+                }
+            }
+        }
+        tris.retain(|tri| tri.iter().all(|&v| v < n));
+        Self { pts: uniq, tris }
+    }
+
+    // Each edge once, as (smaller index, larger index).
+    #[must_use]
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges: Vec<_> = self
+            .tris
+            .iter()
+            .flat_map(|tri| {
+                (0..3).map(move |k| (tri[k].min(tri[(k + 1) % 3]), tri[k].max(tri[(k + 1) % 3])))
+            })
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    // Index of the point at |p|, if any.
+    #[must_use]
+    pub fn find(&self, p: Pt) -> Option<usize> {
+        self.pts.iter().position(|&q| pt_eq(p, q))
+    }
+}
+
+// Whether |p| is strictly inside the circumcircle of |tri|.
+fn in_circumcircle(pts: &[Pt], tri: [usize; 3], p: Pt) -> bool {
+    let [mut a, b, mut c] = tri.map(|i| pts[i]);
+    if orientation(&Line::new(a, b), c) < 0 {
+        std::mem::swap(&mut a, &mut c);
+    }
+    let (a, b, c) = (a - p, b - p, c - p);
+    let det = (a.x * a.x + a.y * a.y) * (b.x * c.y - c.x * b.y)
+        - (b.x * b.x + b.y * b.y) * (a.x * c.y - c.x * a.y)
+        + (c.x * c.x + c.y * c.y) * (a.x * b.y - b.x * a.y);
+    det > 0.0
+}
+
+// Points every |spacing| or less along the closed outline |pts|, each moved
+// |margin| inside it. Corners are also moved |margin| along their outgoing
+// edge, so they end up inside both edges of a convex corner.
+fn outline_pts(pts: &[Pt], spacing: f64, margin: f64) -> Vec<Pt> {
+    let ccw = signed_poly_area(pts) > 0.0;
+    let mut out = Vec::new();
+    for i in 0..pts.len() {
+        let seg = Seg::new(pts[i], pts[(i + 1) % pts.len()]);
+        let len = seg.len();
+        if len <= 0.0 {
+            continue;
+        }
+        let d = (1.0 / len) * seg.dir();
+        let inward = if ccw { pt(-d.y, d.x) } else { pt(d.y, -d.x) };
+        let n = (len / spacing).ceil().max(1.0) as usize;
+        out.push(seg.st + margin * (inward + d));
+        out.extend((1..n).map(|k| seg.point_at(k as f64 / n as f64) + margin * inward));
+    }
+    out
+}
+
+// Points to triangulate for channel routing: pin centres, the centre and
+// corners of each pad and keepout, and points every |spacing| along the
+// board outline. Corners are pushed out (or in, for the outline) by |margin|
+// so a wire through them clears the obstacle.
+#[must_use]
+pub fn channel_pts(pcb: &Pcb, margin: f64, spacing: f64) -> Vec<Pt> {
+    let mut pts = Vec::new();
+    let mut around = |r: Rt| {
+        pts.push(r.center());
+        pts.extend(r.inset(-margin, -margin).pts());
+    };
+    for c in pcb.components() {
+        for pin in c.pins() {
+            let tf = c.pin_tf(pin);
+            around(Rt::enclosing(c.pin_pt(pin), c.pin_pt(pin)));
+            pin.padstack.shapes.iter().for_each(|s| around(shape_tf(&tf, &s.shape).bounds()));
+        }
+        c.keepouts.iter().for_each(|k| around(shape_tf(&c.tf(), &k.shape.shape).bounds()));
+    }
+    pcb.keepouts().iter().for_each(|k| around(k.shape.shape.bounds()));
+    for b in pcb.boundaries() {
+        // Arcs in the design are already flattened to paths, but circles
+        // need flattening here.
+        let outline = match &b.shape.shape {
+            Shape::Polygon(p) => p.pts().to_vec(),
+            Shape::Path(p) => p.pts().to_vec(),
+            Shape::Circle(c) => {
+                let n = ((TAU * c.r() / spacing).ceil() as usize).max(8);
+                (0..n)
+                    .map(|i| {
+                        let a = TAU * i as f64 / n as f64;
+                        c.p() + c.r() * pt(a.cos(), a.sin())
+                    })
+                    .collect()
+            }
+            s => s.bounds().pts().to_vec(),
+        };
+        pts.extend(outline_pts(&outline, spacing, margin));
+    }
+    pts
+}
+
+// Default spacing of triangulation points along the board outline, in mm.
+const DEFAULT_BOUNDARY_SPACING: f64 = 2.0;
+
+// Routes each net along the edges of a Delaunay triangulation of
+// |channel_pts| rather than on a grid. The triangulation has far fewer nodes
+// than a grid, so this is much faster on sparse boards, but wires can only
+// bend at triangulation points and each connection stays on one layer, so
+// it fails where a grid router would find a way through.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct ChannelRouter {
+    place: PlaceModel,
+    net_order: Vec<Id>,
+    tri: Triangulation,
+    adj: Vec<Vec<usize>>,
+    routing_layers: LayerSet,
+}
+
+impl ChannelRouter {
+    pub fn new(pcb: Pcb, net_order: Vec<Id>) -> Self {
+        Self::with_boundary_spacing(pcb, net_order, DEFAULT_BOUNDARY_SPACING)
+    }
+
+    // Creates a router with triangulation points every |spacing| along the
+    // board outline. Closer points follow the outline better but make a
+    // bigger triangulation.
+    pub fn with_boundary_spacing(pcb: Pcb, net_order: Vec<Id>, spacing: f64) -> Self {
+        // Enough room for the widest wire and largest clearance.
+        let margin = pcb
+            .nets()
+            .map(|net| {
+                let rs = pcb.net_ruleset(net.id);
+                let clearance = rs.clearances().iter().map(Clearance::amount).fold(0.0, f64::max);
+                pcb.net_radius(net.id) + clearance
+            })
+            .fold(0.0, f64::max);
+        let tri = Triangulation::new(&channel_pts(&pcb, margin * 1.01, spacing));
+        let mut adj = vec![Vec::new(); tri.pts.len()];
+        for (a, b) in tri.edges() {
+            adj[a].push(b);
+            adj[b].push(a);
+        }
+        let routing_layers = routing_layers(&pcb);
+        Self { place: PlaceModel::new(pcb), net_order, tri, adj, routing_layers }
+    }
+
+    #[must_use]
+    pub fn triangulation(&self) -> &Triangulation {
+        &self.tri
+    }
+
+    // Shortest path from |src| to |dst| along unblocked edges on |layer|.
+    fn search(&self, net_id: Id, layer: LayerId, src: usize, dst: usize) -> Option<Vec<usize>> {
+        let pts = &self.tri.pts;
+        let mut q: PriorityQueue<usize, OrderedFloat<f64>> = PriorityQueue::new();
+        let mut cost: HashMap<usize, (f64, usize)> = HashMap::new();
+        cost.insert(src, (0.0, src));
+        q.push(src, OrderedFloat(0.0));
+        while let Some((cur, _)) = q.pop() {
+            if cur == dst {
+                let mut path = vec![dst];
+                while *path.last().unwrap() != src {
+                    path.push(cost[path.last().unwrap()].1);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let cur_cost = cost[&cur].0;
+            for &next in &self.adj[cur] {
+                let c = cur_cost + pts[cur].dist(pts[next]);
+                if cost.get(&next).map_or(false, |v| v.0 <= c) {
+                    continue;
+                }
+                let wire = self.place.create_wire(net_id, layer, &[pts[cur], pts[next]]);
+                if self.place.is_wire_blocked(&wire) {
+                    continue;
+                }
+                cost.insert(next, (c, cur));
+                q.push(next, OrderedFloat(-(c + pts[next].dist(pts[dst]))));
+            }
+        }
+        None
+    }
+
+    // Connects each pair of pins in the spanning tree of the net's pins on
+    // the cheapest layer both pins are on.
+    fn route_net(&mut self, net_id: Id) -> Result<RouteResult> {
+        let pcb = self.place.pcb();
+        let net = pcb.net(net_id).ok_or_else(|| eyre!("missing net {}", net_id))?;
+        let mut pins = Vec::new();
+        for p in &net.pins {
+            let (c, pin) = pcb.pin_ref(p)?;
+            let idx = self.tri.find(c.pin_pt(pin)).ok_or_else(|| eyre!("pin not triangulated"))?;
+            pins.push((idx, pin.padstack.layers() & self.routing_layers));
+        }
+        let pts: Vec<_> = pins.iter().map(|v| self.tri.pts[v.0]).collect();
+
+        let mut res = RouteResult::default();
+        for (a, b) in mst(&pts) {
+            let layers = pins[a].1 & pins[b].1;
+            let best = layers
+                .iter()
+                .filter_map(|layer| {
+                    Some((layer, self.search(net_id, layer, pins[a].0, pins[b].0)?))
+                })
+                .min_by_key(|(_, path)| {
+                    OrderedFloat(
+                        path.windows(2)
+                            .map(|v| self.tri.pts[v[0]].dist(self.tri.pts[v[1]]))
+                            .sum::<f64>(),
+                    )
+                });
+            let Some((layer, path)) = best else {
+                res.failed = true;
+                continue;
+            };
+            let path: Vec<_> = path.iter().map(|&i| self.tri.pts[i]).collect();
+            let wire = self.place.create_wire(net_id, layer, &path);
+            self.place.add_wire(&wire);
+            res.wires.push(wire);
+        }
+        Ok(res)
+    }
+}
+
+impl RouteStrategy for ChannelRouter {
+    fn route(&mut self) -> Result<RouteResult> {
+        let mut res = RouteResult::default();
+        for net_id in self.net_order.clone() {
+            let route = self.route_net(net_id)?;
+            if route.failed {
+                res.failed_nets.push(net_id);
+            }
+            res.merge(route);
+        }
+        res.num_nets = self.net_order.len();
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{pt, rt, ShapeOps};
+
+    use super::{ChannelRouter, Triangulation};
+    use crate::geom::contains::pt_in_poly;
+    use crate::model::pcb::{Keepout, KeepoutType, LayerKind, LayerShape, Pcb};
+    use crate::route::place_model::PlaceModel;
+    use crate::route::router::RouteStrategy;
+    use crate::testing::{add_component, add_net, board, outline_board};
+
+    fn two_pins() -> Pcb {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)]);
+        add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        pcb
+    }
+
+    fn route(pcb: &Pcb) -> (ChannelRouter, crate::route::router::RouteResult) {
+        let order = pcb.nets().map(|n| n.id).collect();
+        let mut router = ChannelRouter::new(pcb.clone(), order);
+        let res = router.route().unwrap();
+        (router, res)
+    }
+
+    #[test]
+    fn delaunay_square() {
+        let t = Triangulation::new(&[pt(0.0, 0.0), pt(1.0, 0.0), pt(1.0, 1.0), pt(0.0, 1.0)]);
+        assert_eq!(t.tris.len(), 2);
+        assert_eq!(t.edges().len(), 5);
+    }
+
+    #[test]
+    fn routes_along_triangulation_edges() {
+        let pcb = two_pins();
+        let (router, res) = route(&pcb);
+        assert!(!res.failed);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+        let tri = router.triangulation();
+        let edges = tri.edges();
+        for w in &res.wires {
+            let Shape::Path(p) = &w.shape.shape else { panic!("expected a path") };
+            for v in p.pts().windows(2) {
+                let (a, b) = (tri.find(v[0]).unwrap(), tri.find(v[1]).unwrap());
+                assert!(edges.contains(&(a.min(b), a.max(b))));
+            }
+        }
+    }
+
+    #[test]
+    fn routes_around_keepout() {
+        let mut pcb = two_pins();
+        let layers = pcb.layers_by_kind(LayerKind::All);
+        let shape = LayerShape { layers, shape: rt(4.0, 3.0, 6.0, 7.0).shape() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        let (_, res) = route(&pcb);
+        assert!(!res.failed);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+        let place = PlaceModel::new(pcb);
+        assert!(res.wires.iter().all(|w| !place.is_wire_blocked(w)));
+    }
+
+    #[test]
+    fn triangulation_follows_concave_outline() {
+        // An L with the top right quarter missing.
+        let outline = [
+            pt(0.0, 0.0),
+            pt(10.0, 0.0),
+            pt(10.0, 4.0),
+            pt(4.0, 4.0),
+            pt(4.0, 10.0),
+            pt(0.0, 10.0),
+        ];
+        let mut pcb = outline_board(&outline);
+        add_component(&mut pcb, "U1", pt(8.0, 2.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(2.0, 8.0), &[pt(0.0, 0.0)]);
+        add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let order = pcb.nets().map(|n| n.id).collect();
+        let mut router = ChannelRouter::with_boundary_spacing(pcb.clone(), order, 1.0);
+
+        let pts = &router.triangulation().pts;
+        assert!(pts.iter().all(|&p| pt_in_poly(p, &outline)), "{pts:?}");
+        // Points run along both inner edges, just inside them.
+        for v in [5.0, 6.0, 7.0, 8.0, 9.0] {
+            assert!(pts.iter().any(|p| (p.x - v).abs() < 0.6 && p.y < 4.0 && p.y > 3.5));
+            assert!(pts.iter().any(|p| (p.y - v).abs() < 0.6 && p.x < 4.0 && p.x > 3.5));
+        }
+
+        // The straight line between the pins leaves the board.
+        let res = router.route().unwrap();
+        assert!(!res.failed);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+        let place = PlaceModel::new(pcb);
+        assert!(res.wires.iter().all(|w| !place.is_wire_blocked(w)));
+    }
+}
@@ -0,0 +1,191 @@
+use eyre::{eyre, Result};
+use memegeom::primitive::point::Pt;
+
+use crate::geom::math::{lerp, orientation, pt_eq};
+use crate::geom::offset::offset_polyline;
+use crate::geom::Line;
+use crate::model::pcb::{DiffPair, LayerSet};
+use crate::name::Id;
+use crate::route::astar::AStarRouter;
+use crate::route::router::RouteResult;
+
+// Routes the two nets of |pair| side by side. The pair is searched for as a
+// single wire wide enough for both traces, whose centreline is then offset
+// |spacing| / 2 to either side. Pairs with more than two pins per net, or
+// with no corridor that wide, fall back to routing each net on its own, so
+// the traces split where they have to and meet again at the pins.
+pub(crate) fn route_pair(router: &mut AStarRouter, pair: &DiffPair) -> Result<RouteResult> {
+    if let Some(res) = route_coupled(router, pair)? {
+        return Ok(res);
+    }
+    let mut res = RouteResult::default();
+    for net_id in [pair.net_a, pair.net_b] {
+        let route = router.route_net(net_id)?;
+        if route.failed {
+            res.failed_nets.push(net_id);
+        }
+        res.merge(route);
+    }
+    Ok(res)
+}
+
+fn route_coupled(router: &mut AStarRouter, pair: &DiffPair) -> Result<Option<RouteResult>> {
+    let pcb = router.place().pcb().clone();
+    let pins = |net_id: Id| -> Result<Vec<(Pt, LayerSet)>> {
+        let net = pcb.net(net_id).ok_or_else(|| eyre!("missing net {}", net_id))?;
+        net.pins
+            .iter()
+            .map(|p| {
+                let (c, pin) = pcb.pin_ref(p)?;
+                Ok((c.pin_pt(pin), pin.padstack.layers()))
+            })
+            .collect()
+    };
+    let (a, mut b) = (pins(pair.net_a)?, pins(pair.net_b)?);
+    if a.len() != 2 || b.len() != 2 {
+        return Ok(None);
+    }
+    // Start each end of the pair from the closest pins of the two nets.
+    if a[0].0.dist(b[1].0) + a[1].0.dist(b[0].0) < a[0].0.dist(b[0].0) + a[1].0.dist(b[1].0) {
+        b.swap(0, 1);
+    }
+    let layers = a[0].1 & a[1].1 & b[0].1 & b[1].1 & router.routing_layers();
+    let half = pair.spacing / 2.0;
+    let r = pcb.net_radius(pair.net_a).max(pcb.net_radius(pair.net_b));
+    let (st, en) = (lerp(a[0].0, b[0].0, 0.5), lerp(a[1].0, b[1].0, 0.5));
+
+    // The corridor is searched for as |net_a|, so |net_b|'s pins would block
+    // it. Take them out while searching.
+    let net_b = pcb.net(pair.net_b).ok_or_else(|| eyre!("missing net {}", pair.net_b))?;
+    router.place_mut().remove_net(net_b);
+    let found = layers.iter().find_map(|layer| {
+        let centre = router.search(pair.net_a, layer, half + r, st, en)?;
+        Some((layer, centre))
+    });
+    router.place_mut().add_net(&pcb, net_b)?;
+    let Some((layer, centre)) = found else {
+        return Ok(None);
+    };
+
+    let (left, right) = (offset_polyline(&centre, half), offset_polyline(&centre, -half));
+    if left.len() < 2 {
+        return Ok(None); // Both ends of the pair are in the same place.
+    }
+    // |net_a| takes the side its first pin is on.
+    let (side_a, side_b) = if orientation(&Line::new(st, st + left[1] - left[0]), a[0].0) >= 0 {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    let trace = |p: Pt, side: Vec<Pt>, q: Pt| {
+        let mut pts = vec![p];
+        pts.extend(side);
+        pts.push(q);
+        pts.dedup_by(|a, b| pt_eq(*a, *b));
+        pts
+    };
+    let place = router.place_mut();
+    let wa = place.create_wire(pair.net_a, layer, &trace(a[0].0, side_a, a[1].0));
+    let wb = place.create_wire(pair.net_b, layer, &trace(b[0].0, side_b, b[1].0));
+    // The stubs joining the pins to the corridor, and the tips of mitred
+    // corners, can reach outside it.
+    if place.is_wire_blocked(&wa) || place.is_wire_blocked(&wb) {
+        return Ok(None);
+    }
+    place.add_wire(&wa);
+    place.add_wire(&wb);
+    Ok(Some(RouteResult { wires: vec![wa, wb], ..RouteResult::default() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{pt, rt, ShapeOps};
+
+    use crate::geom::distance::pt_seg_dist;
+    use crate::geom::math::{f64_eq, lerp};
+    use crate::geom::Seg;
+    use crate::model::pcb::{DiffPair, Keepout, KeepoutType, LayerKind, LayerShape, Pcb};
+    use crate::route::astar::AStarRouter;
+    use crate::route::router::{RouteResult, RouteStrategy};
+    use crate::testing::{add_component, add_net, board};
+
+    const SPACING: f64 = 1.0;
+
+    // Two connector rows 16 mm apart with the pair on adjacent pins, and a
+    // keepout between them that the pair has to go around.
+    fn connectors() -> Pcb {
+        let mut pcb = board(20.0, 10.0);
+        let rows = [pt(0.0, 0.5), pt(0.0, -0.5)];
+        add_component(&mut pcb, "J1", pt(2.0, 5.0), &rows);
+        add_component(&mut pcb, "J2", pt(18.0, 5.0), &rows);
+        let p = add_net(&mut pcb, "D_P", &[("J1", "1"), ("J2", "1")]);
+        let n = add_net(&mut pcb, "D_N", &[("J1", "2"), ("J2", "2")]);
+        pcb.add_diff_pair(DiffPair { net_a: p, net_b: n, spacing: SPACING }).unwrap();
+        let layers = pcb.layers_by_kind(LayerKind::All);
+        let shape = LayerShape { layers, shape: rt(9.0, 2.0, 11.0, 7.0).shape() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        pcb
+    }
+
+    fn route(pcb: &Pcb) -> RouteResult {
+        let order = pcb.nets().map(|n| n.id).collect();
+        let mut astar = AStarRouter::new(pcb.clone(), order);
+        // Keep the centreline on the grid between the pins, with few turns.
+        astar.set_pitch(0.25);
+        astar.set_turn_cost(1.0);
+        astar.route().unwrap()
+    }
+
+    fn pts(s: &Shape) -> Vec<Pt> {
+        let Shape::Path(p) = s else { panic!("expected a path") };
+        p.pts().to_vec()
+    }
+
+    fn dist(p: Pt, pts: &[Pt]) -> f64 {
+        pts.windows(2).map(|v| pt_seg_dist(p, &Seg::new(v[0], v[1]))).fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn pair_keeps_spacing_around_keepout() {
+        let pcb = connectors();
+        let res = route(&pcb);
+        assert!(!res.failed, "{:?}", res.failed_nets);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+        assert_eq!(res.wires.len(), 2);
+        let (a, b) = (&res.wires[0], &res.wires[1]);
+        assert_eq!((a.net_id, b.net_id), (pcb.to_id("D_P"), pcb.to_id("D_N")));
+        assert_eq!(a.shape.layers, b.shape.layers);
+
+        let (a, b) = (pts(&a.shape.shape), pts(&b.shape.shape));
+        // Went around the keepout rather than through it.
+        assert!(a.iter().chain(&b).any(|p| p.y > 7.0 || p.y < 2.0));
+        // Apart from the stubs at the pins, each segment of a trace runs
+        // |SPACING| from the other trace.
+        for (x, y) in [(&a, &b), (&b, &a)] {
+            assert!(x.len() >= 4, "{x:?}");
+            for v in x[1..x.len() - 1].windows(2) {
+                let mid = lerp(v[0], v[1], 0.5);
+                assert!(f64_eq(dist(mid, y), SPACING), "{mid:?} is {} away", dist(mid, y));
+            }
+        }
+    }
+
+    #[test]
+    fn pair_splits_when_corridor_too_narrow() {
+        let mut pcb = connectors();
+        // A wall with a gap that fits one trace on each layer, but not the pair.
+        let layers = pcb.layers_by_kind(LayerKind::All);
+        for (b, t) in [(0.0, 4.7), (5.3, 10.0)] {
+            let shape = LayerShape { layers, shape: rt(12.0, b, 13.0, t).shape() };
+            pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        }
+        let res = route(&pcb);
+        assert!(!res.failed, "{:?}", res.failed_nets);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+        // Each net went through the gap on its own layer.
+        assert_eq!(res.wires.len(), 2);
+        assert_ne!(res.wires[0].shape.layers, res.wires[1].shape.layers);
+    }
+}
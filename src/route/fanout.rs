@@ -0,0 +1,107 @@
+use eyre::{eyre, Result};
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::pt;
+
+use crate::geom::math::f64_cmp;
+use crate::model::pcb::{Pcb, PinRef};
+use crate::name::Id;
+use crate::route::place_model::PlaceModel;
+use crate::route::router::RouteResult;
+
+// Generates dog-bone escapes for the pins of |component_id|, e.g. for a BGA:
+// a short wire from each pin diagonally into the gap between its neighbours,
+// ending in a via so the main router can continue on another layer. Pins are
+// escaped away from the centre of the component where possible. Pins on a
+// net that have no unblocked escape mark the result as failed. Escapes need
+// a via, so boards without via padstacks are an error.
+pub fn fanout(pcb: &Pcb, component_id: Id) -> Result<RouteResult> {
+    let component = pcb
+        .component(component_id)
+        .ok_or_else(|| eyre!("unknown component id {}", component_id))?;
+    if pcb.via_padstacks().is_empty() {
+        return Err(eyre!("no via padstacks to fan out {} with", pcb.to_name(component_id)));
+    }
+    let pins: Vec<_> =
+        component.pins().map(|p| (PinRef::new(component, p), component.pin_pt(p), p)).collect();
+    let mut res = RouteResult::default();
+    if pins.len() < 2 {
+        return Ok(res);
+    }
+
+    let mut pitch = f64::INFINITY;
+    for (i, (_, a, _)) in pins.iter().enumerate() {
+        for (_, b, _) in &pins[i + 1..] {
+            pitch = pitch.min(a.dist(*b));
+        }
+    }
+    let centre = pins.iter().fold(Pt::zero(), |acc, (_, p, _)| acc + *p);
+    let centre = (1.0 / pins.len() as f64) * centre;
+
+    let mut place = PlaceModel::new(pcb.clone());
+    for (pin_ref, p, pin) in pins {
+        let Some(net_id) = pcb.pin_ref_net(&pin_ref) else { continue };
+        let Some(layer) = pin.padstack.layers().first() else { continue };
+
+        let away = p - centre;
+        let mut dirs = [pt(1.0, 1.0), pt(1.0, -1.0), pt(-1.0, 1.0), pt(-1.0, -1.0)];
+        dirs.sort_by(|a, b| {
+            f64_cmp(&(b.x * away.x + b.y * away.y), &(a.x * away.x + a.y * away.y))
+        });
+
+        let mut escaped = false;
+        for d in dirs {
+            let via_p = p + (pitch / 2.0) * d;
+            let wire = place.create_wire(net_id, layer, &[p, via_p]);
+            let via = place.create_via(net_id, via_p);
+            if place.is_wire_blocked(&wire) || place.is_via_blocked(&via) {
+                continue;
+            }
+            place.add_wire(&wire);
+            place.add_via(&via);
+            res.wires.push(wire);
+            res.vias.push(via);
+            escaped = true;
+            break;
+        }
+        res.failed |= !escaped;
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+
+    use super::fanout;
+    use crate::route::router::apply_route_result;
+    use crate::testing::{add_component, add_net, bare_board, board};
+
+    // 2x2 grid of pins 1 mm apart, each on its own net.
+    const GRID: [(f64, f64); 4] = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+
+    #[test]
+    fn every_pin_escapes_without_collisions() {
+        let mut pcb = board(10.0, 10.0);
+        let pins: Vec<_> = GRID.iter().map(|&(x, y)| pt(x, y)).collect();
+        let id = add_component(&mut pcb, "U1", pt(4.5, 4.5), &pins);
+        for i in 1..=pins.len() {
+            add_net(&mut pcb, &format!("N{i}"), &[("U1", &i.to_string())]);
+        }
+        let res = fanout(&pcb, id).unwrap();
+        assert!(!res.failed);
+        assert_eq!(res.wires.len(), pins.len());
+        assert_eq!(res.vias.len(), pins.len());
+        apply_route_result(&mut pcb, &res);
+        assert!(pcb.drc().is_empty(), "{:?}", pcb.drc());
+    }
+
+    #[test]
+    fn needs_a_via_padstack() {
+        let mut pcb = bare_board(10.0, 10.0);
+        let pins: Vec<_> = GRID.iter().map(|&(x, y)| pt(x, y)).collect();
+        let id = add_component(&mut pcb, "U1", pt(4.5, 4.5), &pins);
+        add_net(&mut pcb, "N1", &[("U1", "1")]);
+        let err = fanout(&pcb, id).unwrap_err();
+        assert!(err.to_string().contains("no via padstacks"), "{err}");
+    }
+}
@@ -0,0 +1,233 @@
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{poly, pt, ShapeOps};
+use memegeom::tf::Tf;
+use strum::IntoEnumIterator;
+
+use crate::geom::math::{f64_eq, pt_eq};
+use crate::geom::polygon::triangulate;
+use crate::geom::Tri;
+use crate::model::pcb::{Clearance, LayerId, LayerSet, LayerShape, ObjectKind, Pcb, Wire};
+use crate::name::Id;
+use crate::route::grid::{grid_cells, grid_points};
+use crate::route::place_model::{NetQuery, PlaceModel};
+
+// Size of the cells used to build up copper fills.
+const FILL_PITCH: f64 = 0.25;
+
+// Fills the free area of |layer| with copper on |net_id|, keeping at least
+// |clearance| from anything on another net. Free grid cells are grouped into
+// polygons, each a stack of rows with one run of cells per row, so the fill
+// stays conservative around angled obstacles and has no holes.
+pub fn add_ground_fill(pcb: &mut Pcb, net_id: Id, layer: LayerId, clearance: f64) {
+    let place = PlaceModel::new(pcb.clone());
+    let pairs: Vec<_> = ObjectKind::iter().map(|k| (ObjectKind::Area, k)).collect();
+    let clearances = [Clearance::new(clearance, &pairs)];
+    let is_free = |r: Rt| {
+        let ls = LayerShape { layers: LayerSet::one(layer), shape: r.shape() };
+        !place.is_shape_blocked(
+            &Tf::identity(),
+            &ls,
+            NetQuery::Except(net_id),
+            ObjectKind::Area,
+            &clearances,
+        )
+    };
+
+    // Merge runs of free cells in each row into strips.
+    let mut rows: Vec<Vec<Rt>> = Vec::new();
+    let mut run: Option<Rt> = None;
+    for cell in grid_cells(&pcb.bounds(), FILL_PITCH) {
+        let free = is_free(cell);
+        run = match run {
+            Some(r) if free && f64_eq(r.b(), cell.b()) && f64_eq(r.r(), cell.l()) => {
+                Some(r.united(&cell))
+            }
+            prev => {
+                prev.into_iter().for_each(|r| push_strip(&mut rows, r));
+                free.then_some(cell)
+            }
+        };
+    }
+    run.into_iter().for_each(|r| push_strip(&mut rows, r));
+
+    for pts in stack_strips(&rows) {
+        let shape = LayerShape { layers: LayerSet::one(layer), shape: poly(&pts).shape() };
+        pcb.add_wire(Wire { shape, net_id });
+    }
+}
+
+// Adds |r| to the row it lies in, starting a new row if needed.
+fn push_strip(rows: &mut Vec<Vec<Rt>>, r: Rt) {
+    match rows.last_mut() {
+        Some(row) if f64_eq(row[0].b(), r.b()) => row.push(r),
+        _ => rows.push(vec![r]),
+    }
+}
+
+// Joins strips in consecutive rows that overlap only each other into
+// polygons. Each polygon is traced up the right side of its strips and back
+// down the left side.
+fn stack_strips(rows: &[Vec<Rt>]) -> Vec<Vec<Pt>> {
+    let overlaps = |a: &Rt, b: &Rt| f64_eq(a.t(), b.b()) && a.l() < b.r() && b.l() < a.r();
+    let mut stacks: Vec<Vec<Rt>> = Vec::new();
+    // Index into |stacks| of the stack each strip of the previous row ended.
+    let mut prev: Vec<usize> = Vec::new();
+    let mut prev_row: &[Rt] = &[];
+    for row in rows {
+        let mut cur = Vec::new();
+        for r in row {
+            let below: Vec<_> =
+                (0..prev_row.len()).filter(|&i| overlaps(&prev_row[i], r)).collect();
+            let unique = match below[..] {
+                [i] => row.iter().filter(|v| overlaps(&prev_row[i], v)).count() == 1,
+                _ => false,
+            };
+            if unique {
+                let idx = prev[below[0]];
+                stacks[idx].push(*r);
+                cur.push(idx);
+            } else {
+                stacks.push(vec![*r]);
+                cur.push(stacks.len() - 1);
+            }
+        }
+        prev = cur;
+        prev_row = row;
+    }
+
+    stacks
+        .into_iter()
+        .map(|stack| {
+            let mut pts = Vec::new();
+            for r in &stack {
+                pts.extend([pt(r.r(), r.b()), pt(r.r(), r.t())]);
+            }
+            for r in stack.iter().rev() {
+                pts.extend([pt(r.l(), r.t()), pt(r.l(), r.b())]);
+            }
+            simplify_ring(pts)
+        })
+        .collect()
+}
+
+// Removes repeated points and points in the middle of straight edges.
+fn simplify_ring(mut pts: Vec<Pt>) -> Vec<Pt> {
+    pts.dedup_by(|a, b| pt_eq(*a, *b));
+    let mut i = 0;
+    while i < pts.len() && pts.len() > 3 {
+        let n = pts.len();
+        let (a, b, c) = (pts[(i + n - 1) % n], pts[i], pts[(i + 1) % n]);
+        if pt_eq(a, b)
+            || (f64_eq(a.x, b.x) && f64_eq(b.x, c.x))
+            || (f64_eq(a.y, b.y) && f64_eq(b.y, c.y))
+        {
+            pts.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    pts
+}
+
+// Places vias on |net_id| every |spacing| wherever the via centre is inside
+// that net's copper fill on |layer| and the via keeps clearance from
+// everything on other nets.
+pub fn add_stitching_vias(pcb: &mut Pcb, net_id: Id, layer: LayerId, spacing: f64) {
+    if pcb.via_padstacks().is_empty() {
+        return;
+    }
+    let fills: Vec<Tri> = pcb
+        .wires()
+        .iter()
+        .filter(|w| w.net_id == net_id && w.shape.layers.contains(layer))
+        .filter_map(|w| if let Shape::Polygon(p) = &w.shape.shape { Some(p) } else { None })
+        .flat_map(|p| triangulate(p.pts()))
+        .collect();
+    let place = PlaceModel::new(pcb.clone());
+    let clearances = pcb.net_ruleset(net_id).clearances().to_vec();
+
+    let mut vias = Vec::new();
+    for p in grid_points(&pcb.bounds(), spacing) {
+        if !fills.iter().any(|t| t.contains(p)) {
+            continue;
+        }
+        let via = place.create_via(net_id, p);
+        let blocked = via.padstack.shapes.iter().any(|s| {
+            place.is_shape_blocked(
+                &via.tf(),
+                s,
+                NetQuery::Except(net_id),
+                ObjectKind::Via,
+                &clearances,
+            )
+        });
+        if !blocked {
+            vias.push(via);
+        }
+    }
+    for via in vias {
+        pcb.add_via(via);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{rt, ShapeOps};
+
+    use super::{add_ground_fill, add_stitching_vias};
+    use crate::geom::distance::shape_dist;
+    use crate::geom::intersects::shape_intersects_shape;
+    use crate::geom::math::f64_ge;
+    use crate::model::pcb::{Keepout, KeepoutType, LayerKind, LayerShape, Pcb};
+    use crate::name::Id;
+    use crate::testing::{add_net, board};
+
+    const CLEARANCE: f64 = 0.3;
+
+    // Board with a keepout in the middle and a poured GND net on F.Cu.
+    fn poured() -> (Pcb, Id, Shape) {
+        let mut pcb = board(10.0, 10.0);
+        let keepout = rt(4.0, 4.0, 6.0, 5.0).shape();
+        let layers = pcb.layers_by_kind(LayerKind::All);
+        let shape = LayerShape { layers, shape: keepout.clone() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        let gnd = add_net(&mut pcb, "GND", &[]);
+        add_ground_fill(&mut pcb, gnd, 0, CLEARANCE);
+        (pcb, gnd, keepout)
+    }
+
+    #[test]
+    fn pour_is_polygons_clear_of_keepout() {
+        let (pcb, gnd, keepout) = poured();
+        let pour: Vec<_> = pcb.wires().iter().filter(|w| w.net_id == gnd).collect();
+        // The keepout splits the board into a few pieces, not a strip per row.
+        assert!(!pour.is_empty() && pour.len() < 10, "{} pieces", pour.len());
+        for w in pour {
+            assert!(matches!(w.shape.shape, Shape::Polygon(_)));
+            assert!(w.shape.layers.contains(0) && !w.shape.layers.contains(1));
+            assert!(f64_ge(shape_dist(&w.shape.shape, &keepout), CLEARANCE));
+        }
+    }
+
+    #[test]
+    fn stitching_stays_in_pour_layer() {
+        let (mut pcb, gnd, keepout) = poured();
+        add_stitching_vias(&mut pcb, gnd, 1, 1.0);
+        assert!(pcb.vias().is_empty());
+
+        add_stitching_vias(&mut pcb, gnd, 0, 1.0);
+        assert!(!pcb.vias().is_empty());
+        for v in pcb.vias() {
+            assert!(v.net_id == gnd);
+            assert!(!shape_intersects_shape(&keepout, &rt(v.p.x, v.p.y, v.p.x, v.p.y).shape()));
+        }
+        // Nothing can go where another net's pour is.
+        let other = add_net(&mut pcb, "VCC", &[]);
+        let before = pcb.vias().len();
+        add_stitching_vias(&mut pcb, other, 0, 1.0);
+        assert_eq!(pcb.vias().len(), before);
+    }
+}
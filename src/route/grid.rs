@@ -1,34 +1,142 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::SQRT_2;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 use eyre::{eyre, Result};
-use memegeom::geom::math::f64_cmp;
-use memegeom::geom::qt::query::TagQuery;
 use memegeom::primitive::point::{Pt, PtI};
 use memegeom::primitive::rect::{Rt, RtI};
-use memegeom::primitive::{circ, pt, pti, ShapeOps};
+use memegeom::primitive::{circ, pt, pti, rt, ShapeOps};
 use memegeom::tf::Tf;
 use ordered_float::OrderedFloat;
 use priority_queue::PriorityQueue;
+use rayon::prelude::*;
 
-use crate::model::pcb::{LayerSet, LayerShape, ObjectKind, Pcb, PinRef, Via, Wire};
+use crate::geom::contains::pt_in_rt;
+use crate::geom::intersects::rt_intersects_rt;
+use crate::geom::math::{f64_cmp, f64_le};
+use crate::model::pcb::{
+    Clearance, LayerId, LayerSet, LayerShape, ObjectKind, Pcb, PinRef, Via, Wire,
+};
 use crate::name::{Id, NO_ID};
-use crate::route::place_model::PlaceModel;
-use crate::route::router::{RouteResult, RouteStrategy};
+use crate::route::layers::{assign_layers, routing_layers, Direction, ViaCostModel};
+use crate::route::place_model::{NetQuery, PlaceId, PlaceModel};
+use crate::route::router::{Progress, RouteResult, RouteStrategy};
 
-const VIA_COST: f64 = 10.0;
+// Cost multiplier for wires placed off a net's preferred layer.
+const OFF_LAYER_FACTOR: f64 = 1.5;
+// Cost multiplier for moves against a layer's preferred direction.
+const OFF_DIRECTION_FACTOR: f64 = 2.0;
+// Default number of rip-up and reroute passes over failed nets.
+const MAX_RIPUP_ROUNDS: usize = 3;
+// Number of search steps between checks of the routing deadline.
+const DEADLINE_CHECK_ITERS: usize = 1024;
+// Used when there is nothing on the board to derive a resolution from.
+const DEFAULT_RESOLUTION: f64 = 0.4;
+// Lower bound on the derived resolution, to stop the grid blowing up.
+const MIN_RESOLUTION: f64 = 0.05;
+// Room around a net's pins that the first attempt at routing it may search.
+// Nets whose search bounds are far apart can't affect each other, so they are
+// routed in parallel.
+const SEARCH_MARGIN: f64 = 5.0;
 
+// Moves between grid cells and their length in grid steps.
 const DIR: [(PtI, f64); 9] = [
     (pti(-1, 0), 1.0),
     (pti(1, 0), 1.0),
     (pti(0, -1), 1.0),
     (pti(0, 1), 1.0),
-    (pti(1, 1), 1.414),
-    (pti(1, -1), 1.414),
-    (pti(-1, 1), 1.414),
-    (pti(-1, -1), 1.414),
-    (pti(0, 0), VIA_COST),
+    (pti(1, 1), SQRT_2),
+    (pti(1, -1), SQRT_2),
+    (pti(-1, 1), SQRT_2),
+    (pti(-1, -1), SQRT_2),
+    (pti(0, 0), 0.0), // Via, costed by |GridRouter::via_cost|.
 ];
 
+// Number of grid steps of size |pitch| needed to cover |len|. A trailing
+// partial step counts as a full step.
+fn grid_steps(len: f64, pitch: f64) -> usize {
+    let n = (len / pitch).ceil().max(0.0) as usize;
+    if n > 0 && f64_le(len, (n - 1) as f64 * pitch) {
+        n - 1
+    } else {
+        n
+    }
+}
+
+// Points of a grid with spacing |pitch| anchored at the bottom left of |r|.
+// The top and right edges of |r| are always included, even if they don't fall
+// on a multiple of |pitch|.
+pub fn grid_points(r: &Rt, pitch: f64) -> impl Iterator<Item = Pt> {
+    let (l, b, rr, t) = (r.l(), r.b(), r.r(), r.t());
+    let (nx, ny) = (grid_steps(r.w(), pitch), grid_steps(r.h(), pitch));
+    (0..=ny).flat_map(move |y| {
+        (0..=nx).map(move |x| pt((l + x as f64 * pitch).min(rr), (b + y as f64 * pitch).min(t)))
+    })
+}
+
+// Cells of a grid with spacing |pitch| anchored at the bottom left of |r|.
+// Cells on the top and right edges are clipped to |r|.
+pub fn grid_cells(r: &Rt, pitch: f64) -> impl Iterator<Item = Rt> {
+    let (l, b, rr, t) = (r.l(), r.b(), r.r(), r.t());
+    let (nx, ny) = (grid_steps(r.w(), pitch), grid_steps(r.h(), pitch));
+    (0..ny).flat_map(move |y| {
+        (0..nx).map(move |x| {
+            let (x, y) = (l + x as f64 * pitch, b + y as f64 * pitch);
+            rt(x, y, (x + pitch).min(rr), (y + pitch).min(t))
+        })
+    })
+}
+
+// Snaps |p| to the nearest point of a grid with spacing |pitch| passing
+// through |origin|.
+#[must_use]
+pub fn snap_to_grid(p: Pt, pitch: f64, origin: Pt) -> Pt {
+    let d = p - origin;
+    origin + pt((d.x / pitch).round() * pitch, (d.y / pitch).round() * pitch)
+}
+
+// Maps between world coordinates and grid cells of size |pitch|. Cell (0, 0)
+// has its lower left corner at |origin|.
+#[must_use]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GridCoord {
+    pub pitch: f64,
+    pub origin: Pt,
+}
+
+impl GridCoord {
+    pub fn new(pitch: f64, origin: Pt) -> Self {
+        Self { pitch, origin }
+    }
+
+    // Cell containing |p|. Points on a cell boundary map to the cell above
+    // and to the right.
+    #[must_use]
+    pub fn grid_pt(&self, p: Pt) -> PtI {
+        let d = p - self.origin;
+        pti((d.x / self.pitch).floor() as i64, (d.y / self.pitch).floor() as i64)
+    }
+
+    // Lower left corner of cell |p|.
+    #[must_use]
+    pub fn world_pt(&self, p: PtI) -> Pt {
+        self.origin + pt(p.x as f64 * self.pitch, p.y as f64 * self.pitch)
+    }
+
+    // Centre of cell |p|.
+    #[must_use]
+    pub fn world_pt_mid(&self, p: PtI) -> Pt {
+        self.world_pt(p) + pt(self.pitch / 2.0, self.pitch / 2.0)
+    }
+
+    #[must_use]
+    pub fn snap(&self, p: Pt) -> Pt {
+        snap_to_grid(p, self.pitch, self.origin)
+    }
+}
+
 #[must_use]
 #[derive(Debug, Default, Hash, Copy, Clone, PartialEq, Eq)]
 pub struct State {
@@ -53,30 +161,207 @@ impl Default for NodeData {
 
 pub type BlockMap = HashMap<State, i64>;
 
+// Route for a single net, along with what's needed to rip it up again.
+#[derive(Debug, Clone)]
+struct NetRoute {
+    res: RouteResult,
+    ids: Vec<PlaceId>,
+    bounds: Rt, // Bounds of the net's pins.
+}
+
+// Estimate of the remaining distance used to guide the search.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Heuristic {
+    #[default]
+    Euclidean,
+    // Not admissible with diagonal moves, so routes may not be the shortest,
+    // but the search expands fewer states.
+    Manhattan,
+}
+
+// Directions wires may run in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum RouteDirections {
+    Orthogonal, // Horizontal and vertical only.
+    #[default]
+    Octilinear, // Also at 45 degrees.
+}
+
 #[must_use]
 #[derive(Debug, Clone)]
 pub struct GridRouter {
-    resolution: f64,
+    grid: GridCoord,
     place: PlaceModel,
     net_order: Vec<Id>,
+    preferred_layers: HashMap<Id, LayerId>,
+    routing_layers: LayerSet, // Layers wires and vias may be placed on.
+    via_cost: ViaCostModel,
+    layer_direction: HashMap<LayerId, Direction>,
+    heuristic: Heuristic,
+    directions: RouteDirections,
+    turn_penalty: f64,
+    max_ripup_rounds: usize,
+    parallel: bool,
+    deadline: Option<Instant>,
+    cancel: Option<Arc<AtomicBool>>,
+    progress: Option<Progress>,
 }
 
 impl GridRouter {
+    // Creates a router with the grid pitch picked by |derive_resolution|.
     pub fn new(pcb: Pcb, net_order: Vec<Id>) -> Self {
+        let pitch = Self::derive_resolution(&pcb);
+        Self::with_grid(pcb, net_order, pitch)
+    }
+
+    // Creates a router on a grid with spacing |pitch|. A finer pitch finds
+    // routes through tighter gaps, but the number of cells, and so memory and
+    // search time, grows with the inverse square of the pitch.
+    pub fn with_grid(pcb: Pcb, net_order: Vec<Id>, pitch: f64) -> Self {
+        let grid = GridCoord::new(pitch, pcb.origin());
+        let preferred_layers = assign_layers(&pcb, &net_order);
+        let routing_layers = routing_layers(&pcb);
         let place = PlaceModel::new(pcb);
-        Self { resolution: 0.4, place, net_order }
+        Self {
+            grid,
+            place,
+            net_order,
+            preferred_layers,
+            routing_layers,
+            via_cost: ViaCostModel::default(),
+            layer_direction: HashMap::new(),
+            heuristic: Heuristic::default(),
+            directions: RouteDirections::default(),
+            turn_penalty: 0.0,
+            max_ripup_rounds: MAX_RIPUP_ROUNDS,
+            parallel: true,
+            deadline: None,
+            cancel: None,
+            progress: None,
+        }
+    }
+
+    // Stop routing before the next batch of nets once |cancel| is set,
+    // returning whatever was routed so far.
+    pub fn set_cancel(&mut self, cancel: Option<Arc<AtomicBool>>) {
+        self.cancel = cancel;
+    }
+
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().map_or(false, |c| c.load(Ordering::Relaxed))
+    }
+
+    // Number of times to rip up and reroute the nets blocking failed nets.
+    pub fn set_max_ripup_rounds(&mut self, max_ripup_rounds: usize) {
+        self.max_ripup_rounds = max_ripup_rounds;
+    }
+
+    // Whether to route batches of far apart nets on several threads. The
+    // result is the same either way.
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    pub fn set_via_cost(&mut self, via_cost: ViaCostModel) {
+        self.via_cost = via_cost;
+    }
+
+    pub fn set_heuristic(&mut self, heuristic: Heuristic) {
+        self.heuristic = heuristic;
+    }
+
+    // Orthogonal routing only makes horizontal and vertical moves, giving
+    // longer but L-shaped routes.
+    pub fn set_directions(&mut self, directions: RouteDirections) {
+        self.directions = directions;
     }
 
-    fn pin_ref_state(&self, pin_ref: &PinRef) -> Result<State> {
+    // Extra cost, in mm of wire, for each change of direction along a wire,
+    // to get straighter routes. Since states don't record the direction they were entered from,
+    // this steers the search rather than finding the fewest turns exactly.
+    pub fn set_turn_penalty(&mut self, turn_penalty: f64) {
+        self.turn_penalty = turn_penalty;
+    }
+
+    // Makes wires on each layer prefer to run in the given direction. Layers
+    // without an entry have no preference.
+    pub fn set_layer_direction(&mut self, layer_direction: HashMap<LayerId, Direction>) {
+        self.layer_direction = layer_direction;
+    }
+
+    // Stop routing once |deadline| passes, returning whatever was routed so far.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    fn timed_out(&self) -> bool {
+        self.deadline.map_or(false, |d| Instant::now() >= d)
+    }
+
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.grid.pitch
+    }
+
+    // Picks a grid resolution from the board's features. A cell should be able
+    // to hold a track plus its clearance, and there should be at least two
+    // cells between adjacent pin centres so routes can get between pins.
+    #[must_use]
+    pub fn derive_resolution(pcb: &Pcb) -> f64 {
+        let mut res = f64::INFINITY;
+        for net in pcb.nets() {
+            let rs = pcb.net_ruleset(net.id);
+            let clearance = rs.clearances().iter().map(Clearance::amount).fold(0.0, f64::max);
+            res = res.min(2.0 * pcb.net_radius(net.id) + clearance);
+        }
+        for c in pcb.components() {
+            let pts: Vec<_> = c.pins().map(|p| c.pin_pt(p)).collect();
+            for (i, a) in pts.iter().enumerate() {
+                for b in &pts[i + 1..] {
+                    let d = a.dist(*b);
+                    if d > 0.0 {
+                        res = res.min(d / 2.0);
+                    }
+                }
+            }
+        }
+        if res.is_finite() {
+            res.max(MIN_RESOLUTION)
+        } else {
+            DEFAULT_RESOLUTION
+        }
+    }
+
+    // State to start or end routing at |pin_ref|, placed on the pad copper
+    // nearest to |target| rather than at the pad centre.
+    fn pin_ref_state(&self, pin_ref: &PinRef, target: Pt) -> Result<State> {
         let (component, pin) = self.place.pcb().pin_ref(pin_ref)?;
-        let p = self.grid_pt((component.tf() * pin.tf()).pt(Pt::zero()));
-        // TODO: Assumes connect to the center of the pin. Look at padstack instead.
-        let layers = pin.padstack.shapes.iter().map(|v| v.layers).collect();
         let net_id =
             self.place.pcb().pin_ref_net(pin_ref).ok_or_else(|| eyre!("missing net id"))?;
+        // Keep far enough inside the pad that the snapped grid point still is.
+        let inset = self.place.pcb().net_radius(net_id) + self.grid.pitch;
+        let p = self.grid_pt(component.pin_connection_pt(pin, target, inset));
+        // TODO: Assumes every padstack layer is connected at this point.
+        let layers = pin.padstack.shapes.iter().map(|v| v.layers).collect();
         Ok(State { p, layers, net_id })
     }
 
+    // Returns true if |states| lie in different board regions, in which case
+    // they can never be connected.
+    fn spans_regions(&self, regions: &[Rt], states: &[State]) -> bool {
+        let region = |s: &State| {
+            let p = self.world_pt_mid(s.p);
+            regions
+                .iter()
+                .position(|r| p.x >= r.l() && p.x <= r.r() && p.y >= r.b() && p.y <= r.t())
+        };
+        states.iter().map(region).collect::<HashSet<_>>().len() > 1
+    }
+
     fn wire_from_states(&self, states: &[State]) -> Wire {
         let pts: Vec<_> = states.iter().map(|s| self.world_pt_mid(s.p)).collect();
         self.place.create_wire(states[0].net_id, states[0].layers.id().unwrap(), &pts)
@@ -87,16 +372,15 @@ impl GridRouter {
     }
 
     fn grid_pt(&self, p: Pt) -> PtI {
-        // Map points to the lower left corner.
-        pti((p.x / self.resolution).floor() as i64, (p.y / self.resolution).floor() as i64)
+        self.grid.grid_pt(p)
     }
 
     fn world_pt(&self, p: PtI) -> Pt {
-        pt(p.x as f64 * self.resolution, p.y as f64 * self.resolution)
+        self.grid.world_pt(p)
     }
 
     fn world_pt_mid(&self, p: PtI) -> Pt {
-        self.world_pt(p) + pt(self.resolution / 2.0, self.resolution / 2.0)
+        self.grid.world_pt_mid(p)
     }
 
     fn push_path(
@@ -140,13 +424,42 @@ impl GridRouter {
         (wires, vias)
     }
 
-    fn dijkstra(&self, srcs: &[State], dsts: &[State]) -> Vec<State> {
+    // Cost of moving by |dp|, |steps| grid steps long, from |cur| into |next|.
+    // Wire costs are in mm like the heuristic, so it never overestimates.
+    // Wires off the net's preferred layer or against the layer's preferred
+    // direction cost more.
+    fn move_cost(&self, cur: &State, next: &State, dp: PtI, steps: f64) -> f64 {
+        if dp.is_zero() {
+            return match (cur.layers.id(), next.layers.id()) {
+                (Some(a), Some(b)) => self.via_cost.cost(a, b),
+                _ => self.via_cost.via_cost(cur.layers | next.layers),
+            };
+        }
+        let len = steps * self.grid.pitch;
+        let mut cost = match self.preferred_layers.get(&next.net_id) {
+            Some(&layer) if !next.layers.contains(layer) => len * OFF_LAYER_FACTOR,
+            _ => len,
+        };
+        let against = match next.layers.id().and_then(|l| self.layer_direction.get(&l)) {
+            Some(Direction::Horizontal) => dp.y != 0,
+            Some(Direction::Vertical) => dp.x != 0,
+            None => false,
+        };
+        if against {
+            cost *= OFF_DIRECTION_FACTOR;
+        }
+        cost
+    }
+
+    // Finds a path from any of |srcs| to any of |dsts|. If |limit| is set,
+    // the path stays within it.
+    fn dijkstra(&self, srcs: &[State], dsts: &[State], limit: Option<&Rt>) -> Vec<State> {
         let mut q: PriorityQueue<State, OrderedFloat<f64>> = PriorityQueue::new();
         let mut node_data: HashMap<State, NodeData> = HashMap::new();
 
         for src in srcs {
             // Try going from each of the valid layers in this state.
-            for layer in src.layers.iter() {
+            for layer in (src.layers & self.routing_layers).iter() {
                 let s = State { layers: LayerSet::one(layer), ..*src };
                 q.push(s, OrderedFloat(0.0));
                 node_data.insert(s, NodeData { prev: State::default(), cost: 0.0, seen: true });
@@ -154,17 +467,31 @@ impl GridRouter {
         }
 
         let mut dst = None;
+        let mut iters = 0;
         while let Some((cur, _)) = q.pop() {
-            let cur_cost = node_data.get(&cur).unwrap().cost;
+            // Checking the time is relatively expensive, so don't do it every step.
+            iters += 1;
+            if iters % DEADLINE_CHECK_ITERS == 0 && self.timed_out() {
+                break;
+            }
+            let cur_data = node_data.get(&cur).unwrap();
+            let cur_cost = cur_data.cost;
+            // Direction |cur| was entered from, if it was entered by a wire.
+            let cur_dir = (cur_data.prev != State::default() && cur_data.prev.layers == cur.layers)
+                .then(|| cur.p - cur_data.prev.p);
 
-            for (dp, edge_cost) in DIR {
+            for (dp, steps) in DIR {
+                let is_diagonal = dp.x != 0 && dp.y != 0;
+                if is_diagonal && self.directions == RouteDirections::Orthogonal {
+                    continue;
+                }
                 let is_via = dp.is_zero();
                 let cur_layer = cur.layers.id().unwrap(); // Should only be one layer.
                 let layers = if is_via {
                     let mut layers = self.via_from_state(&cur).padstack.layers();
-                    // Try all layers from via except the current one.
+                    // Try all routable layers from via except the current one.
                     layers.remove(cur_layer);
-                    layers
+                    layers & self.routing_layers
                 } else {
                     LayerSet::one(cur_layer)
                 };
@@ -174,7 +501,13 @@ impl GridRouter {
                         layers: LayerSet::one(layer),
                         net_id: srcs[0].net_id,
                     };
-                    let cost = cur_cost + edge_cost;
+                    if limit.map_or(false, |r| !pt_in_rt(self.world_pt_mid(next.p), r)) {
+                        continue;
+                    }
+                    let mut cost = cur_cost + self.move_cost(&cur, &next, dp, steps);
+                    if !is_via && cur_dir.map_or(false, |d| d != dp) {
+                        cost += self.turn_penalty;
+                    }
                     let data = node_data.entry(next).or_insert_with(Default::default);
 
                     if data.seen {
@@ -198,8 +531,13 @@ impl GridRouter {
                         data.prev = cur;
 
                         // A* heuristic. Minimum distance to a destination.
-                        let dist_fn =
-                            |d: &State| self.world_pt_mid(d.p).dist(self.world_pt_mid(next.p));
+                        let dist_fn = |d: &State| {
+                            let (a, b) = (self.world_pt_mid(d.p), self.world_pt_mid(next.p));
+                            match self.heuristic {
+                                Heuristic::Euclidean => a.dist(b),
+                                Heuristic::Manhattan => (a.x - b.x).abs() + (a.y - b.y).abs(),
+                            }
+                        };
                         let heuristic = dsts.iter().map(dist_fn).min_by(f64_cmp).unwrap();
                         q.push(next, OrderedFloat(-(cost + heuristic)));
                     }
@@ -238,24 +576,31 @@ impl GridRouter {
     }
 
     // Connect the given states together and return a route result doing that.
-    fn connect(&mut self, mut srcs: Vec<State>) -> RouteResult {
+    // The wires and vias are marked as obstacles, and their place ids are
+    // added to |ids|.
+    fn connect(
+        &mut self,
+        mut srcs: Vec<State>,
+        limit: Option<&Rt>,
+        ids: &mut Vec<PlaceId>,
+    ) -> RouteResult {
         let mut res = RouteResult::default();
         if srcs.len() <= 1 {
             return res;
         }
         let mut dsts = srcs.split_off(1);
         while !dsts.is_empty() {
-            let path = self.dijkstra(&srcs, &dsts);
+            let path = self.dijkstra(&srcs, &dsts, limit);
             if path.is_empty() {
                 res.failed = true;
                 return res;
             }
             let (wires, vias) = self.create_path(&path);
             for wire in &wires {
-                self.place.add_wire(wire);
+                ids.extend(self.place.add_wire(wire));
             }
             for via in &vias {
-                self.place.add_via(via);
+                ids.extend(self.place.add_via(via));
             }
             res.wires.extend(wires);
             res.vias.extend(vias);
@@ -271,27 +616,180 @@ impl GridRouter {
         res
     }
 
+    fn pin_centres(&self, net_id: Id) -> Result<Vec<Pt>> {
+        let net = self.place.pcb().net(net_id).ok_or_else(|| eyre!("missing net {}", net_id))?;
+        net.pins.iter().map(|p| self.place.pcb().pin_ref(p).map(|(c, pin)| c.pin_pt(pin))).collect()
+    }
+
+    // Routes a single net, marking its wires and vias as obstacles. If
+    // |limit| is set, the route stays within it.
+    fn route_net(&mut self, net_id: Id, regions: &[Rt], limit: Option<&Rt>) -> Result<NetRoute> {
+        let net =
+            self.place.pcb().net(net_id).ok_or_else(|| eyre!("missing net {}", net_id))?.clone();
+        // Approach each pin from the direction of the nearest other pin.
+        let centres = self.pin_centres(net_id)?;
+        let states: Vec<_> = net
+            .pins
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let target = centres
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, &c)| c)
+                    .min_by(|a, b| a.dist(centres[i]).total_cmp(&b.dist(centres[i])))
+                    .unwrap_or(centres[i]);
+                self.pin_ref_state(p, target)
+            })
+            .collect::<Result<_>>()?;
+
+        let mut ids = Vec::new();
+        let res = if self.spans_regions(regions, &states) {
+            RouteResult { failed: true, ..RouteResult::default() }
+        } else {
+            self.connect(states, limit, &mut ids)
+        };
+        let bounds = centres.iter().fold(Rt::empty(), |r, &p| r.united(&Rt::enclosing(p, p)));
+        Ok(NetRoute { res, ids, bounds })
+    }
+
+    // Removes the wires and vias of |net_id| and of the nets crossing its
+    // bounds, routes |net_id| first, then routes the removed nets again. Keeps
+    // the new routes only if fewer nets fail than before.
+    fn rip_up_and_reroute(
+        &mut self,
+        net_id: Id,
+        regions: &[Rt],
+        routed: &mut HashMap<Id, NetRoute>,
+    ) -> Result<()> {
+        let bounds = routed[&net_id].bounds;
+        let mut nets = vec![net_id];
+        nets.extend(self.net_order.iter().copied().filter(|id| {
+            *id != net_id
+                && routed.get(id).map_or(false, |r| {
+                    r.res.wires.iter().any(|w| rt_intersects_rt(&w.shape.shape.bounds(), &bounds))
+                })
+        }));
+        if nets.len() == 1 {
+            return Ok(()); // Nothing to rip up.
+        }
+
+        let old: Vec<NetRoute> = nets.iter().map(|id| routed.remove(id).unwrap()).collect();
+        for route in &old {
+            self.place.remove_shapes(&route.ids);
+        }
+        let mut new = Vec::new();
+        for &id in &nets {
+            new.push(self.route_net(id, regions, None)?);
+        }
+
+        let num_failed = |v: &[NetRoute]| v.iter().filter(|r| r.res.failed).count();
+        let keep = if num_failed(&new) < num_failed(&old) {
+            new
+        } else {
+            for route in &new {
+                self.place.remove_shapes(&route.ids);
+            }
+            old.into_iter().map(|route| self.restore(route)).collect()
+        };
+        routed.extend(nets.into_iter().zip(keep));
+        Ok(())
+    }
+
+    // Marks the wires and vias of a previously ripped up route as obstacles again.
+    fn restore(&mut self, mut route: NetRoute) -> NetRoute {
+        route.ids.clear();
+        for wire in &route.res.wires {
+            route.ids.extend(self.place.add_wire(wire));
+        }
+        for via in &route.res.vias {
+            route.ids.extend(self.place.add_via(via));
+        }
+        route
+    }
+
+    // Splits the net order into runs of nets whose search bounds are far
+    // enough apart that routing one can't change the route of another. Each
+    // net comes with its search bounds.
+    fn batches(&self) -> Result<Vec<Vec<(Id, Rt)>>> {
+        let pcb = self.place.pcb();
+        // Copper of one net can only affect another through the clearance
+        // between them, and both may stick out of their search bounds by a
+        // wire or via, and a grid cell.
+        let clearance = pcb
+            .nets()
+            .flat_map(|n| pcb.net_ruleset(n.id).clearances())
+            .map(Clearance::amount)
+            .fold(0.0, f64::max);
+        let radius = pcb.nets().map(|n| pcb.net_radius(n.id)).fold(0.0, f64::max);
+        let via = pcb
+            .via_padstacks()
+            .iter()
+            .flat_map(|p| &p.shapes)
+            .map(|s| s.shape.bounds())
+            .map(|b| b.w().max(b.h()) / 2.0)
+            .fold(0.0, f64::max);
+        let reach = radius.max(via) + clearance / 2.0 + self.grid.pitch;
+
+        let mut batches: Vec<Vec<(Id, Rt)>> = Vec::new();
+        for &net_id in &self.net_order {
+            let limit = self
+                .pin_centres(net_id)?
+                .into_iter()
+                .fold(Rt::empty(), |r, p| r.united(&Rt::enclosing(p, p)))
+                .inset(-SEARCH_MARGIN, -SEARCH_MARGIN);
+            let grown = limit.inset(-reach, -reach);
+            match batches.last_mut() {
+                Some(batch)
+                    if batch
+                        .iter()
+                        .all(|(_, r)| !rt_intersects_rt(&r.inset(-reach, -reach), &grown)) =>
+                {
+                    batch.push((net_id, limit));
+                }
+                _ => batches.push(vec![(net_id, limit)]),
+            }
+        }
+        Ok(batches)
+    }
+
+    // Routes each net of |batch| within its search bounds, marking the wires
+    // and vias as obstacles. Nets in a batch don't affect each other, so
+    // routing them on copies of the router gives the same routes as routing
+    // them one after the other.
+    fn route_batch(&mut self, batch: &[(Id, Rt)], regions: &[Rt]) -> Result<Vec<NetRoute>> {
+        if !self.parallel || batch.len() == 1 {
+            return batch.iter().map(|(id, r)| self.route_net(*id, regions, Some(r))).collect();
+        }
+        let this = &*self;
+        let routes = batch
+            .par_iter()
+            .map_init(|| this.clone(), |router, (id, r)| router.route_net(*id, regions, Some(r)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(routes.into_iter().map(|route| self.restore(route)).collect())
+    }
+
     fn _draw_debug(&mut self, res: &mut RouteResult) {
         let bounds = self.place.pcb().bounds();
         // let bounds = rt(77.0495, -125.1745, 79.099, -120.75);
-        let bounds =
-            RtI::enclosing(self.grid_pt(bounds.bl()), self.grid_pt(bounds.tr()) + pti(1, 1));
-        for l in bounds.l()..bounds.r() {
-            for b in bounds.b()..bounds.t() {
-                let p = pti(l, b);
-                let shape = circ(self.world_pt_mid(p), self.resolution / 2.0).shape();
-                let shape = LayerShape { layers: LayerSet::one(0), shape };
-                if self.place.is_shape_blocked(
-                    &Tf::identity(),
-                    &shape,
-                    TagQuery::All,
-                    ObjectKind::Wire,
-                    &[],
-                ) {
-                    continue;
-                }
-                res.wires.push(Wire { shape, net_id: NO_ID });
+        let bounds = Rt::enclosing(
+            self.world_pt(self.grid_pt(bounds.bl())),
+            self.world_pt(self.grid_pt(bounds.tr()) + pti(1, 1)),
+        );
+        for cell in grid_cells(&bounds, self.grid.pitch) {
+            let shape = circ(cell.center(), self.grid.pitch / 2.0).shape();
+            let shape = LayerShape { layers: LayerSet::one(0), shape };
+            if self.place.is_shape_blocked(
+                &Tf::identity(),
+                &shape,
+                NetQuery::All,
+                ObjectKind::Wire,
+                &[],
+            ) {
+                continue;
             }
+            res.wires.push(Wire { shape, net_id: NO_ID });
         }
 
         let bounds = RtI::new(157, -116, 1, 1);
@@ -306,28 +804,463 @@ impl GridRouter {
 impl RouteStrategy for GridRouter {
     fn route(&mut self) -> Result<RouteResult> {
         let mut res = RouteResult::default();
-        for net_id in self.net_order.clone() {
-            let net = self
-                .place
-                .pcb()
-                .net(net_id)
-                .ok_or_else(|| eyre!("missing net {}", net_id))?
-                .clone();
-            let states = net.pins.iter().map(|p| self.pin_ref_state(p)).collect::<Result<_>>()?;
-
-            let sub_result = self.connect(states);
-            println!("done {}, failed {}", self.place.pcb().to_name(net_id), sub_result.failed);
-            // Mark wires and vias.
-            for wire in &sub_result.wires {
-                self.place.add_wire(wire);
+        let regions = self.place.pcb().boundary_regions();
+        let mut routed: HashMap<Id, NetRoute> = HashMap::new();
+        let total = self.net_order.len();
+        let mut done = 0;
+        for batch in self.batches()? {
+            if self.timed_out() {
+                res.failed = true;
+                res.timed_out = true;
+                break;
+            }
+            // Nets not reached yet are unrouted, so a cancelled route failed.
+            if self.cancelled() {
+                res.failed = true;
+                res.cancelled = true;
+                break;
+            }
+            let routes = self.route_batch(&batch, &regions)?;
+            for ((net_id, _), mut route) in batch.into_iter().zip(routes) {
+                // Nets that don't fit in their search bounds get another go
+                // without them, once the rest of the batch is in place.
+                if route.res.failed {
+                    self.place.remove_shapes(&route.ids);
+                    route = self.route_net(net_id, &regions, None)?;
+                }
+                println!("done {}, failed {}", self.place.pcb().to_name(net_id), route.res.failed);
+                routed.insert(net_id, route);
+                done += 1;
+                if let Some(progress) = &self.progress {
+                    progress.report(done, total);
+                }
+            }
+        }
+
+        for _ in 0..self.max_ripup_rounds {
+            if self.timed_out() || self.cancelled() {
+                break;
             }
-            for via in &sub_result.vias {
-                self.place.add_via(via);
+            let failed: Vec<Id> = self
+                .net_order
+                .iter()
+                .copied()
+                .filter(|id| routed.get(id).map_or(false, |r| r.res.failed))
+                .collect();
+            if failed.is_empty() {
+                break;
+            }
+            res.ripup_rounds += 1;
+            for net_id in failed {
+                self.rip_up_and_reroute(net_id, &regions, &mut routed)?;
             }
-            res.merge(sub_result);
+        }
+
+        res.num_nets = self.net_order.len();
+        res.failed_nets = self
+            .net_order
+            .iter()
+            .copied()
+            .filter(|id| routed.get(id).map_or(true, |r| r.res.failed))
+            .collect();
+        for net_id in &self.net_order {
+            if let Some(route) = routed.remove(net_id) {
+                res.merge(route.res);
+            }
+        }
+        // The deadline may have cut the search for the last net short.
+        if self.timed_out() {
+            res.failed = true;
+            res.timed_out = true;
         }
 
         // self.draw_debug(&mut res);
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::SQRT_2;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{pt, pti, rt, ShapeOps};
+
+    use super::{
+        grid_cells, grid_points, GridCoord, GridRouter, RouteDirections, State, DEFAULT_RESOLUTION,
+        DIR, MIN_RESOLUTION, OFF_DIRECTION_FACTOR,
+    };
+    use crate::dsn::pcb_to_session::PcbToSession;
+    use crate::geom::math::{f64_eq, f64_le, pt_eq};
+    use crate::model::pcb::{Keepout, KeepoutType, LayerKind, LayerSet, LayerShape, Pcb};
+    use crate::name::Id;
+    use crate::route::layers::{Direction, ViaCostModel};
+    use crate::route::router::{apply_route_result, Progress, RouteResult, RouteStrategy};
+    use crate::testing::{
+        add_component, add_net, add_smd_component, board, CLEARANCE, PAD_R, RADIUS,
+    };
+
+    #[test]
+    fn grid_cells_cover_region() {
+        let cells: Vec<_> = grid_cells(&rt(1.0, 2.0, 4.0, 4.0), 1.0).collect();
+        assert_eq!(cells.len(), 6);
+        assert!(pt_eq(cells[0].bl(), pt(1.0, 2.0)));
+        assert!(pt_eq(cells[0].tr(), pt(2.0, 3.0)));
+        assert!(pt_eq(cells[5].bl(), pt(3.0, 3.0)));
+        assert!(pt_eq(cells[5].tr(), pt(4.0, 4.0)));
+    }
+
+    #[test]
+    fn grid_cells_clip_partial_edges() {
+        let cells: Vec<_> = grid_cells(&rt(0.0, 0.0, 2.5, 1.0), 1.0).collect();
+        assert_eq!(cells.len(), 3);
+        assert!(f64_eq(cells[2].w(), 0.5));
+        assert!(pt_eq(cells[2].tr(), pt(2.5, 1.0)));
+    }
+
+    #[test]
+    fn grid_points_include_far_edges() {
+        let pts: Vec<_> = grid_points(&rt(0.0, 0.0, 3.0, 2.0), 1.0).collect();
+        assert_eq!(pts.len(), 12);
+        assert!(pt_eq(pts[0], pt(0.0, 0.0)));
+        assert!(pt_eq(pts[11], pt(3.0, 2.0)));
+
+        let pts: Vec<_> = grid_points(&rt(0.0, 0.0, 2.5, 0.0), 1.0).collect();
+        assert_eq!(pts.len(), 4);
+        assert!(pt_eq(pts[3], pt(2.5, 0.0)));
+    }
+
+    #[test]
+    fn grid_coord_maps_cells() {
+        let g = GridCoord::new(0.5, pt(1.0, 1.0));
+        assert_eq!(g.grid_pt(pt(1.6, 2.0)), pti(1, 2));
+        assert!(pt_eq(g.world_pt(pti(1, 2)), pt(1.5, 2.0)));
+        assert!(pt_eq(g.world_pt_mid(pti(1, 2)), pt(1.75, 2.25)));
+    }
+
+    #[test]
+    fn cancel_stops_after_current_net() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 3.0), &[pt(0.0, 0.0), pt(0.0, 4.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 3.0), &[pt(0.0, 0.0), pt(0.0, 4.0)]);
+        let n1 = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let n2 = add_net(&mut pcb, "N2", &[("U1", "2"), ("U2", "2")]);
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut router = GridRouter::new(pcb, vec![n1, n2]);
+        router.set_cancel(Some(Arc::clone(&cancel)));
+        let flag = Arc::clone(&cancel);
+        router.set_progress(Some(Progress(Arc::new(move |done, _| {
+            if done == 1 {
+                flag.store(true, Ordering::Relaxed);
+            }
+        }))));
+        let res = router.route().unwrap();
+
+        assert!(res.cancelled);
+        assert!(res.failed);
+        assert_eq!(res.num_nets, 2);
+        // N1 was routed before the flag was seen; N2 was never attempted.
+        assert_eq!(res.failed_nets, vec![n2]);
+        assert!(!res.wires.is_empty());
+        assert!(res.wires.iter().all(|w| w.net_id == n1));
+    }
+
+    // Six short nets far apart from each other, then a long one passing near
+    // all of them.
+    fn spread_nets() -> (Pcb, Vec<Id>) {
+        let mut pcb = board(60.0, 50.0);
+        let mut order = Vec::new();
+        for (i, j) in [0, 1, 2].into_iter().flat_map(|i| [(i, 0), (i, 1)]) {
+            let name = format!("U{i}{j}");
+            let p = pt(5.0 + 20.0 * f64::from(i), 5.0 + 30.0 * f64::from(j));
+            add_component(&mut pcb, &name, p, &[pt(0.0, 0.0), pt(4.0, 4.0)]);
+            order.push(add_net(&mut pcb, &format!("N{i}{j}"), &[(&name, "1"), (&name, "2")]));
+        }
+        add_component(&mut pcb, "J1", pt(3.0, 18.0), &[pt(0.0, 0.0), pt(50.0, 0.0)]);
+        order.push(add_net(&mut pcb, "LONG", &[("J1", "1"), ("J1", "2")]));
+        (pcb, order)
+    }
+
+    #[test]
+    fn far_apart_nets_share_a_batch() {
+        let (pcb, order) = spread_nets();
+        let router = GridRouter::new(pcb, order.clone());
+        let batches = router.batches().unwrap();
+        let ids: Vec<Vec<Id>> =
+            batches.iter().map(|b| b.iter().map(|(id, _)| *id).collect()).collect();
+        assert_eq!(ids, vec![order[..6].to_vec(), vec![order[6]]]);
+    }
+
+    #[test]
+    fn parallel_route_matches_serial() {
+        let (pcb, order) = spread_nets();
+        let ses = |parallel: bool| {
+            let mut router = GridRouter::new(pcb.clone(), order.clone());
+            router.set_parallel(parallel);
+            let res = router.route().unwrap();
+            assert!(!res.failed, "{:?}", res.failed_nets);
+            let mut pcb = pcb.clone();
+            apply_route_result(&mut pcb, &res);
+            PcbToSession::new(pcb).convert().unwrap()
+        };
+        assert_eq!(ses(false), ses(true));
+    }
+
+    // Routes a net from a pad at (2, 5) on |from| to a pad at (8, 5) on
+    // |to|, with a keepout of |kind| on |layers| across the middle of the
+    // board.
+    fn route_past_keepout(
+        from: LayerSet,
+        to: LayerSet,
+        kind: KeepoutType,
+        layers: LayerSet,
+    ) -> RouteResult {
+        let mut pcb = board(10.0, 10.0);
+        add_smd_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)], from);
+        add_smd_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)], to);
+        let net_id = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let shape = LayerShape { layers, shape: rt(4.0, 0.0, 6.0, 10.0).shape() };
+        pcb.add_keepout(Keepout { kind, shape });
+        GridRouter::new(pcb, vec![net_id]).route().unwrap()
+    }
+
+    fn xs(w: &LayerShape) -> Vec<f64> {
+        let Shape::Path(p) = &w.shape else { panic!("expected a path") };
+        p.pts().iter().map(|p| p.x).collect()
+    }
+
+    #[test]
+    fn via_keepout_blocks_vias_only() {
+        let (f, b) = (LayerSet::one(0), LayerSet::one(1));
+        let all = LayerSet::one(0) | LayerSet::one(1);
+        // The pads are on different layers, so the route needs a via, but the
+        // wire can still go through the keepout.
+        let res = route_past_keepout(f, b, KeepoutType::ViaKeepout, all);
+        assert!(!res.failed);
+        assert!(!res.vias.is_empty());
+        for v in &res.vias {
+            assert!(v.p.x + PAD_R <= 4.0 || v.p.x - PAD_R >= 6.0, "via at {:?}", v.p);
+        }
+        assert!(res.wires.iter().flat_map(|w| xs(&w.shape)).any(|x| x > 4.0 && x < 6.0));
+    }
+
+    #[test]
+    fn wire_keepout_blocks_wires_only() {
+        let f = LayerSet::one(0);
+        // Wires can't cross on F.Cu, so the route dives under the keepout on
+        // B.Cu. Vias may sit in it.
+        let res = route_past_keepout(f, f, KeepoutType::WireKeepout, f);
+        assert!(!res.failed);
+        assert!(res.vias.len() >= 2);
+        for w in res.wires.iter().filter(|w| w.shape.layers == f) {
+            assert!(xs(&w.shape).iter().all(|&x| x < 4.0 || x > 6.0), "{w:?}");
+        }
+        // A plain keepout on every layer blocks both.
+        let all = LayerSet::one(0) | LayerSet::one(1);
+        assert!(route_past_keepout(f, f, KeepoutType::Keepout, all).failed);
+    }
+
+    #[test]
+    fn costly_vias_are_avoided() {
+        // The pads are diagonally apart on F.Cu. Running across on F.Cu and up
+        // on B.Cu keeps both layers to their direction, for the price of two
+        // vias.
+        let route = |cost: f64| {
+            let mut pcb = board(10.0, 10.0);
+            let f = LayerSet::one(0);
+            add_smd_component(&mut pcb, "U1", pt(2.0, 2.0), &[pt(0.0, 0.0)], f);
+            add_smd_component(&mut pcb, "U2", pt(8.0, 8.0), &[pt(0.0, 0.0)], f);
+            let net_id = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+            let mut router = GridRouter::new(pcb, vec![net_id]);
+            router
+                .set_layer_direction([(0, Direction::Horizontal), (1, Direction::Vertical)].into());
+            let mut via_cost = ViaCostModel::default();
+            via_cost.set(0, 1, cost);
+            router.set_via_cost(via_cost);
+            let res = router.route().unwrap();
+            assert!(!res.failed);
+            res.vias.len()
+        };
+        assert!(route(0.1) >= 2);
+        assert_eq!(route(1000.0), 0);
+    }
+
+    #[test]
+    fn ripup_frees_blocked_net() {
+        // B's pad sits in a pocket open to the left, and A's shortest route
+        // runs straight across the mouth of it. With no vias A has to go the
+        // long way round the pocket for B to get out.
+        let mut pcb = board(10.0, 10.0);
+        let f = LayerSet::one(0);
+        let all = LayerSet::one(0) | LayerSet::one(1);
+        for r in [rt(4.0, 3.0, 7.0, 3.5), rt(4.0, 6.5, 7.0, 7.0), rt(6.5, 3.0, 7.0, 7.0)] {
+            let shape = LayerShape { layers: all, shape: r.shape() };
+            pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+        }
+        let shape = LayerShape { layers: all, shape: rt(0.0, 0.0, 10.0, 10.0).shape() };
+        pcb.add_keepout(Keepout { kind: KeepoutType::ViaKeepout, shape });
+        add_smd_component(&mut pcb, "U1", pt(3.0, 0.5), &[pt(0.0, 0.0), pt(0.0, 9.0)], f);
+        add_smd_component(&mut pcb, "U2", pt(1.0, 5.0), &[pt(0.0, 0.0), pt(4.0, 0.0)], f);
+        let a = add_net(&mut pcb, "A", &[("U1", "1"), ("U1", "2")]);
+        let b = add_net(&mut pcb, "B", &[("U2", "1"), ("U2", "2")]);
+
+        let mut router = GridRouter::new(pcb.clone(), vec![a, b]);
+        router.set_max_ripup_rounds(0);
+        let res = router.route().unwrap();
+        assert!(res.failed);
+        assert_eq!(res.failed_nets, vec![b]);
+
+        let mut router = GridRouter::new(pcb.clone(), vec![a, b]);
+        let res = router.route().unwrap();
+        assert!(!res.failed);
+        assert!(res.ripup_rounds > 0);
+        assert!(res.vias.is_empty());
+        assert!(res.verify_connectivity(&pcb).is_empty());
+    }
+
+    // Pads either side of a wall across the board, with a gap 0.5 mm wide
+    // centred on y = 5.125. That leaves 0.05 mm either side of a 0.2 mm wide
+    // wire with its clearance, so the wire has to run almost exactly down the
+    // middle of the gap.
+    fn walled_board(wall: bool) -> (Pcb, Id) {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)]);
+        let net_id = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        if wall {
+            let layers = pcb.layers_by_kind(LayerKind::All);
+            for (b, t) in [(0.0, 4.875), (5.375, 10.0)] {
+                let shape = LayerShape { layers, shape: rt(4.0, b, 6.0, t).shape() };
+                pcb.add_keepout(Keepout { kind: KeepoutType::Keepout, shape });
+            }
+        }
+        (pcb, net_id)
+    }
+
+    #[test]
+    fn derive_resolution_from_tracks_and_pitch() {
+        let resolution = |pitch: f64| {
+            let mut pcb = board(10.0, 10.0);
+            add_component(&mut pcb, "U1", pt(5.0, 5.0), &[pt(0.0, 0.0), pt(pitch, 0.0)]);
+            add_net(&mut pcb, "N1", &[("U1", "1")]);
+            GridRouter::derive_resolution(&pcb)
+        };
+        assert!(f64_eq(GridRouter::derive_resolution(&board(10.0, 10.0)), DEFAULT_RESOLUTION));
+        // Widely spaced pins leave the track and its clearance to decide.
+        assert!(f64_eq(resolution(2.54), 2.0 * RADIUS + CLEARANCE));
+        // Fine pitch parts get two cells between pin centres, down to the
+        // smallest grid allowed.
+        assert!(f64_eq(resolution(0.5), 0.25));
+        assert!(f64_eq(resolution(0.06), MIN_RESOLUTION));
+    }
+
+    #[test]
+    fn fine_pitch_fits_through_narrow_gap() {
+        let route = |wall: bool, pitch: f64| {
+            let (pcb, net_id) = walled_board(wall);
+            let mut router = GridRouter::with_grid(pcb, vec![net_id], pitch);
+            assert!(f64_eq(router.resolution(), pitch));
+            router.route().unwrap()
+        };
+        // Grid lines at a 1 mm pitch run 0.375 mm off the middle of the gap,
+        // so the coarse grid only fails because of it.
+        assert!(!route(false, 1.0).failed);
+        assert!(route(true, 1.0).failed);
+        let res = route(true, 0.25);
+        assert!(!res.failed);
+        assert!(res.vias.is_empty());
+        let (pcb, _) = walled_board(true);
+        assert!(res.verify_connectivity(&pcb).is_empty());
+    }
+
+    #[test]
+    fn move_costs_are_in_mm() {
+        let (pcb, net_id) = walled_board(false);
+        let router = GridRouter::with_grid(pcb, vec![net_id], 0.5);
+        // On the net's preferred layer, so wires there cost just their length.
+        let layer = router.preferred_layers.get(&net_id).copied().unwrap_or(0);
+        let cur = State { p: pti(4, 4), layers: LayerSet::one(layer), net_id };
+        for (dp, steps) in DIR.into_iter().filter(|(dp, _)| !dp.is_zero()) {
+            let next = State { p: cur.p + dp, ..cur };
+            let cost = router.move_cost(&cur, &next, dp, steps);
+            let dist = router.world_pt_mid(cur.p).dist(router.world_pt_mid(next.p));
+            // Straight moves cost the pitch and diagonal ones sqrt(2) times
+            // it, the same as the distance the heuristic measures.
+            assert!(f64_eq(cost, dist), "{dp:?}: {cost} vs {dist}");
+            let want = if dp.x != 0 && dp.y != 0 { SQRT_2 * 0.5 } else { 0.5 };
+            assert!(f64_eq(cost, want), "{dp:?}: {cost}");
+        }
+    }
+
+    #[test]
+    fn layer_direction_penalises_moves_against_it() {
+        let (pcb, net_id) = walled_board(false);
+        let mut router = GridRouter::with_grid(pcb, vec![net_id], 0.5);
+        let layer = router.preferred_layers.get(&net_id).copied().unwrap_or(0);
+        let cur = State { p: pti(4, 4), layers: LayerSet::one(layer), net_id };
+        let cost = |router: &GridRouter, dp| {
+            let steps = if dp == pti(1, 1) { SQRT_2 } else { 1.0 };
+            router.move_cost(&cur, &State { p: cur.p + dp, ..cur }, dp, steps)
+        };
+        for (direction, along, across) in [
+            (Direction::Horizontal, pti(1, 0), pti(0, 1)),
+            (Direction::Vertical, pti(0, 1), pti(1, 0)),
+        ] {
+            router.set_layer_direction([(layer, direction)].into());
+            assert!(f64_eq(cost(&router, along), 0.5), "{direction:?}");
+            assert!(f64_eq(cost(&router, across), 0.5 * OFF_DIRECTION_FACTOR), "{direction:?}");
+            // Diagonals go against either direction.
+            let diagonal = SQRT_2 * 0.5 * OFF_DIRECTION_FACTOR;
+            assert!(f64_eq(cost(&router, pti(1, 1)), diagonal), "{direction:?}");
+        }
+    }
+
+    fn wire_len(res: &RouteResult) -> f64 {
+        let len = |w: &LayerShape| {
+            let Shape::Path(p) = &w.shape else { panic!("expected a path") };
+            p.pts().windows(2).map(|v| v[0].dist(v[1])).sum::<f64>()
+        };
+        res.wires.iter().map(|w| len(&w.shape)).sum()
+    }
+
+    #[test]
+    fn octilinear_routes_diagonally() {
+        let mut pcb = board(10.0, 10.0);
+        let f = LayerSet::one(0);
+        add_smd_component(&mut pcb, "U1", pt(2.125, 2.125), &[pt(0.0, 0.0)], f);
+        add_smd_component(&mut pcb, "U2", pt(7.125, 7.125), &[pt(0.0, 0.0)], f);
+        let net_id = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let route = |directions| {
+            let mut router = GridRouter::with_grid(pcb.clone(), vec![net_id], 0.25);
+            router.set_directions(directions);
+            let res = router.route().unwrap();
+            assert!(!res.failed);
+            assert!(res.verify_connectivity(&pcb).is_empty());
+            res
+        };
+        let diagonal = 5.0 * SQRT_2;
+        // A single 45 degree run between the pads.
+        let res = route(RouteDirections::Octilinear);
+        assert!(f64_le(wire_len(&res), diagonal), "{}", wire_len(&res));
+        for w in &res.wires {
+            let Shape::Path(p) = &w.shape.shape else { panic!("expected a path") };
+            for v in p.pts().windows(2) {
+                let d = v[1] - v[0];
+                assert!(f64_eq(d.x.abs(), d.y.abs()), "{v:?} isn't at 45 degrees");
+            }
+        }
+        // Only horizontal and vertical runs, so at least as long as an L.
+        let res = route(RouteDirections::Orthogonal);
+        assert!(f64_le(10.0 - 1.0, wire_len(&res)), "{}", wire_len(&res));
+        for w in &res.wires {
+            let Shape::Path(p) = &w.shape.shape else { panic!("expected a path") };
+            for v in p.pts().windows(2) {
+                let d = v[1] - v[0];
+                assert!(f64_eq(d.x, 0.0) || f64_eq(d.y, 0.0), "{v:?} is diagonal");
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::rect::Rt;
+
+use crate::model::pcb::{LayerId, LayerKind, LayerSet, Pcb};
+use crate::name::Id;
+
+// Cost of a via between two layers when no other cost is set, in mm of wire.
+pub const DEFAULT_VIA_COST: f64 = 10.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+// Layers the router may place wires on. Boards which only declare planes
+// are routed on all layers.
+pub fn routing_layers(pcb: &Pcb) -> LayerSet {
+    let signal = pcb.signal_layers();
+    if signal.is_empty() {
+        pcb.layers_by_kind(LayerKind::All)
+    } else {
+        signal
+    }
+}
+
+// Assigns a preferred routing layer to each net in |net_order|. Nets whose
+// pins only share a single routing layer prefer that layer. Otherwise, mostly
+// horizontal nets prefer the first routing layer and mostly vertical nets the
+// second, so that crossing nets tend to end up on different layers.
+#[must_use]
+pub fn assign_layers(pcb: &Pcb, net_order: &[Id]) -> HashMap<Id, LayerId> {
+    let routable = routing_layers(pcb);
+    let mut layers = routable.iter();
+    let (Some(horizontal), vertical) = (layers.next(), layers.next()) else {
+        return HashMap::new();
+    };
+    let vertical = vertical.unwrap_or(horizontal);
+
+    let mut assignment = HashMap::new();
+    for &net_id in net_order {
+        let Some(net) = pcb.net(net_id) else { continue };
+        let mut common = routable;
+        let mut pts: Vec<Pt> = Vec::new();
+        for p in &net.pins {
+            if let Ok((component, pin)) = pcb.pin_ref(p) {
+                common &= pin.padstack.layers();
+                pts.push(component.pin_pt(pin));
+            }
+        }
+        let layer = if let Some(layer) = common.id() {
+            layer
+        } else if pts.len() >= 2 {
+            let bounds = pts.iter().fold(Rt::empty(), |r, &p| r.united(&Rt::enclosing(p, p)));
+            if bounds.w() >= bounds.h() {
+                horizontal
+            } else {
+                vertical
+            }
+        } else {
+            continue;
+        };
+        assignment.insert(net_id, layer);
+    }
+    assignment
+}
+
+// Alternates horizontal and vertical preferred directions over the routing
+// layers, starting with horizontal.
+#[must_use]
+pub fn alternate_directions(pcb: &Pcb) -> HashMap<LayerId, Direction> {
+    routing_layers(pcb)
+        .iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            (layer, if i % 2 == 0 { Direction::Horizontal } else { Direction::Vertical })
+        })
+        .collect()
+}
+
+// Cost of a via for each pair of layers it connects, e.g. to make adjacent
+// layer transitions cheaper than ones through the whole board. Pairs without
+// a cost use |default|.
+#[must_use]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViaCostModel {
+    default: f64,
+    costs: HashMap<(LayerId, LayerId), f64>,
+}
+
+impl Default for ViaCostModel {
+    fn default() -> Self {
+        Self::uniform(DEFAULT_VIA_COST)
+    }
+}
+
+impl ViaCostModel {
+    pub fn uniform(cost: f64) -> Self {
+        Self { default: cost, costs: HashMap::new() }
+    }
+
+    // Sets the cost of going between layers |a| and |b|, in either direction.
+    pub fn set(&mut self, a: LayerId, b: LayerId, cost: f64) {
+        self.costs.insert((a.min(b), a.max(b)), cost);
+    }
+
+    #[must_use]
+    pub fn cost(&self, a: LayerId, b: LayerId) -> f64 {
+        self.costs.get(&(a.min(b), a.max(b))).copied().unwrap_or(self.default)
+    }
+
+    // Cost of a via spanning |layers|, taken as going from its first to its
+    // last layer.
+    #[must_use]
+    pub fn via_cost(&self, layers: LayerSet) -> f64 {
+        match (layers.first(), layers.iter().last()) {
+            (Some(a), Some(b)) => self.cost(a, b),
+            _ => self.default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::pt;
+
+    use super::assign_layers;
+    use crate::model::pcb::LayerSet;
+    use crate::testing::{add_component, add_net, add_smd_component, board};
+
+    #[test]
+    fn crossing_nets_get_different_layers() {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(1.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(9.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U3", pt(5.0, 1.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U4", pt(5.0, 9.0), &[pt(0.0, 0.0)]);
+        let across = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        let down = add_net(&mut pcb, "N2", &[("U3", "1"), ("U4", "1")]);
+        // Mostly horizontal, but its pads are only on B.Cu.
+        let b = LayerSet::one(1);
+        add_smd_component(&mut pcb, "U5", pt(2.0, 2.0), &[pt(0.0, 0.0)], b);
+        add_smd_component(&mut pcb, "U6", pt(8.0, 3.0), &[pt(0.0, 0.0)], b);
+        let smd = add_net(&mut pcb, "N3", &[("U5", "1"), ("U6", "1")]);
+
+        let layers = assign_layers(&pcb, &[across, down, smd]);
+        assert_eq!(layers[&across], 0);
+        assert_eq!(layers[&down], 1);
+        assert_eq!(layers[&smd], 1);
+    }
+}
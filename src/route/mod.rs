@@ -1,3 +1,10 @@
+pub mod astar;
+pub mod channel;
+pub mod diff_pair;
+pub mod fanout;
+pub mod fill;
 pub mod grid;
+pub mod layers;
 pub mod place_model;
+pub mod ratsnest;
 pub mod router;
@@ -1,22 +1,89 @@
 use std::collections::HashMap;
 
+use enumset::EnumSet;
 use eyre::Result;
-use memegeom::geom::math::le;
-use memegeom::geom::qt::quadtree::ShapeIdx;
-use memegeom::geom::qt::query::{Kinds, KindsQuery, Query, ShapeInfo, Tag, TagQuery, NO_TAG};
-use memegeom::primitive::compound::Compound;
 use memegeom::primitive::point::Pt;
 use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
 use memegeom::primitive::{path, ShapeOps};
 use memegeom::tf::Tf;
 
+use crate::geom::contains::shape_in_shape;
+use crate::geom::distance::shape_dist;
+use crate::geom::intersects::shape_intersects_shape;
+use crate::geom::math::f64_le;
+use crate::geom::quadtree::Quadtree;
+use crate::geom::transform::shape_tf;
 use crate::model::pcb::{
-    Clearance, LayerId, LayerSet, LayerShape, Net, ObjectKind, Padstack, Pcb, Pin, PinRef, Via,
-    Wire,
+    Clearance, Keepout, KeepoutType, LayerId, LayerSet, LayerShape, Net, ObjectKind, Padstack, Pcb,
+    Pin, PinRef, Via, Wire,
 };
 use crate::name::Id;
 
-pub type PlaceId = (LayerId, ShapeIdx);
+pub type PlaceId = (LayerId, usize);
+
+// Which nets' shapes a query looks at. Shapes not on any net, like keepouts
+// and unconnected pins, are always looked at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NetQuery {
+    All,
+    Except(Id),
+}
+
+impl NetQuery {
+    fn matches(self, net_id: Option<Id>) -> bool {
+        match self {
+            NetQuery::All => true,
+            NetQuery::Except(id) => net_id != Some(id),
+        }
+    }
+}
+
+// Shapes on one layer, with the net and kind of object each came from.
+#[derive(Debug, Clone)]
+struct LayerShapes {
+    qt: Quadtree,
+    info: Vec<(Option<Id>, ObjectKind)>,
+}
+
+impl LayerShapes {
+    fn new(bounds: Rt) -> Self {
+        Self { qt: Quadtree::new(bounds), info: Vec::new() }
+    }
+
+    fn add(&mut self, s: Shape, net_id: Option<Id>, kind: ObjectKind) -> usize {
+        self.info.push((net_id, kind));
+        self.qt.insert(s)
+    }
+
+    // Shapes selected by |q| and |kinds| whose bounds are within |dist| of
+    // |s|.
+    fn near<'a>(
+        &'a self,
+        s: &Shape,
+        dist: f64,
+        q: NetQuery,
+        kinds: EnumSet<ObjectKind>,
+    ) -> impl Iterator<Item = &'a Shape> + 'a {
+        self.qt.candidates(s, dist).into_iter().filter_map(move |idx| {
+            let (net_id, kind) = self.info[idx];
+            (q.matches(net_id) && kinds.contains(kind)).then(|| self.qt.shape(idx))
+        })
+    }
+
+    fn intersects(&self, s: &Shape, q: NetQuery) -> bool {
+        self.near(s, 0.0, q, EnumSet::all()).any(|v| shape_intersects_shape(v, s))
+    }
+
+    fn within(&self, s: &Shape, dist: f64, q: NetQuery, kinds: EnumSet<ObjectKind>) -> bool {
+        self.near(s, dist, q, kinds).any(|v| f64_le(shape_dist(v, s), dist))
+    }
+
+    // Whether one of the shapes holds all of |s|.
+    fn contains(&self, s: &Shape) -> bool {
+        self.near(s, 0.0, NetQuery::All, EnumSet::all()).any(|v| shape_in_shape(s, v))
+    }
+}
 
 // Need to handle:
 // but also keeping them for hole drils
@@ -25,8 +92,11 @@ pub type PlaceId = (LayerId, ShapeIdx);
 pub struct PlaceModel {
     pcb: Pcb,
     // TODO: Can move layerids to quadtree?
-    boundary: HashMap<LayerId, Compound>,
-    blocked: HashMap<LayerId, Compound>,
+    boundary: HashMap<LayerId, LayerShapes>,
+    blocked: HashMap<LayerId, LayerShapes>,
+    // Keepouts that only block wires or only block vias.
+    wire_keepouts: HashMap<LayerId, LayerShapes>,
+    via_keepouts: HashMap<LayerId, LayerShapes>,
     pins: HashMap<PinRef, Vec<PlaceId>>, // Record which pins correspond to which place ids in |blocked|.
     bounds: Rt,
 }
@@ -37,6 +107,8 @@ impl PlaceModel {
             pcb: Pcb::default(), // Initially set as empty since we will initialise.
             boundary: HashMap::new(),
             blocked: HashMap::new(),
+            wire_keepouts: HashMap::new(),
+            via_keepouts: HashMap::new(),
             pins: HashMap::new(),
             bounds: Rt::empty(),
         };
@@ -46,7 +118,7 @@ impl PlaceModel {
 
     pub fn debug_rts(&self) -> Vec<Rt> {
         // 0 = F.Cu, 1 = B.Cu
-        self.blocked.get(&1).unwrap().quadtree().rts()
+        self.blocked.get(&1).unwrap().qt.rts()
     }
 
     pub fn pcb(&self) -> &Pcb {
@@ -55,9 +127,8 @@ impl PlaceModel {
 
     // Creates a wire for a given net, but doesn't add it.
     pub fn create_wire(&self, net_id: Id, layer: LayerId, pts: &[Pt]) -> Wire {
-        let rs = self.pcb.net_ruleset(net_id);
-        let shape =
-            LayerShape { layers: LayerSet::one(layer), shape: path(pts, rs.radius()).shape() };
+        let r = self.pcb.net_radius(net_id);
+        let shape = LayerShape { layers: LayerSet::one(layer), shape: path(pts, r).shape() };
         Wire { shape, net_id }
     }
 
@@ -67,8 +138,8 @@ impl PlaceModel {
             &mut self.blocked,
             &Tf::identity(),
             &wire.shape,
-            Tag(wire.net_id),
-            ObjectKind::Wire.query(),
+            Some(wire.net_id),
+            ObjectKind::Wire,
         )
     }
 
@@ -79,14 +150,14 @@ impl PlaceModel {
     }
 
     pub fn add_via(&mut self, via: &Via) -> Vec<PlaceId> {
-        self.add_padstack(&via.tf(), &via.padstack, Tag(via.net_id), ObjectKind::Via.query())
+        self.add_padstack(&via.tf(), &via.padstack, Some(via.net_id), ObjectKind::Via)
     }
 
     // Adds all pins in the given net.
     pub fn add_net(&mut self, pcb: &Pcb, net: &Net) -> Result<()> {
         for p in &net.pins {
             let (component, pin) = pcb.pin_ref(p)?;
-            self.add_pin(&component.tf(), p.clone(), pin, Tag(net.id));
+            self.add_pin(&component.tf(), p.clone(), pin, Some(net.id));
         }
         Ok(())
     }
@@ -98,11 +169,18 @@ impl PlaceModel {
         }
     }
 
+    // Removes shapes previously returned by |add_wire| or |add_via|.
+    pub fn remove_shapes(&mut self, ids: &[PlaceId]) {
+        for &id in ids {
+            self.remove_shape(id);
+        }
+    }
+
     pub fn is_wire_blocked(&self, wire: &Wire) -> bool {
         self.is_shape_blocked(
             &Tf::identity(),
             &wire.shape,
-            TagQuery::Except(Tag(wire.net_id)),
+            NetQuery::Except(wire.net_id),
             ObjectKind::Wire,
             self.pcb.net_ruleset(wire.net_id).clearances(),
         )
@@ -112,7 +190,7 @@ impl PlaceModel {
         self.is_padstack_blocked(
             &via.tf(),
             &via.padstack,
-            TagQuery::All,
+            NetQuery::All,
             ObjectKind::Via,
             self.pcb.net_ruleset(via.net_id).clearances(),
         )
@@ -122,16 +200,16 @@ impl PlaceModel {
         &self,
         tf: &Tf,
         ls: &LayerShape,
-        q: TagQuery,
+        q: NetQuery,
         kind: ObjectKind,
         clearances: &[Clearance],
     ) -> bool {
-        let s = tf.shape(&ls.shape);
+        let s = shape_tf(tf, &ls.shape);
 
         for layer in ls.layers.iter() {
             if let Some(boundary) = self.boundary.get(&layer) {
                 // TODO: Convert boundary to path and compute distance to it for clearance.
-                if !boundary.contains(&s, Query(q, KindsQuery::All)) {
+                if !boundary.contains(&s) {
                     return true;
                 }
             }
@@ -140,18 +218,33 @@ impl PlaceModel {
         // Check for intersection first, it's generally cheaper than checking distance.
         for layer in ls.layers.iter() {
             if let Some(blocked) = self.blocked.get(&layer) {
-                if blocked.intersects(&s, Query(q, KindsQuery::All)) {
+                if blocked.intersects(&s, q) {
                     return true;
                 }
             }
         }
 
+        // Check keepouts which only apply to this kind of object.
+        let keepouts = match kind {
+            ObjectKind::Wire => Some(&self.wire_keepouts),
+            ObjectKind::Via => Some(&self.via_keepouts),
+            _ => None,
+        };
+        if let Some(keepouts) = keepouts {
+            for layer in ls.layers.iter() {
+                if let Some(keepout) = keepouts.get(&layer) {
+                    if keepout.intersects(&s, NetQuery::All) {
+                        return true;
+                    }
+                }
+            }
+        }
+
         // Check for clearance.
         for layer in ls.layers.iter() {
             if let Some(blocked) = self.blocked.get(&layer) {
                 for c in clearances {
-                    let d = blocked.dist(&s, Query(q, KindsQuery::HasCommon(c.subset_for(kind))));
-                    if le(d, c.amount()) {
+                    if blocked.within(&s, c.amount(), q, c.subset_for(kind)) {
                         return true;
                     }
                 }
@@ -166,14 +259,7 @@ impl PlaceModel {
 
         self.bounds = self.bounds.united(&pcb.bounds());
         for boundary in pcb.boundaries() {
-            Self::add_shape(
-                self.bounds,
-                &mut self.boundary,
-                &tf,
-                boundary,
-                NO_TAG,
-                ObjectKind::Area.query(),
-            );
+            Self::add_shape(self.bounds, &mut self.boundary, &tf, boundary, None, ObjectKind::Area);
         }
 
         for wire in pcb.wires() {
@@ -183,32 +269,18 @@ impl PlaceModel {
             self.add_via(via);
         }
         for keepout in pcb.keepouts() {
-            Self::add_shape(
-                self.bounds,
-                &mut self.blocked,
-                &tf,
-                &keepout.shape,
-                NO_TAG,
-                ObjectKind::Area.query(),
-            );
+            self.add_keepout(&tf, keepout);
         }
 
         for c in pcb.components() {
             let tf = tf * c.tf();
             for pin in c.pins() {
                 let r = PinRef::new(c, pin);
-                let tag = if let Some(tag) = pcb.pin_ref_net(&r) { Tag(tag) } else { NO_TAG };
-                self.add_pin(&tf, r, pin, tag);
+                let net_id = pcb.pin_ref_net(&r);
+                self.add_pin(&tf, r, pin, net_id);
             }
             for keepout in &c.keepouts {
-                Self::add_shape(
-                    self.bounds,
-                    &mut self.blocked,
-                    &tf,
-                    &keepout.shape,
-                    NO_TAG,
-                    ObjectKind::Area.query(),
-                );
+                self.add_keepout(&tf, keepout);
             }
         }
         self.pcb = pcb;
@@ -216,46 +288,49 @@ impl PlaceModel {
 
     fn add_shape(
         bounds: Rt,
-        map: &mut HashMap<LayerId, Compound>,
+        map: &mut HashMap<LayerId, LayerShapes>,
         tf: &Tf,
         ls: &LayerShape,
-        tag: Tag,
-        kinds: Kinds,
+        net_id: Option<Id>,
+        kind: ObjectKind,
     ) -> Vec<PlaceId> {
-        let s = tf.shape(&ls.shape);
-        let mut idxs = Vec::new();
-
-        for layer in ls.layers.iter() {
-            idxs.extend(
-                map.entry(layer)
-                    .or_insert_with(|| Compound::with_bounds(&bounds))
-                    .add_shape(ShapeInfo::new(s.clone(), tag, kinds))
-                    .iter()
-                    .map(|&v| (layer, v)),
-            );
-        }
+        let s = shape_tf(tf, &ls.shape);
+        ls.layers
+            .iter()
+            .map(|layer| {
+                let shapes = map.entry(layer).or_insert_with(|| LayerShapes::new(bounds));
+                (layer, shapes.add(s.clone(), net_id, kind))
+            })
+            .collect()
+    }
 
-        idxs
+    fn add_keepout(&mut self, tf: &Tf, keepout: &Keepout) {
+        let map = match keepout.kind {
+            KeepoutType::Keepout => &mut self.blocked,
+            KeepoutType::ViaKeepout => &mut self.via_keepouts,
+            KeepoutType::WireKeepout => &mut self.wire_keepouts,
+        };
+        Self::add_shape(self.bounds, map, tf, &keepout.shape, None, ObjectKind::Area);
     }
 
     fn add_padstack(
         &mut self,
         tf: &Tf,
         padstack: &Padstack,
-        tag: Tag,
-        kinds: Kinds,
+        net_id: Option<Id>,
+        kind: ObjectKind,
     ) -> Vec<PlaceId> {
         padstack
             .shapes
             .iter()
             .flat_map(|shape| {
-                Self::add_shape(self.bounds, &mut self.blocked, tf, shape, tag, kinds)
+                Self::add_shape(self.bounds, &mut self.blocked, tf, shape, net_id, kind)
             })
             .collect()
     }
 
-    fn add_pin(&mut self, tf: &Tf, pinref: PinRef, pin: &Pin, tag: Tag) -> Vec<PlaceId> {
-        let ids = self.add_padstack(&(tf * pin.tf()), &pin.padstack, tag, ObjectKind::Pin.query());
+    fn add_pin(&mut self, tf: &Tf, pinref: PinRef, pin: &Pin, net_id: Option<Id>) -> Vec<PlaceId> {
+        let ids = self.add_padstack(&(tf * pin.tf()), &pin.padstack, net_id, ObjectKind::Pin);
         let e = self.pins.entry(pinref).or_insert_with(Vec::new);
         for &id in &ids {
             e.push(id);
@@ -272,14 +347,14 @@ impl PlaceModel {
     }
 
     fn remove_shape(&mut self, id: PlaceId) {
-        self.blocked.get_mut(&id.0).unwrap().remove_shape(id.1);
+        self.blocked.get_mut(&id.0).unwrap().qt.remove(id.1);
     }
 
     fn is_padstack_blocked(
         &self,
         tf: &Tf,
         padstack: &Padstack,
-        q: TagQuery,
+        q: NetQuery,
         kind: ObjectKind,
         clearances: &[Clearance],
     ) -> bool {
@@ -0,0 +1,209 @@
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{path, ShapeOps};
+use ordered_float::OrderedFloat;
+use priority_queue::PriorityQueue;
+
+use crate::model::pcb::{LayerSet, Net, Pcb};
+use crate::route::router::RouteResult;
+
+// Number of nearest neighbours of each pin considered for the spanning tree.
+const NEIGHBOURS: usize = 8;
+const RATSNEST_RADIUS: f64 = 0.05;
+// Connections between pins with no layer in common need a via.
+const INTER_LAYER_RADIUS: f64 = 0.25;
+
+// Connections of each net, as a minimum spanning tree over its pins.
+// Connections between pins that don't share a layer are drawn wider.
+#[must_use]
+pub fn ratsnest(pcb: &Pcb) -> Vec<Vec<Shape>> {
+    pcb.nets().map(|net| net_ratsnest(pcb, net)).collect()
+}
+
+// Like |ratsnest|, but skips nets already connected by copper on |pcb|.
+#[must_use]
+pub fn open_ratsnest(pcb: &Pcb) -> Vec<Vec<Shape>> {
+    let open = RouteResult::default().verify_connectivity(pcb);
+    pcb.nets().filter(|net| open.contains(&net.id)).map(|net| net_ratsnest(pcb, net)).collect()
+}
+
+// Whether |s|, from |ratsnest|, connects pins with no layer in common.
+#[must_use]
+pub fn is_inter_layer(s: &Shape) -> bool {
+    matches!(s, Shape::Path(p) if p.r() > RATSNEST_RADIUS)
+}
+
+// Connections needed to join the pins of one net. See |ratsnest|.
+#[must_use]
+pub fn net_ratsnest(pcb: &Pcb, net: &Net) -> Vec<Shape> {
+    let pins: Vec<(Pt, LayerSet)> = net
+        .pins
+        .iter()
+        .filter_map(|p| pcb.pin_ref(p).ok())
+        .map(|(c, pin)| {
+            let layers = pin.padstack.shapes.iter().map(|v| v.layers).collect();
+            (c.pin_pt(pin), layers)
+        })
+        .collect();
+    let pts: Vec<_> = pins.iter().map(|v| v.0).collect();
+    mst(&pts)
+        .into_iter()
+        .map(|(a, b)| {
+            let same_layer = !(pins[a].1 & pins[b].1).is_empty();
+            let r = if same_layer { RATSNEST_RADIUS } else { INTER_LAYER_RADIUS };
+            path(&[pts[a], pts[b]], r).shape()
+        })
+        .collect()
+}
+
+// Minimum spanning tree of |pts| as pairs of indices. Only edges from each
+// point to its nearest neighbours are considered, which avoids looking at
+// every pair on large nets. That candidate set nearly always contains the
+// tree, but if it doesn't connect every point, all pairs are used instead.
+#[must_use]
+pub fn mst(pts: &[Pt]) -> Vec<(usize, usize)> {
+    let adj = nearest_neighbours(pts, NEIGHBOURS);
+    prim(pts, |i| adj[i].clone())
+        .or_else(|| prim(pts, |i| (0..pts.len()).filter(|&j| j != i).collect()))
+        .unwrap_or_default()
+}
+
+// Prim's algorithm over the edges given by |adj|. Returns None if the edges
+// don't connect all of |pts|.
+fn prim(pts: &[Pt], adj: impl Fn(usize) -> Vec<usize>) -> Option<Vec<(usize, usize)>> {
+    if pts.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut seen = vec![false; pts.len()];
+    let mut from = vec![0; pts.len()];
+    let mut q = PriorityQueue::new();
+    let mut edges = Vec::new();
+    q.push(0, OrderedFloat(0.0));
+    while let Some((i, _)) = q.pop() {
+        seen[i] = true;
+        if i != 0 {
+            edges.push((from[i], i));
+        }
+        for j in adj(i) {
+            if seen[j] {
+                continue;
+            }
+            // Priority queue pops the largest first, so negate distances.
+            let d = OrderedFloat(-pts[i].dist(pts[j]));
+            if q.get_priority(&j).map_or(true, |&p| d > p) {
+                from[j] = i;
+                q.push(j, d);
+            }
+        }
+    }
+    (edges.len() + 1 == pts.len()).then_some(edges)
+}
+
+// For each point, the indices of up to |k| nearest other points, symmetrised
+// so that the edge set is undirected. Uses a sweep over points sorted by x to
+// avoid comparing every pair.
+fn nearest_neighbours(pts: &[Pt], k: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..pts.len()).collect();
+    order.sort_unstable_by(|&a, &b| pts[a].x.total_cmp(&pts[b].x));
+
+    let mut adj = vec![Vec::new(); pts.len()];
+    for (pos, &i) in order.iter().enumerate() {
+        let mut best: Vec<(f64, usize)> = Vec::new();
+        let kth = |best: &Vec<(f64, usize)>| {
+            if best.len() < k {
+                f64::INFINITY
+            } else {
+                best[best.len() - 1].0
+            }
+        };
+        let consider = |j: usize, best: &mut Vec<(f64, usize)>| {
+            let d = pts[i].dist(pts[j]);
+            if d < kth(best) {
+                let at = best.partition_point(|v| v.0 <= d);
+                best.insert(at, (d, j));
+                best.truncate(k);
+            }
+        };
+        for &j in &order[pos + 1..] {
+            if pts[j].x - pts[i].x >= kth(&best) {
+                break;
+            }
+            consider(j, &mut best);
+        }
+        for &j in order[..pos].iter().rev() {
+            if pts[i].x - pts[j].x >= kth(&best) {
+                break;
+            }
+            consider(j, &mut best);
+        }
+        for (_, j) in best {
+            adj[i].push(j);
+            adj[j].push(i);
+        }
+    }
+    for v in &mut adj {
+        v.sort_unstable();
+        v.dedup();
+    }
+    adj
+}
+
+#[cfg(test)]
+mod tests {
+    use memegeom::primitive::point::Pt;
+    use memegeom::primitive::pt;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::mst;
+    use crate::geom::math::f64_eq;
+
+    // Weight of the minimum spanning tree of |pts|, by Prim's algorithm over
+    // every pair.
+    fn brute_force_mst(pts: &[Pt]) -> f64 {
+        let mut dist = vec![f64::INFINITY; pts.len()];
+        let mut seen = vec![false; pts.len()];
+        let mut total = 0.0;
+        if let Some(d) = dist.first_mut() {
+            *d = 0.0;
+        }
+        while let Some(i) =
+            (0..pts.len()).filter(|&i| !seen[i]).min_by(|&a, &b| dist[a].total_cmp(&dist[b]))
+        {
+            seen[i] = true;
+            total += dist[i];
+            for j in 0..pts.len() {
+                dist[j] = dist[j].min(pts[i].dist(pts[j]));
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn mst_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for clustered in [false, true] {
+            for n in [0, 1, 2, 3, 10, 50, 200] {
+                // Far apart clusters leave each point's nearest neighbours all
+                // in its own cluster, so the tree needs the all pairs fallback.
+                let pts: Vec<_> = (0..n)
+                    .map(|i| {
+                        let x = if clustered { (i % 3) as f64 * 50.0 } else { 0.0 };
+                        pt(x + rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0))
+                    })
+                    .collect();
+                let edges = mst(&pts);
+                assert_eq!(edges.len(), n.saturating_sub(1), "{n}");
+                // n - 1 edges joining every point make a spanning tree.
+                let mut group: Vec<usize> = (0..n).collect();
+                for &(a, b) in &edges {
+                    let (ga, gb) = (group[a], group[b]);
+                    group.iter_mut().filter(|g| **g == gb).for_each(|g| *g = ga);
+                }
+                assert!(group.iter().all(|&g| g == group[0]), "{n}");
+                let weight: f64 = edges.iter().map(|&(a, b)| pts[a].dist(pts[b])).sum();
+                assert!(f64_eq(weight, brute_force_mst(&pts)), "{n} {clustered}");
+            }
+        }
+    }
+}
@@ -1,7 +1,13 @@
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use derive_more::{Deref, DerefMut, Display};
-use eyre::Result;
+use eyre::{eyre, Result};
 use memega::eval::Evaluator;
 use memega::evolve::cfg::{
     Crossover, Duplicates, EvolveCfg, Mutation, Niching, Replacement, Stagnation, Survival,
@@ -9,71 +15,913 @@ use memega::evolve::cfg::{
 use memega::evolve::evolver::Evolver;
 use memega::ops::crossover::{crossover_cycle, crossover_order, crossover_pmx};
 use memega::ops::distance::kendall_tau;
-use memega::ops::mutation::{mutate_insert, mutate_inversion, mutate_scramble, mutate_swap};
-use memega::train::cfg::{Termination, TrainerCfg};
-use memega::train::sampler::EmptyDataSampler;
-use memega::train::trainer::Trainer;
+use memegeom::primitive::point::Pt;
 use memegeom::primitive::rect::Rt;
+use memegeom::primitive::shape::Shape;
+use memegeom::primitive::{path, poly, pt, ShapeOps};
+use memegeom::tf::Tf;
+use ordered_float::OrderedFloat;
 use rand::prelude::SliceRandom;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-use crate::model::pcb::{Pcb, Via, Wire};
+use crate::error::MemerouteError;
+use crate::geom::distance::pt_seg_dist;
+use crate::geom::intersects::shape_intersects_shape;
+use crate::geom::math::{dot, f64_eq, is_collinear, pt_eq};
+use crate::geom::transform::shape_tf;
+use crate::geom::{path_point_at, Seg};
+use crate::model::pcb::{
+    Clearance, LayerId, LayerSet, LayerShape, Padstack, Pcb, PinRef, Via, Wire,
+};
 use crate::name::Id;
-use crate::route::grid::GridRouter;
+use crate::route::grid::{GridRouter, RouteDirections};
+use crate::route::layers::{Direction, ViaCostModel};
+use crate::route::place_model::PlaceModel;
+use crate::route::ratsnest::ratsnest;
+
+// Maximum angle covered by each segment of an approximated arc.
+const FILLET_STEP: f64 = PI / 16.0;
 
 pub trait RouteStrategy {
     fn route(&mut self) -> Result<RouteResult>;
 }
 
+// Reports the number of nets routed so far out of the total.
+#[must_use]
+#[derive(Clone)]
+pub struct Progress(pub Arc<dyn Fn(usize, usize) + Send + Sync>);
+
+impl Progress {
+    pub fn report(&self, done: usize, total: usize) {
+        (self.0)(done, total);
+    }
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+// Creates the strategy used to route a PCB with the given net order.
+pub type StrategyFactory = dyn Fn(Pcb, Vec<Id>) -> Box<dyn RouteStrategy> + Send + Sync;
+
+// Scores a route for the GA. Lower costs are better.
+pub trait CostModel: std::fmt::Debug + Send + Sync {
+    fn cost(&self, res: &RouteResult) -> f64;
+}
+
+// Weighted sum of failure, vias, and total wire length. This is the default
+// cost model.
 #[must_use]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteCost {
+    pub failure_cost: f64,
+    pub via_cost: ViaCostModel,
+    pub length_cost: f64, // Per millimetre of wire.
+}
+
+impl Default for RouteCost {
+    fn default() -> Self {
+        Self { failure_cost: 1000.0, via_cost: ViaCostModel::default(), length_cost: 1.0 }
+    }
+}
+
+impl CostModel for RouteCost {
+    fn cost(&self, res: &RouteResult) -> f64 {
+        let mut cost = 0.0;
+        if res.failed {
+            cost += self.failure_cost;
+        }
+        cost += res.vias.iter().map(|v| self.via_cost.via_cost(v.padstack.layers())).sum::<f64>();
+        cost += res.wire_length() * self.length_cost;
+        cost
+    }
+}
+
+#[must_use]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct RouteResult {
     pub wires: Vec<Wire>,
     pub vias: Vec<Via>,
     pub debug_rts: Vec<Rt>,
     pub failed: bool,
+    pub timed_out: bool,      // Routing stopped early because it ran out of time.
+    pub ripup_rounds: usize,  // Number of rip-up and reroute passes made.
+    pub cancelled: bool,      // Routing was stopped early on request.
+    pub num_nets: usize,      // Number of nets the router was asked to route.
+    pub failed_nets: Vec<Id>, // Nets left unrouted, including ones never attempted.
+    pub locked_nets: Vec<Id>, // Nets left as they were, see |Router::set_locked|.
+}
+
+// Summary of a route, see |RouteResult::stats|.
+#[must_use]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RouteStats {
+    pub layer_length: HashMap<LayerId, f64>, // Length of path wires on each layer.
+    pub via_count: usize,
+    pub failed_nets: usize,
+    pub completion: f64, // Fraction of nets routed, 1 if there were none.
 }
 
 impl RouteResult {
+    // Saves the result as JSON, e.g. to cache an expensive route.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    // Total length of the centrelines of all path wires.
+    #[must_use]
+    pub fn wire_length(&self) -> f64 {
+        self.wires.iter().map(wire_length).sum()
+    }
+
+    // Length of the centrelines of the path wires of each net. Vias and other
+    // wire shapes don't count.
+    #[must_use]
+    pub fn net_lengths(&self) -> HashMap<Id, f64> {
+        let mut lengths = HashMap::new();
+        for w in &self.wires {
+            *lengths.entry(w.net_id).or_insert(0.0) += wire_length(w);
+        }
+        lengths
+    }
+
+    // Pads nets that are shorter than their |LengthRule| on |pcb| with
+    // serpentine detours. Each detour replaces a single straight segment and
+    // is only added if it keeps clearance to everything on other nets, so nets
+    // without room for one are left short.
+    pub fn match_lengths(&mut self, pcb: &Pcb) {
+        let mut model = PlaceModel::new(pcb.clone());
+        for wire in &self.wires {
+            model.add_wire(wire);
+        }
+        for via in &self.vias {
+            model.add_via(via);
+        }
+
+        for rule in pcb.length_rules() {
+            for &net_id in &rule.group {
+                if self.locked_nets.contains(&net_id) {
+                    continue;
+                }
+                let len = self.net_lengths().get(&net_id).copied().unwrap_or(0.0);
+                let extra = rule.target - len;
+                if extra <= rule.tolerance {
+                    continue;
+                }
+                let clearance = pcb
+                    .net_ruleset(net_id)
+                    .clearances()
+                    .iter()
+                    .map(Clearance::amount)
+                    .fold(0.0, f64::max);
+                if let Some(wire) = self.add_serpentine(&model, net_id, extra, clearance) {
+                    model.add_wire(&wire);
+                }
+            }
+        }
+    }
+
+    // Replaces the longest straight segment of |net_id| that has room with a
+    // serpentine adding |extra| length. Returns the detour that was added.
+    fn add_serpentine(
+        &mut self,
+        model: &PlaceModel,
+        net_id: Id,
+        extra: f64,
+        clearance: f64,
+    ) -> Option<Wire> {
+        let mut segs = Vec::new();
+        for (wire_idx, wire) in self.wires.iter().enumerate() {
+            let Shape::Path(p) = &wire.shape.shape else { continue };
+            if wire.net_id != net_id {
+                continue;
+            }
+            for (i, v) in p.pts().windows(2).enumerate() {
+                segs.push((wire_idx, i, v[0].dist(v[1])));
+            }
+        }
+        segs.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        for (wire_idx, i, _) in segs {
+            let wire = &self.wires[wire_idx];
+            let Shape::Path(p) = &wire.shape.shape else { continue };
+            let (a, b) = (p.pts()[i], p.pts()[i + 1]);
+            // Legs of the serpentine are one wire width plus clearance apart.
+            let spacing = 2.0 * p.r() + clearance;
+            for side in [1.0, -1.0] {
+                let Some(meander) = serpentine_pts(a, b, spacing, side * extra) else { continue };
+                let detour =
+                    LayerShape { layers: wire.shape.layers, shape: path(&meander, p.r()).shape() };
+                let detour = Wire { shape: detour, net_id };
+                if model.is_wire_blocked(&detour) {
+                    continue;
+                }
+                let mut pts = p.pts()[..i].to_vec();
+                pts.extend(meander);
+                pts.extend_from_slice(&p.pts()[i + 2..]);
+                self.wires[wire_idx].shape.shape = path(&pts, p.r()).shape();
+                return Some(detour);
+            }
+        }
+        None
+    }
+
     pub fn merge(&mut self, r: RouteResult) {
         self.wires.extend(r.wires);
         self.vias.extend(r.vias);
         self.debug_rts.extend(r.debug_rts);
         self.failed |= r.failed;
+        self.timed_out |= r.timed_out;
+        self.ripup_rounds += r.ripup_rounds;
+        self.cancelled |= r.cancelled;
+        self.num_nets += r.num_nets;
+        self.failed_nets.extend(r.failed_nets);
+        self.locked_nets.extend(r.locked_nets);
+    }
+
+    pub fn stats(&self) -> RouteStats {
+        let mut layer_length = HashMap::new();
+        for w in &self.wires {
+            let len = wire_length(w);
+            for layer in w.shape.layers.iter() {
+                *layer_length.entry(layer).or_insert(0.0) += len;
+            }
+        }
+        let completion = if self.num_nets == 0 {
+            1.0
+        } else {
+            1.0 - self.failed_nets.len() as f64 / self.num_nets as f64
+        };
+        RouteStats {
+            layer_length,
+            via_count: self.vias.len(),
+            failed_nets: self.failed_nets.len(),
+            completion,
+        }
+    }
+
+    // Joins wires with the same net, layers, and width which meet end to end
+    // in a straight line, and removes repeated points and points in the middle
+    // of straight runs inside each wire. Zero length wires lying on another
+    // wire of the group add no copper, so they are dropped. The grid router
+    // emits lots of short segments, so this shrinks the output a lot. Locked
+    // nets are left alone.
+    pub fn simplify(&mut self) {
+        let mut groups: HashMap<(Id, LayerSet, OrderedFloat<f64>), Vec<Vec<Pt>>> = HashMap::new();
+        let mut wires = Vec::new();
+        for wire in self.wires.drain(..) {
+            match &wire.shape.shape {
+                Shape::Path(p) if !self.locked_nets.contains(&wire.net_id) => {
+                    groups
+                        .entry((wire.net_id, wire.shape.layers, OrderedFloat(p.r())))
+                        .or_default()
+                        .push(simplify_pts(p.pts()));
+                }
+                _ => wires.push(wire),
+            }
+        }
+
+        for ((net_id, layers, r), mut paths) in groups {
+            let mut i = 0;
+            while i < paths.len() {
+                let mut joined = false;
+                for j in (i + 1)..paths.len() {
+                    if let Some(pts) = join_pts(&paths[i], &paths[j]) {
+                        paths[i] = pts;
+                        paths.swap_remove(j);
+                        joined = true;
+                        break;
+                    }
+                }
+                // Keep trying to extend this path until nothing else joins on.
+                if !joined {
+                    i += 1;
+                }
+            }
+            let is_dot = |pts: &[Pt]| pts.iter().all(|&p| pt_eq(p, pts[0]));
+            let on = |p: Pt, pts: &[Pt]| {
+                pts.windows(2).any(|v| f64_eq(pt_seg_dist(p, &Seg::new(v[0], v[1])), 0.0))
+            };
+            let covered = |i: usize| {
+                is_dot(&paths[i])
+                    && paths
+                        .iter()
+                        .enumerate()
+                        .any(|(j, other)| j != i && !is_dot(other) && on(paths[i][0], other))
+            };
+            let keep: Vec<_> = (0..paths.len()).map(|i| !covered(i)).collect();
+            for (pts, _) in paths.into_iter().zip(keep).filter(|v| v.1) {
+                let shape = LayerShape { layers, shape: path(&pts, r.0).shape() };
+                wires.push(Wire { shape, net_id });
+            }
+        }
+        self.wires = wires;
+    }
+
+    // Rounds each corner of each wire into an arc of radius |radius|, tangent
+    // to the segments on either side. memegeom has no arc shape, so arcs are
+    // approximated by short segments. The radius is reduced at corners where
+    // the segments are too short to fit it. The arc cuts across the inside of
+    // the corner, so it can come closer to copper there; a wire whose arcs
+    // would break clearance to anything on |pcb| or in this result is left as
+    // it was. Locked nets are left alone.
+    pub fn fillet_corners(&mut self, pcb: &Pcb, radius: f64) {
+        let mut model = PlaceModel::new(pcb.clone());
+        let ids: Vec<_> = self.wires.iter().map(|w| model.add_wire(w)).collect();
+        for via in &self.vias {
+            model.add_via(via);
+        }
+        for (wire, ids) in self.wires.iter_mut().zip(ids) {
+            if self.locked_nets.contains(&wire.net_id) {
+                continue;
+            }
+            let Shape::Path(p) = &wire.shape.shape else { continue };
+            let pts = simplify_pts(p.pts());
+            if pts.len() < 3 {
+                continue;
+            }
+            let mut out = vec![pts[0]];
+            for w in pts.windows(3) {
+                out.extend(fillet_pts(w[0], w[1], w[2], radius));
+            }
+            out.push(pts[pts.len() - 1]);
+            let shape = LayerShape { layers: wire.shape.layers, shape: path(&out, p.r()).shape() };
+            let filleted = Wire { shape, net_id: wire.net_id };
+            if model.is_wire_blocked(&filleted) {
+                continue;
+            }
+            model.remove_shapes(&ids);
+            model.add_wire(&filleted);
+            *wire = filleted;
+        }
+    }
+
+    // Adds a teardrop at each place a wire ends on a pad of the same net.
+    // The teardrop is as wide as the pad at the pad centre and tapers to the
+    // wire width at a distance of |size| beyond the pad edge.
+    pub fn add_teardrops(&mut self, pcb: &Pcb, size: f64) {
+        let mut teardrops = Vec::new();
+        for wire in &self.wires {
+            let Shape::Path(path) = &wire.shape.shape else { continue };
+            let pts = path.pts();
+            if pts.len() < 2 {
+                continue;
+            }
+            // Each end of the wire, with the wire's points going away from it.
+            let ends = [pts.to_vec(), pts.iter().rev().copied().collect()];
+            for c in pcb.components() {
+                for pin in c.pins() {
+                    if pcb.pin_ref_net(&PinRef::new(c, pin)) != Some(wire.net_id) {
+                        continue;
+                    }
+                    let centre = c.pin_pt(pin);
+                    for ps in &pin.padstack.shapes {
+                        if (ps.layers & wire.shape.layers).is_empty() {
+                            continue;
+                        }
+                        let r = pad_radius(&ps.shape);
+                        for end in &ends {
+                            let (p0, p1) = (end[0], end[1]);
+                            if p0.dist(centre) > r || p0.dist(p1) <= r {
+                                continue;
+                            }
+                            let perp = (p1 - p0).perp();
+                            // Measured along the wire, so the tip stays on its
+                            // copper if it bends soon after leaving the pad.
+                            let tip = path_point_at(end, r + size);
+                            let pts = [
+                                centre - r * perp,
+                                tip - path.r() * perp,
+                                tip + path.r() * perp,
+                                centre + r * perp,
+                            ];
+                            let shape =
+                                LayerShape { layers: wire.shape.layers, shape: poly(&pts).shape() };
+                            teardrops.push(Wire { shape, net_id: wire.net_id });
+                        }
+                    }
+                }
+            }
+        }
+        self.wires.extend(teardrops);
+    }
+
+    // Returns the ids of nets whose pins are not all electrically connected by
+    // the wires and vias in this result plus any copper already on |pcb|.
+    // This is independent of |failed|, so it catches the router claiming
+    // success while leaving a gap.
+    #[must_use]
+    pub fn verify_connectivity(&self, pcb: &Pcb) -> Vec<Id> {
+        let mut disconnected = Vec::new();
+        for net in pcb.nets() {
+            if net.pins.len() <= 1 {
+                continue;
+            }
+            // Each group is a piece of copper that is connected internally.
+            let mut groups: Vec<Vec<LayerShape>> = Vec::new();
+            let mut resolved = true;
+            for p in &net.pins {
+                let Ok((component, pin)) = pcb.pin_ref(p) else {
+                    resolved = false;
+                    break;
+                };
+                groups.push(padstack_shapes(&component.pin_tf(pin), &pin.padstack));
+            }
+            if !resolved {
+                disconnected.push(net.id);
+                continue;
+            }
+            let num_pins = groups.len();
+            for wire in pcb.wires().iter().chain(self.wires.iter()) {
+                if wire.net_id == net.id {
+                    groups.push(vec![wire.shape.clone()]);
+                }
+            }
+            for via in pcb.vias().iter().chain(self.vias.iter()) {
+                if via.net_id == net.id {
+                    groups.push(padstack_shapes(&via.tf(), &via.padstack));
+                }
+            }
+
+            let mut parent: Vec<usize> = (0..groups.len()).collect();
+            for i in 0..groups.len() {
+                for j in (i + 1)..groups.len() {
+                    if groups_touch(&groups[i], &groups[j]) {
+                        let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                        parent[a] = b;
+                    }
+                }
+            }
+            let root = find(&mut parent, 0);
+            if (1..num_pins).any(|i| find(&mut parent, i) != root) {
+                disconnected.push(net.id);
+            }
+        }
+        disconnected.sort_unstable();
+        disconnected
     }
 }
 
+fn wire_length(w: &Wire) -> f64 {
+    let Shape::Path(p) = &w.shape.shape else { return 0.0 };
+    p.pts().windows(2).map(|v| v[0].dist(v[1])).sum()
+}
+
+// Points going from |a| to |b| with square bumps |spacing| wide to the left
+// of the segment (right if |extra| is negative), adding |extra| to its
+// length. The bumps are spread along the whole segment to keep them short.
+// Returns None if the segment is too short for any bumps.
+fn serpentine_pts(a: Pt, b: Pt, spacing: f64, extra: f64) -> Option<Vec<Pt>> {
+    let len = a.dist(b);
+    let periods = ((len - 2.0 * spacing) / (2.0 * spacing)).floor();
+    if periods < 1.0 || f64_eq(len, 0.0) {
+        return None;
+    }
+    // Each bump goes out and back, so adds twice its height.
+    let height = extra / (2.0 * periods);
+    let dir = (1.0 / len) * (b - a);
+    let up = height * pt(-dir.y, dir.x);
+    let mut pts = vec![a];
+    for k in 0..periods as usize {
+        let q = a + (spacing * (1 + 2 * k) as f64) * dir;
+        let q1 = q + spacing * dir;
+        pts.extend([q, q + up, q1 + up, q1]);
+    }
+    pts.push(b);
+    Some(pts)
+}
+
+// Whether b lies on the straight line from a to c, continuing in the same
+// direction.
+fn is_straight(a: Pt, b: Pt, c: Pt) -> bool {
+    is_collinear(a, b, c) && dot(b - a, c - b) > 0.0
+}
+
+// Removes repeated points and points in the middle of straight runs.
+fn simplify_pts(pts: &[Pt]) -> Vec<Pt> {
+    let mut out: Vec<Pt> = pts.to_vec();
+    out.dedup_by(|a, b| pt_eq(*a, *b));
+    // Single square wires are stored as two identical points, so keep those.
+    if out.len() < 2 {
+        return pts.to_vec();
+    }
+    let mut i = 1;
+    while i + 1 < out.len() {
+        if is_straight(out[i - 1], out[i], out[i + 1]) {
+            out.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+// Joins |a| and |b| into one path if they share an endpoint and continue in a
+// straight line through it.
+fn join_pts(a: &[Pt], b: &[Pt]) -> Option<Vec<Pt>> {
+    let rev = |v: &[Pt]| v.iter().rev().copied().collect::<Vec<_>>();
+    let (a_st, a_en) = (a[0], a[a.len() - 1]);
+    let (b_st, b_en) = (b[0], b[b.len() - 1]);
+    let (first, second) = if pt_eq(a_en, b_st) {
+        (a.to_vec(), b.to_vec())
+    } else if pt_eq(a_en, b_en) {
+        (a.to_vec(), rev(b))
+    } else if pt_eq(a_st, b_en) {
+        (b.to_vec(), a.to_vec())
+    } else if pt_eq(a_st, b_st) {
+        (rev(b), a.to_vec())
+    } else {
+        return None;
+    };
+    if first.len() < 2 || second.len() < 2 {
+        return None;
+    }
+    let joint = first[first.len() - 1];
+    if !is_straight(first[first.len() - 2], joint, second[1]) {
+        return None;
+    }
+    let mut pts = first;
+    pts.extend_from_slice(&second[1..]);
+    Some(simplify_pts(&pts))
+}
+
+// Points approximating an arc of radius |r| which replaces the corner at |b|
+// in the path a-b-c. Returns just |b| if there is no corner to round.
+fn fillet_pts(a: Pt, b: Pt, c: Pt, r: f64) -> Vec<Pt> {
+    let (la, lc) = (a.dist(b), c.dist(b));
+    if f64_eq(la, 0.0) || f64_eq(lc, 0.0) {
+        return vec![b];
+    }
+    let (u, v) = ((1.0 / la) * (a - b), (1.0 / lc) * (c - b));
+    // Angle between the two segments at the corner.
+    let theta = (u.x * v.x + u.y * v.y).clamp(-1.0, 1.0).acos();
+    if f64_eq(theta, PI) || f64_eq(theta, 0.0) {
+        return vec![b];
+    }
+    // Distance from the corner to the tangent points, limited so that
+    // fillets at neighbouring corners don't overlap.
+    let half = theta / 2.0;
+    let t = (r / half.tan()).min(la / 2.0).min(lc / 2.0);
+    let r = t * half.tan();
+    let (p0, p1) = (b + t * u, b + t * v);
+    let bisect = u + v;
+    let centre = b + (r / half.sin() / bisect.dist(Pt::zero())) * bisect;
+
+    let a0 = (p0.y - centre.y).atan2(p0.x - centre.x);
+    let mut da = (p1.y - centre.y).atan2(p1.x - centre.x) - a0;
+    if da > PI {
+        da -= 2.0 * PI;
+    } else if da < -PI {
+        da += 2.0 * PI;
+    }
+    let n = ((da.abs() / FILLET_STEP).ceil() as usize).max(1);
+    (0..=n)
+        .map(|i| {
+            let ang = a0 + da * i as f64 / n as f64;
+            centre + r * pt(ang.cos(), ang.sin())
+        })
+        .collect()
+}
+
+// Radius of the largest circle centred on the pad origin that fits in |s|.
+fn pad_radius(s: &Shape) -> f64 {
+    match s {
+        Shape::Circle(s) => s.r(),
+        s => {
+            let b = s.bounds();
+            b.w().min(b.h()) / 2.0
+        }
+    }
+}
+
+fn padstack_shapes(tf: &Tf, padstack: &Padstack) -> Vec<LayerShape> {
+    padstack
+        .shapes
+        .iter()
+        .map(|s| LayerShape { layers: s.layers, shape: shape_tf(tf, &s.shape) })
+        .collect()
+}
+
+fn groups_touch(a: &[LayerShape], b: &[LayerShape]) -> bool {
+    a.iter().any(|a| {
+        b.iter().any(|b| {
+            !(a.layers & b.layers).is_empty() && shape_intersects_shape(&a.shape, &b.shape)
+        })
+    })
+}
+
+// Union-find lookup with path halving.
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
 #[must_use]
-#[derive(Debug)]
 pub struct Router {
     pcb: Mutex<Pcb>,
+    timeout: Option<Duration>,
+    // Deadline shared by every route in a GA run.
+    deadline: Option<Instant>,
+    // If unset, a |RouteCost| with |via_cost| is used.
+    cost_model: Option<Arc<dyn CostModel>>,
+    layer_direction: HashMap<LayerId, Direction>,
+    via_cost: ViaCostModel,
+    // Grid pitch for the grid router. If unset, it's derived from the board.
+    grid_pitch: Option<f64>,
+    directions: RouteDirections,
+    rng: Mutex<StdRng>,
+    // If unset, a |GridRouter| configured from this router is used.
+    factory: Option<Arc<StrategyFactory>>,
+    cancel: Option<Arc<AtomicBool>>,
+    progress: Option<Progress>,
+    // Best (fitness, net order) evaluated so far in a GA run.
+    best: Option<Arc<Mutex<Option<(f64, Vec<Id>)>>>>,
+    // Fitness of net orders already routed by the GA. Shared between clones,
+    // since the GA evaluates on its own copy of the router.
+    fitness_cache: Arc<Mutex<FitnessCache>>,
+    // Nets which are never routed. Their existing copper on the Pcb stays as
+    // fixed obstacles.
+    locked: HashSet<Id>,
+}
+
+impl std::fmt::Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field("pcb", &self.pcb)
+            .field("timeout", &self.timeout)
+            .field("deadline", &self.deadline)
+            .field("cost_model", &self.cost_model)
+            .field("layer_direction", &self.layer_direction)
+            .field("via_cost", &self.via_cost)
+            .field("grid_pitch", &self.grid_pitch)
+            .field("directions", &self.directions)
+            .field("has_factory", &self.factory.is_some())
+            .field("cancel", &self.cancel)
+            .field("progress", &self.progress)
+            .field("best", &self.best)
+            .field("fitness_cache_len", &self.fitness_cache.lock().unwrap().len())
+            .field("locked", &self.locked)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Clone for Router {
     fn clone(&self) -> Self {
-        Self::new(self.pcb.lock().unwrap().clone())
+        Self {
+            pcb: Mutex::new(self.pcb.lock().unwrap().clone()),
+            timeout: self.timeout,
+            deadline: self.deadline,
+            cost_model: self.cost_model.clone(),
+            layer_direction: self.layer_direction.clone(),
+            via_cost: self.via_cost.clone(),
+            grid_pitch: self.grid_pitch,
+            directions: self.directions,
+            rng: Mutex::new(self.rng.lock().unwrap().clone()),
+            factory: self.factory.clone(),
+            cancel: self.cancel.clone(),
+            progress: self.progress.clone(),
+            best: self.best.clone(),
+            fitness_cache: Arc::clone(&self.fitness_cache),
+            locked: self.locked.clone(),
+        }
     }
 }
 
 impl Router {
     pub fn new(pcb: Pcb) -> Self {
-        Self { pcb: Mutex::new(pcb) }
+        Self::with_seed(pcb, rand::thread_rng().gen())
+    }
+
+    // Creates a router whose random choices are all derived from |seed|. Two
+    // routers with the same seed and PCB produce identical net orders from
+    // |rand_net_order|, and so identical wires from |route|.
+    pub fn with_seed(pcb: Pcb, seed: u64) -> Self {
+        Self {
+            pcb: Mutex::new(pcb),
+            timeout: None,
+            deadline: None,
+            cost_model: None,
+            layer_direction: HashMap::new(),
+            via_cost: ViaCostModel::default(),
+            grid_pitch: None,
+            directions: RouteDirections::default(),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            factory: None,
+            cancel: None,
+            progress: None,
+            best: None,
+            fitness_cache: Arc::new(Mutex::new(FitnessCache::default())),
+            locked: HashSet::new(),
+        }
+    }
+
+    // Lets another thread stop routing by setting |cancel|. Nets are checked
+    // between, so routing stops after the current net finishes.
+    pub fn set_cancel(&mut self, cancel: Option<Arc<AtomicBool>>) {
+        self.cancel = cancel;
+    }
+
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    // Stops the given nets from being routed, e.g. ones routed by hand. Their
+    // wires and vias already on the Pcb are left as they are and routed
+    // around, and they are never ripped up.
+    pub fn set_locked(&mut self, locked: HashSet<Id>) {
+        self.locked = locked;
+    }
+
+    // Routes with strategies made by |factory| instead of the grid router,
+    // including when evaluating net orders in |run_ga|.
+    pub fn set_strategy_factory(
+        &mut self,
+        factory: impl Fn(Pcb, Vec<Id>) -> Box<dyn RouteStrategy> + Send + Sync + 'static,
+    ) {
+        self.factory = Some(Arc::new(factory));
+    }
+
+    // Sets the preferred wire direction for each layer. See
+    // |layers::alternate_directions| for the usual horizontal/vertical split.
+    pub fn set_layer_direction(&mut self, layer_direction: HashMap<LayerId, Direction>) {
+        self.layer_direction = layer_direction;
+    }
+
+    // Sets the cost of vias between each pair of layers, both for the router's
+    // search and for the GA's default cost model. A cost model set with
+    // |set_cost_model| is left as it is.
+    pub fn set_via_cost(&mut self, via_cost: ViaCostModel) {
+        self.via_cost = via_cost;
+    }
+
+    // Sets the grid router's pitch. See |GridRouter::with_grid|.
+    pub fn set_grid_pitch(&mut self, grid_pitch: Option<f64>) {
+        self.grid_pitch = grid_pitch;
+    }
+
+    // Sets whether the grid router may route wires at 45 degrees.
+    pub fn set_directions(&mut self, directions: RouteDirections) {
+        self.directions = directions;
+    }
+
+    // Sets what the GA optimises for, e.g. a |RouteCost| with custom weights.
+    pub fn set_cost_model(&mut self, cost_model: impl CostModel + 'static) {
+        self.cost_model = Some(Arc::new(cost_model));
+    }
+
+    fn cost(&self, res: &RouteResult) -> f64 {
+        match &self.cost_model {
+            Some(cost_model) => cost_model.cost(res),
+            None => RouteCost { via_cost: self.via_cost.clone(), ..RouteCost::default() }.cost(res),
+        }
+    }
+
+    // Limits the time spent in a single call to |route| or |run_ga|. When the
+    // time runs out, the partial result is returned with |timed_out| set.
+    // |run_ga| stops searching at the limit, then routes its best order with
+    // the same limit again.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline.or_else(|| self.timeout.map(|t| Instant::now() + t))
+    }
+
+    fn timed_out(&self) -> bool {
+        self.deadline.map_or(false, |d| Instant::now() >= d)
+    }
+
+    // Unrouted connections of each net. See |ratsnest::ratsnest|.
+    #[must_use]
+    pub fn ratsnest(&self) -> Vec<Vec<Shape>> {
+        ratsnest(&self.pcb.lock().unwrap())
     }
 
     pub fn rand_net_order(&self) -> Vec<Id> {
         let mut net_order: Vec<_> = self.pcb.lock().unwrap().nets().map(|v| v.id).collect();
-        //net_order.shuffle(rand::thread_rng());
+        // Sort first so the result only depends on the seed, not on map order.
         net_order.sort_unstable();
+        net_order.shuffle(&mut *self.rng.lock().unwrap());
+        net_order
+    }
+
+    // Routes the nets in |net_order| apart from locked ones, around the copper
+    // already on the Pcb. The result only has the new copper, to add to the
+    // Pcb with |apply_route_result|.
+    pub fn route(&self, net_order: Vec<Id>) -> Result<RouteResult, MemerouteError> {
+        let pcb = self.pcb.lock().unwrap().clone();
+        self.route_pcb(pcb, net_order)
+    }
+
+    // Like |route|, but first drops the copper of nets that aren't locked, so
+    // they are routed from scratch. The result has all the copper on the
+    // board, including the locked nets' unchanged, so it replaces the Pcb's
+    // wires and vias rather than adding to them.
+    pub fn reroute_all(&self, net_order: Vec<Id>) -> Result<RouteResult, MemerouteError> {
+        let mut pcb = self.pcb.lock().unwrap().clone();
+        pcb.retain_copper(|id| self.locked.contains(&id));
+        let (wires, vias) = (pcb.wires().to_vec(), pcb.vias().to_vec());
+        let mut res = self.route_pcb(pcb, net_order)?;
+        res.wires.splice(0..0, wires);
+        res.vias.splice(0..0, vias);
+        Ok(res)
+    }
+
+    fn route_pcb(&self, pcb: Pcb, mut net_order: Vec<Id>) -> Result<RouteResult, MemerouteError> {
+        net_order.retain(|id| !self.locked.contains(id));
+        if let Err(errors) = pcb.validate() {
+            let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+            return Err(MemerouteError::Route(eyre!("invalid pcb: {errors}")));
+        }
+        let mut res = if let Some(factory) = &self.factory {
+            self.route_with(factory(pcb, net_order).as_mut())?
+        } else {
+            let mut grid = match self.grid_pitch {
+                Some(pitch) => GridRouter::with_grid(pcb, net_order, pitch),
+                None => GridRouter::new(pcb, net_order),
+            };
+            grid.set_deadline(self.deadline());
+            grid.set_layer_direction(self.layer_direction.clone());
+            grid.set_via_cost(self.via_cost.clone());
+            grid.set_directions(self.directions);
+            grid.set_cancel(self.cancel.clone());
+            grid.set_progress(self.progress.clone());
+            self.route_with(&mut grid)?
+        };
+        res.locked_nets = self.locked.iter().copied().collect();
+        res.locked_nets.sort_unstable();
+        Ok(res)
+    }
+
+    // Routes with a caller supplied strategy, e.g. one not made by a factory.
+    #[allow(clippy::unused_self)]
+    pub fn route_with(
+        &self,
+        strategy: &mut dyn RouteStrategy,
+    ) -> Result<RouteResult, MemerouteError> {
+        strategy.route().map_err(MemerouteError::Route)
+    }
+
+    // Net order sorted by |heuristic|, smallest first. Ties are broken by id.
+    #[must_use]
+    pub fn heuristic_net_order(&self, heuristic: NetOrderHeuristic) -> Vec<Id> {
+        let pcb = self.pcb.lock().unwrap();
+        let mut net_order: Vec<_> = pcb.nets().map(|v| v.id).collect();
+        net_order.sort_unstable();
+        match heuristic {
+            NetOrderHeuristic::BoundsArea => {
+                net_order.sort_by_cached_key(|&id| {
+                    let b = pcb.net_bounds(id);
+                    OrderedFloat(b.w() * b.h())
+                });
+            }
+            NetOrderHeuristic::PinCount => {
+                net_order.sort_by_cached_key(|&id| pcb.net(id).map_or(0, |n| n.pins.len()));
+            }
+        }
         net_order
     }
 
-    pub fn route(&self, net_order: Vec<Id>) -> Result<RouteResult> {
-        let mut grid = GridRouter::new(self.pcb.lock().unwrap().clone(), net_order);
-        grid.route()
+    pub fn run_ga(&self, params: &GaParams) -> Result<RouteResult> {
+        let seed = params.seed.unwrap_or_else(|| self.rng.lock().unwrap().gen());
+        let mut rng = StdRng::seed_from_u64(seed);
+        let best = Arc::new(Mutex::new(None));
+        let mut router = self.clone();
+        router.rng = Mutex::new(StdRng::seed_from_u64(rng.gen()));
+        router.deadline = self.deadline();
+        router.best = Some(Arc::clone(&best));
+
+        let heuristic = self.heuristic_net_order(NetOrderHeuristic::BoundsArea);
+        router.run_generations(params, &heuristic, rng.gen())?;
+
+        let order = best.lock().unwrap().take().map_or_else(|| self.rand_net_order(), |v| v.1);
+        router.best = None;
+        // The GA may have used up the time, so the best order gets a fresh
+        // deadline rather than being cut short before it starts.
+        let timed_out = router.timed_out();
+        router.deadline = None;
+        let mut res = router.route(order)?;
+        res.timed_out |= timed_out;
+        Ok(res)
     }
 
-    pub fn run_ga(&self) -> Result<RouteResult> {
-        let cfg = EvolveCfg::new(32)
+    // Evolves one population from |net_order| until |params.generations| says
+    // to stop or the deadline passes. The best order is kept in |self.best|.
+    fn run_generations(&self, params: &GaParams, net_order: &[Id], seed: u64) -> Result<()> {
+        let cfg = EvolveCfg::new(params.pop_size)
             .set_mutation(Mutation::Adaptive)
             .set_crossover(Crossover::Adaptive)
             .set_survival(Survival::TopProportion(0.1))
@@ -84,22 +932,117 @@ impl Router {
             .set_par_fitness(true)
             .set_par_dist(true);
 
-        let net_order: Vec<_> = self.pcb.lock().unwrap().nets().map(|v| v.id).collect();
+        // Seed half the population from |net_order| to speed up convergence.
+        // The first individual is |net_order| itself, and the rest have a
+        // random swap so they aren't duplicates of it.
+        let net_order = net_order.to_vec();
+        let rng = Mutex::new(StdRng::seed_from_u64(seed));
+        let count = AtomicUsize::new(0);
         let genfn = move || {
-            let mut rand_order = net_order.clone();
-            rand_order.shuffle(&mut rand::thread_rng());
-            RouteState(rand_order)
+            let mut order = net_order.clone();
+            let mut rng = rng.lock().unwrap();
+            let n = count.fetch_add(1, Ordering::Relaxed);
+            if n % 2 == 1 {
+                order.shuffle(&mut *rng);
+            } else if n > 0 && order.len() >= 2 {
+                let (a, b) = (rng.gen_range(0..order.len()), rng.gen_range(0..order.len()));
+                order.swap(a, b);
+            }
+            RouteState(order)
         };
 
-        let evolver = Evolver::new(self.clone(), cfg, genfn);
-        let mut trainer = Trainer::new(
-            TrainerCfg::new("memeroute").set_termination(Termination::FixedGenerations(1)),
-        );
-        let order = trainer.train(evolver, &EmptyDataSampler {})?.nth(0).state.0.clone();
-        self.route(order)
+        let (patience, max) = match params.generations {
+            Generations::Fixed(generations) => (usize::MAX, generations),
+            Generations::Stagnant { patience, max } => (patience.max(1), max),
+        };
+        let best_fitness =
+            || self.best.as_ref().and_then(|b| b.lock().unwrap().as_ref().map(|v| v.0));
+        let mut evolver = Evolver::new(self.clone(), cfg, genfn);
+        let (mut fitness, mut stale) = (None, 0);
+        for _ in 0..max {
+            evolver.run()?;
+            self.fitness_cache.lock().unwrap().next_generation();
+            if self.timed_out() {
+                break;
+            }
+            let cur = best_fitness();
+            if cur > fitness {
+                (fitness, stale) = (cur, 0);
+            } else {
+                stale += 1;
+                if stale >= patience {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Fitness of the net orders evaluated in the current and previous GA
+// generation. Survivors and children that repeat a parent come from the
+// previous generation, so older orders are forgotten, which keeps the cache
+// about the size of the population.
+#[derive(Debug, Default)]
+struct FitnessCache {
+    cur: HashMap<RouteState, f64>,
+    prev: HashMap<RouteState, f64>,
+}
+
+impl FitnessCache {
+    fn get(&mut self, s: &RouteState) -> Option<f64> {
+        if let Some(&fitness) = self.cur.get(s) {
+            return Some(fitness);
+        }
+        let fitness = self.prev.remove(s)?;
+        self.cur.insert(s.clone(), fitness);
+        Some(fitness)
+    }
+
+    fn insert(&mut self, s: RouteState, fitness: f64) {
+        self.cur.insert(s, fitness);
+    }
+
+    fn next_generation(&mut self) {
+        self.prev = std::mem::take(&mut self.cur);
+    }
+
+    fn len(&self) -> usize {
+        self.cur.len() + self.prev.len()
+    }
+}
+
+// How long |Router::run_ga| runs for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Generations {
+    Fixed(usize),
+    // Stops once |patience| generations pass without a better route, or after
+    // |max| generations in total.
+    Stagnant { patience: usize, max: usize },
+}
+
+#[must_use]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GaParams {
+    pub generations: Generations,
+    pub pop_size: usize,
+    // Seed for the initial population. If unset, one is drawn from the router.
+    pub seed: Option<u64>,
+}
+
+impl Default for GaParams {
+    fn default() -> Self {
+        Self { generations: Generations::Fixed(1), pop_size: 32, seed: None }
     }
 }
 
+// Key to sort nets by for an initial net order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NetOrderHeuristic {
+    BoundsArea, // Area of the bounds of the net's pins.
+    PinCount,
+}
+
 #[must_use]
 #[derive(Debug, Display, Deref, DerefMut, Hash, Clone, PartialEq, Eq, PartialOrd)]
 #[display(fmt = "{_0:?}")]
@@ -120,29 +1063,47 @@ impl Evaluator for Router {
         };
     }
 
+    // memega's permutation mutations draw from the thread RNG, so these use
+    // the router's seeded one instead.
     fn mutate(&self, s: &mut Self::State, rate: f64, idx: usize) {
-        let mut r = rand::thread_rng();
-        if r.gen::<f64>() > rate {
+        let mut r = self.rng.lock().unwrap();
+        if r.gen::<f64>() > rate || s.len() < 2 {
             return;
         }
+        let (i, j) = (r.gen_range(0..s.len()), r.gen_range(0..s.len()));
+        let (lo, hi) = (i.min(j), i.max(j));
         match idx {
-            0 => mutate_swap(s),
-            1 => mutate_insert(s),
-            2 => mutate_scramble(s),
-            3 => mutate_inversion(s),
+            0 => s.swap(i, j),
+            1 => {
+                let v = s.remove(i);
+                s.insert(j, v);
+            }
+            2 => s[lo..=hi].shuffle(&mut *r),
+            3 => s[lo..=hi].reverse(),
             _ => panic!("unknown mutation strategy"),
         }
     }
 
     fn fitness(&self, s: &Self::State, _data: &Self::Data) -> Result<f64> {
-        let res = self.route(s.0.clone()).unwrap();
-        let mut cost = 0.0;
-        if res.failed {
-            cost += 1000.0;
+        // Survivors and repeated permutations come up again, so don't reroute
+        // them.
+        if let Some(fitness) = self.fitness_cache.lock().unwrap().get(s) {
+            return Ok(fitness);
+        }
+        // A net order the router errors on is as bad as it gets, but it
+        // shouldn't stop the rest of the GA.
+        let fitness = match self.route(s.0.clone()) {
+            Ok(res) => 1.0 / (1.0 + self.cost(&res)),
+            Err(_) => 0.0,
+        };
+        self.fitness_cache.lock().unwrap().insert(s.clone(), fitness);
+        if let Some(best) = &self.best {
+            let mut best = best.lock().unwrap();
+            if best.as_ref().map_or(true, |b| fitness > b.0) {
+                *best = Some((fitness, s.0.clone()));
+            }
         }
-        cost += res.vias.len() as f64 * 10.0;
-        // TODO: Count wire lengths
-        Ok(1.0 / (1.0 + cost))
+        Ok(fitness)
     }
 
     fn distance(&self, s1: &Self::State, s2: &Self::State) -> Result<f64> {
@@ -161,3 +1122,580 @@ pub fn apply_route_result(pcb: &mut Pcb, r: &RouteResult) {
         pcb.add_debug_rt(*rt);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use eyre::{eyre, Result};
+    use memega::eval::Evaluator;
+    use memegeom::primitive::shape::Shape;
+    use memegeom::primitive::{pt, rt, ShapeOps};
+    use serde_json::to_string;
+
+    use super::{
+        apply_route_result, CostModel, FitnessCache, GaParams, Generations, NetOrderHeuristic,
+        RouteCost, RouteResult, RouteState, RouteStrategy, Router,
+    };
+    use crate::geom::math::{f64_eq, pt_eq};
+    use crate::model::pcb::{LengthRule, Pcb, Via};
+    use crate::name::Id;
+    use crate::route::layers::ViaCostModel;
+    use crate::testing::{add_component, add_net, board, wire, PAD_R};
+
+    // Two single pin parts at (2, 5) and (8, 5), on net N1.
+    fn two_pins() -> (Pcb, Id) {
+        let mut pcb = board(10.0, 10.0);
+        add_component(&mut pcb, "U1", pt(2.0, 5.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U2", pt(8.0, 5.0), &[pt(0.0, 0.0)]);
+        let net_id = add_net(&mut pcb, "N1", &[("U1", "1"), ("U2", "1")]);
+        (pcb, net_id)
+    }
+
+    // |n| nets, each joining single pin parts on the left and right edges of
+    // the board.
+    fn nets(n: usize) -> (Pcb, Vec<Id>) {
+        let mut pcb = board(10.0, 2.0 * n as f64);
+        let ids = (0..n)
+            .map(|i| {
+                let y = 2.0 * i as f64 + 1.0;
+                let (a, b) = (format!("L{i}"), format!("R{i}"));
+                add_component(&mut pcb, &a, pt(1.0, y), &[pt(0.0, 0.0)]);
+                add_component(&mut pcb, &b, pt(9.0, y), &[pt(0.0, 0.0)]);
+                add_net(&mut pcb, &format!("N{i}"), &[(a.as_str(), "1"), (b.as_str(), "1")])
+            })
+            .collect();
+        (pcb, ids)
+    }
+
+    // Stands in for a router: waits |delay|, then gives each net in
+    // |net_order| a short wire, in order.
+    struct FakeStrategy {
+        net_order: Vec<Id>,
+        delay: Duration,
+    }
+
+    impl RouteStrategy for FakeStrategy {
+        fn route(&mut self) -> Result<RouteResult> {
+            thread::sleep(self.delay);
+            let mut res = RouteResult { num_nets: self.net_order.len(), ..RouteResult::default() };
+            for &net_id in &self.net_order {
+                res.wires.push(wire(net_id, 0, &[pt(0.0, 0.0), pt(1.0, 0.0)]));
+            }
+            Ok(res)
+        }
+    }
+
+    // Leaves |unroutable| unrouted, and errors if it's the first net.
+    struct OneFailsStrategy {
+        net_order: Vec<Id>,
+        unroutable: Id,
+    }
+
+    impl RouteStrategy for OneFailsStrategy {
+        fn route(&mut self) -> Result<RouteResult> {
+            if self.net_order[0] == self.unroutable {
+                return Err(eyre!("net {} can't be routed", self.unroutable));
+            }
+            let mut res = RouteResult {
+                failed: true,
+                num_nets: self.net_order.len(),
+                failed_nets: vec![self.unroutable],
+                ..RouteResult::default()
+            };
+            for &net_id in self.net_order.iter().filter(|&&id| id != self.unroutable) {
+                res.wires.push(wire(net_id, 0, &[pt(0.0, 0.0), pt(1.0, 0.0)]));
+            }
+            Ok(res)
+        }
+    }
+
+    #[test]
+    fn verify_connectivity_reports_gap() {
+        let (pcb, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        res.wires.push(wire(net_id, 0, &[pt(2.0, 5.0), pt(4.5, 5.0)]));
+        res.wires.push(wire(net_id, 0, &[pt(5.5, 5.0), pt(8.0, 5.0)]));
+        assert_eq!(res.verify_connectivity(&pcb), vec![net_id]);
+
+        res.wires.push(wire(net_id, 0, &[pt(4.5, 5.0), pt(5.5, 5.0)]));
+        assert!(res.verify_connectivity(&pcb).is_empty());
+    }
+
+    #[test]
+    fn verify_connectivity_needs_shared_layer() {
+        let (pcb, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        res.wires.push(wire(net_id, 0, &[pt(2.0, 5.0), pt(5.0, 5.0)]));
+        res.wires.push(wire(net_id, 1, &[pt(5.0, 5.0), pt(8.0, 5.0)]));
+        assert_eq!(res.verify_connectivity(&pcb), vec![net_id]);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let (pcb, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        res.wires.push(wire(net_id, 0, &[pt(2.0, 5.0), pt(5.0, 5.0)]));
+        res.wires.push(wire(net_id, 1, &[pt(5.0, 5.0), pt(8.0, 5.0)]));
+        let padstack = pcb.via_padstacks()[0].clone();
+        res.vias.push(Via { p: pt(5.0, 5.0), padstack, net_id });
+        res.debug_rts.push(rt(1.0, 1.0, 2.0, 2.0));
+        res.failed = true;
+        res.num_nets = 1;
+        res.failed_nets.push(net_id);
+
+        let path = std::env::temp_dir().join(format!("route-{}.json", std::process::id()));
+        res.save(&path).unwrap();
+        let loaded = RouteResult::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), serde_json::to_string(&res).unwrap());
+        assert_eq!(loaded.wires.len(), 2);
+        assert!(loaded.failed);
+    }
+
+    #[test]
+    fn simplify_joins_segments() {
+        let (_, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        // Out of order and reversed, as the router can emit them.
+        res.wires.push(wire(net_id, 0, &[pt(2.0, 5.0), pt(1.0, 5.0)]));
+        res.wires.push(wire(net_id, 0, &[pt(0.0, 5.0), pt(1.0, 5.0)]));
+        res.wires.push(wire(net_id, 0, &[pt(2.0, 5.0), pt(3.0, 5.0)]));
+        res.simplify();
+        assert_eq!(res.wires.len(), 1);
+        let Shape::Path(p) = &res.wires[0].shape.shape else { panic!("expected a path") };
+        assert_eq!(p.pts().len(), 2);
+        let ends = [p.pts()[0], p.pts()[1]];
+        assert!(ends.iter().any(|&v| pt_eq(v, pt(0.0, 5.0))));
+        assert!(ends.iter().any(|&v| pt_eq(v, pt(3.0, 5.0))));
+        assert!(f64_eq(res.wire_length(), 3.0));
+    }
+
+    #[test]
+    fn simplify_keeps_layers_and_bends_apart() {
+        let (_, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        res.wires.push(wire(net_id, 0, &[pt(0.0, 5.0), pt(1.0, 5.0)]));
+        res.wires.push(wire(net_id, 1, &[pt(1.0, 5.0), pt(2.0, 5.0)]));
+        res.wires.push(wire(net_id, 0, &[pt(1.0, 5.0), pt(1.0, 6.0)]));
+        res.simplify();
+        assert_eq!(res.wires.iter().filter(|w| w.shape.layers.contains(1)).count(), 1);
+        // Wires meeting at a corner aren't straight, so stay separate.
+        assert_eq!(res.wires.iter().filter(|w| w.shape.layers.contains(0)).count(), 2);
+    }
+
+    #[test]
+    fn simplify_collapses_unit_steps() {
+        let (_, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        // Unit steps along a slanted line, some with repeated points, and a
+        // zero length wire sitting on the line.
+        let p = |k: f64| pt(1.0 + 0.8 * k, 2.0 + 0.6 * k);
+        for k in 0..8 {
+            let (a, b) = (p(k as f64), p(k as f64 + 1.0));
+            let pts = if k == 3 { vec![a, a, b] } else { vec![a, b] };
+            res.wires.push(wire(net_id, 0, &pts));
+        }
+        res.wires.push(wire(net_id, 0, &[p(3.0), p(3.0)]));
+        res.simplify();
+        assert_eq!(res.wires.len(), 1);
+        let Shape::Path(path) = &res.wires[0].shape.shape else { panic!("expected a path") };
+        let ends = [path.pts()[0], path.pts()[path.pts().len() - 1]];
+        assert_eq!(path.pts().len(), 2, "{:?}", path.pts());
+        assert!(ends.iter().any(|&v| pt_eq(v, p(0.0))));
+        assert!(ends.iter().any(|&v| pt_eq(v, p(8.0))));
+        assert!(f64_eq(res.wire_length(), 8.0));
+    }
+
+    #[test]
+    fn simplify_keeps_lone_dots() {
+        let (_, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        res.wires.push(wire(net_id, 0, &[pt(0.0, 5.0), pt(1.0, 5.0)]));
+        // Off the wire, and on another layer.
+        res.wires.push(wire(net_id, 0, &[pt(3.0, 5.0), pt(3.0, 5.0)]));
+        res.wires.push(wire(net_id, 1, &[pt(0.5, 5.0), pt(0.5, 5.0)]));
+        res.simplify();
+        assert_eq!(res.wires.len(), 3);
+    }
+
+    #[test]
+    fn fillet_keeps_ends_and_radius() {
+        let (mut pcb, n1) = two_pins();
+        add_component(&mut pcb, "U3", pt(8.0, 8.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U4", pt(9.0, 9.0), &[pt(0.0, 0.0)]);
+        let n2 = add_net(&mut pcb, "N2", &[("U3", "1"), ("U4", "1")]);
+        let corner = wire(n1, 0, &[pt(1.0, 1.0), pt(5.0, 1.0), pt(5.0, 5.0)]);
+        let mut res = RouteResult { wires: vec![corner.clone()], ..RouteResult::default() };
+        res.fillet_corners(&pcb, 1.0);
+        let Shape::Path(p) = &res.wires[0].shape.shape else { panic!("expected a path") };
+        let Shape::Path(orig) = &corner.shape.shape else { panic!("expected a path") };
+        assert!(f64_eq(p.r(), orig.r()));
+        let pts = p.pts();
+        assert!(pts.len() > 4, "{pts:?}");
+        assert!(pt_eq(pts[0], pt(1.0, 1.0)));
+        assert!(pt_eq(pts[pts.len() - 1], pt(5.0, 5.0)));
+        // Everything between the ends is on the arc, centred on (4, 2).
+        for &q in &pts[1..pts.len() - 1] {
+            assert!(f64_eq(q.dist(pt(4.0, 2.0)), 1.0), "{q:?}");
+        }
+
+        // Copper of another net inside the corner keeps clearance to the
+        // corner, but not to the arc.
+        let dot = wire(n2, 0, &[pt(4.65, 1.35)]);
+        let mut res = RouteResult { wires: vec![corner.clone(), dot], ..RouteResult::default() };
+        res.fillet_corners(&pcb, 1.0);
+        assert_eq!(to_string(&res.wires[0]).unwrap(), to_string(&corner).unwrap());
+        apply_route_result(&mut pcb, &res);
+        assert!(pcb.drc().is_empty(), "{:?}", pcb.drc());
+    }
+
+    #[test]
+    fn stats_count_vias_and_length() {
+        let (pcb, net_id) = two_pins();
+        let via = Via { p: pt(5.0, 5.0), padstack: pcb.via_padstacks()[0].clone(), net_id };
+        let res = RouteResult {
+            wires: vec![
+                wire(net_id, 0, &[pt(2.0, 5.0), pt(5.0, 5.0)]),
+                wire(net_id, 1, &[pt(5.0, 5.0), pt(5.0, 6.0), pt(8.0, 6.0), pt(8.0, 5.0)]),
+            ],
+            vias: vec![via],
+            num_nets: 1,
+            ..RouteResult::default()
+        };
+        let stats = res.stats();
+        assert_eq!(stats.via_count, 1);
+        assert_eq!(stats.failed_nets, 0);
+        assert!(f64_eq(stats.completion, 1.0));
+        assert_eq!(stats.layer_length.len(), 2);
+        assert!(f64_eq(stats.layer_length[&0], 3.0));
+        assert!(f64_eq(stats.layer_length[&1], 5.0));
+        assert!(f64_eq(res.wire_length(), 8.0));
+    }
+
+    #[test]
+    fn match_lengths_pads_short_nets_within_tolerance() {
+        let (mut pcb, ids) = nets(3);
+        let rule = LengthRule { group: vec![ids[0], ids[1]], target: 10.0, tolerance: 0.05 };
+        pcb.add_length_rule(rule).unwrap();
+        let wires = ids.iter().enumerate().map(|(i, &id)| {
+            let y = 2.0 * i as f64 + 1.0;
+            wire(id, 0, &[pt(1.0, y), pt(9.0, y)])
+        });
+        let mut res = RouteResult { wires: wires.collect(), ..RouteResult::default() };
+        res.match_lengths(&pcb);
+        let lengths = res.net_lengths();
+        for id in &ids[..2] {
+            assert!((lengths[id] - 10.0).abs() <= 0.05, "{}", lengths[id]);
+        }
+        // Nets without a rule keep their length.
+        assert!(f64_eq(lengths[&ids[2]], 8.0));
+        assert!(res.verify_connectivity(&pcb).is_empty());
+        apply_route_result(&mut pcb, &res);
+        assert!(pcb.drc().is_empty(), "{:?}", pcb.drc());
+    }
+
+    #[test]
+    fn teardrops_join_pads_and_pass_drc() {
+        let (mut pcb, net_id) = two_pins();
+        let trace = wire(net_id, 0, &[pt(2.0, 5.0), pt(8.0, 5.0)]);
+        let mut res = RouteResult { wires: vec![trace], ..RouteResult::default() };
+        res.add_teardrops(&pcb, 0.5);
+        // One at each pad, as wide as the pad and reaching 0.5 mm past it.
+        assert_eq!(res.wires.len(), 3);
+        for w in &res.wires[1..] {
+            assert_eq!(w.net_id, net_id);
+            let b = w.shape.shape.bounds();
+            assert!(f64_eq(b.w(), PAD_R + 0.5), "{b:?}");
+            assert!(f64_eq(b.h(), 2.0 * PAD_R), "{b:?}");
+        }
+        apply_route_result(&mut pcb, &res);
+        assert!(pcb.drc().is_empty(), "{:?}", pcb.drc());
+    }
+
+    #[test]
+    fn routed_board_passes_drc() {
+        let (mut pcb, _) = two_pins();
+        add_component(&mut pcb, "U3", pt(5.0, 2.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U4", pt(5.0, 8.0), &[pt(0.0, 0.0)]);
+        add_net(&mut pcb, "N2", &[("U3", "1"), ("U4", "1")]);
+        let router = Router::with_seed(pcb.clone(), 1);
+        let res = router.route(router.rand_net_order()).unwrap();
+        assert!(!res.failed);
+        apply_route_result(&mut pcb, &res);
+        assert!(pcb.drc().is_empty(), "{:?}", pcb.drc());
+    }
+
+    #[test]
+    fn locked_net_keeps_its_copper() {
+        let (mut pcb, n1) = two_pins();
+        add_component(&mut pcb, "U3", pt(5.0, 2.0), &[pt(0.0, 0.0)]);
+        add_component(&mut pcb, "U4", pt(5.0, 8.0), &[pt(0.0, 0.0)]);
+        let n2 = add_net(&mut pcb, "N2", &[("U3", "1"), ("U4", "1")]);
+        // N1 is routed by hand around the top of the board, and N2 has old
+        // copper.
+        let hand = wire(n1, 0, &[pt(2.0, 5.0), pt(2.0, 9.0), pt(8.0, 9.0), pt(8.0, 5.0)]);
+        let via = Via { p: pt(2.0, 9.0), padstack: pcb.via_padstacks()[0].clone(), net_id: n1 };
+        let stale = wire(n2, 1, &[pt(1.0, 1.0), pt(1.0, 2.0)]);
+        pcb.add_wire(hand.clone());
+        pcb.add_via(via.clone());
+        pcb.add_wire(stale.clone());
+        let (hand, via, stale) =
+            (to_string(&hand).unwrap(), to_string(&via).unwrap(), to_string(&stale).unwrap());
+
+        let mut router = Router::with_seed(pcb.clone(), 1);
+        router.set_locked([n1].into());
+
+        // |route| only adds copper, so N1's is left on the board.
+        let res = router.route(router.rand_net_order()).unwrap();
+        assert!(!res.failed);
+        assert_eq!(res.locked_nets, vec![n1]);
+        assert!(res.wires.iter().all(|w| w.net_id == n2));
+        assert!(res.vias.iter().all(|v| v.net_id == n2));
+        assert!(res.wires.iter().all(|w| to_string(w).unwrap() != stale));
+        let mut added = pcb.clone();
+        apply_route_result(&mut added, &res);
+        assert!(added.drc().is_empty(), "{:?}", added.drc());
+
+        // |reroute_all| drops N2's old copper and carries N1's through.
+        let mut res = router.reroute_all(router.rand_net_order()).unwrap();
+        assert!(!res.failed);
+        assert_eq!(res.locked_nets, vec![n1]);
+        for simplified in [false, true] {
+            if simplified {
+                res.simplify();
+            }
+            let wires: Vec<_> = res.wires.iter().filter(|w| w.net_id == n1).collect();
+            assert_eq!(wires.len(), 1);
+            assert_eq!(to_string(wires[0]).unwrap(), hand);
+            let vias: Vec<_> = res.vias.iter().filter(|v| v.net_id == n1).collect();
+            assert_eq!(vias.len(), 1);
+            assert_eq!(to_string(vias[0]).unwrap(), via);
+            assert!(res.wires.iter().any(|w| w.net_id == n2));
+            assert!(res.wires.iter().all(|w| to_string(w).unwrap() != stale));
+        }
+        pcb.retain_copper(|_| false);
+        apply_route_result(&mut pcb, &res);
+        assert_eq!(pcb.wires().iter().filter(|w| w.net_id == n1).count(), 1);
+        assert!(pcb.wires().iter().all(|w| to_string(w).unwrap() != stale));
+    }
+
+    #[test]
+    fn ga_stops_at_timeout() {
+        let (pcb, ids) = nets(5);
+        let mut router = Router::with_seed(pcb, 1);
+        router.set_strategy_factory(|_, net_order| {
+            Box::new(FakeStrategy { net_order, delay: Duration::from_millis(10) })
+        });
+        router.set_timeout(Some(Duration::from_millis(50)));
+        let params = GaParams { generations: Generations::Fixed(1000), pop_size: 8, seed: Some(1) };
+        let start = Instant::now();
+        let res = router.run_ga(&params).unwrap();
+        // All 1000 generations would take at least 10 s.
+        assert!(start.elapsed() < Duration::from_secs(5), "{:?}", start.elapsed());
+        assert!(res.timed_out);
+        // The best order is still routed in full.
+        let mut routed: Vec<_> = res.wires.iter().map(|w| w.net_id).collect();
+        routed.sort_unstable();
+        assert_eq!(routed, ids);
+    }
+
+    // Counts pairs of wires not in descending net id order, so the heuristic
+    // order, ascending on a board of identical nets, is the worst there is.
+    #[derive(Debug)]
+    struct InversionCost;
+
+    impl CostModel for InversionCost {
+        fn cost(&self, res: &RouteResult) -> f64 {
+            let ids: Vec<_> = res.wires.iter().map(|w| w.net_id).collect();
+            let pairs = (0..ids.len()).flat_map(|i| (i + 1..ids.len()).map(move |j| (i, j)));
+            pairs.filter(|&(i, j)| ids[i] < ids[j]).count() as f64
+        }
+    }
+
+    // Counts pairs of wires not in ascending net id order: the same as
+    // |InversionCost| with the wires reversed.
+    #[derive(Debug)]
+    struct AscendingCost;
+
+    impl CostModel for AscendingCost {
+        fn cost(&self, res: &RouteResult) -> f64 {
+            let wires = res.wires.iter().rev().cloned().collect();
+            InversionCost.cost(&RouteResult { wires, ..RouteResult::default() })
+        }
+    }
+
+    #[test]
+    fn cost_model_decides_ga_ranking() {
+        let ga = |cost_model: &dyn Fn(&mut Router)| {
+            let (pcb, _) = nets(5);
+            let mut router = Router::with_seed(pcb, 1);
+            router.set_strategy_factory(|_, net_order| {
+                Box::new(FakeStrategy { net_order, delay: Duration::ZERO })
+            });
+            cost_model(&mut router);
+            let params =
+                GaParams { generations: Generations::Fixed(4), pop_size: 8, seed: Some(1) };
+            router.run_ga(&params).unwrap()
+        };
+        let desc = ga(&|r| r.set_cost_model(InversionCost));
+        let asc = ga(&|r| r.set_cost_model(AscendingCost));
+        // The heuristic order is ascending, so it wins under |AscendingCost|
+        // from the start, and loses to anything under |InversionCost|.
+        assert!(f64_eq(AscendingCost.cost(&asc), 0.0));
+        assert!(InversionCost.cost(&desc) < InversionCost.cost(&asc));
+        assert!(AscendingCost.cost(&asc) < AscendingCost.cost(&desc));
+    }
+
+    #[test]
+    fn more_generations_are_never_worse() {
+        let (pcb, _) = nets(5);
+        let mut router = Router::with_seed(pcb, 1);
+        router.set_strategy_factory(|_, net_order| {
+            Box::new(FakeStrategy { net_order, delay: Duration::ZERO })
+        });
+        router.set_cost_model(InversionCost);
+        let cost = |generations| {
+            let params = GaParams { generations, pop_size: 8, seed: Some(1) };
+            InversionCost.cost(&router.run_ga(&params).unwrap())
+        };
+        let fixed = [1, 2, 4, 8].map(|n| cost(Generations::Fixed(n)));
+        assert!(fixed.windows(2).all(|v| v[1] <= v[0]), "{fixed:?}");
+        assert!(fixed[3] < 10.0, "{fixed:?}");
+        let stagnant = cost(Generations::Stagnant { patience: 2, max: 8 });
+        assert!(stagnant <= fixed[0], "{stagnant} {fixed:?}");
+    }
+
+    #[test]
+    fn heuristic_orders_by_area_and_pin_count() {
+        let mut pcb = board(10.0, 10.0);
+        let pins =
+            [(1.0, 1.0), (7.0, 7.0), (1.0, 8.0), (4.0, 8.0), (4.0, 9.0), (8.0, 1.0), (9.0, 2.0)];
+        add_component(&mut pcb, "U1", pt(0.0, 0.0), &pins.map(|(x, y)| pt(x, y)));
+        // A long diagonal, three pins close together, and two pins closer still.
+        let n1 = add_net(&mut pcb, "N1", &[("U1", "1"), ("U1", "2")]);
+        let n2 = add_net(&mut pcb, "N2", &[("U1", "3"), ("U1", "4"), ("U1", "5")]);
+        let n3 = add_net(&mut pcb, "N3", &[("U1", "6"), ("U1", "7")]);
+        let router = Router::new(pcb);
+        assert_eq!(router.heuristic_net_order(NetOrderHeuristic::BoundsArea), vec![n3, n2, n1]);
+        // N1 and N3 tie on pin count, so they stay in id order.
+        assert_eq!(router.heuristic_net_order(NetOrderHeuristic::PinCount), vec![n1, n3, n2]);
+    }
+
+    #[test]
+    fn shorter_route_is_fitter() {
+        let (pcb, net_id) = two_pins();
+        let direct = RouteResult {
+            wires: vec![wire(net_id, 0, &[pt(2.0, 5.0), pt(8.0, 5.0)])],
+            ..RouteResult::default()
+        };
+        let detour = RouteResult {
+            wires: vec![wire(net_id, 0, &[pt(2.0, 5.0), pt(2.0, 7.0), pt(8.0, 7.0), pt(8.0, 5.0)])],
+            ..RouteResult::default()
+        };
+        let model = RouteCost::default();
+        assert!(f64_eq(model.cost(&direct), 6.0 * model.length_cost));
+        assert!(model.cost(&direct) < model.cost(&detour));
+        // A via or a failure costs more than the same route without.
+        let via = Via { p: pt(5.0, 5.0), padstack: pcb.via_padstacks()[0].clone(), net_id };
+        let with_via = RouteResult { vias: vec![via], ..direct.clone() };
+        let failed = RouteResult { failed: true, ..direct.clone() };
+        assert!(model.cost(&direct) < model.cost(&with_via));
+        assert!(f64_eq(model.cost(&failed) - model.cost(&direct), model.failure_cost));
+        // The router's default model is a |RouteCost| like this one.
+        let router = Router::new(pcb);
+        assert!(f64_eq(router.cost(&detour), model.cost(&detour)));
+    }
+
+    #[test]
+    fn via_cost_keeps_custom_cost_model() {
+        let (pcb, net_id) = two_pins();
+        let mut res = RouteResult::default();
+        res.vias.push(Via { p: pt(5.0, 5.0), padstack: pcb.via_padstacks()[0].clone(), net_id });
+        let mut router = Router::new(pcb);
+        router.set_via_cost(ViaCostModel::uniform(3.0));
+        assert!(f64_eq(router.cost(&res), 3.0));
+        // There are no wires out of order, so this model costs nothing.
+        router.set_cost_model(InversionCost);
+        router.set_via_cost(ViaCostModel::uniform(5.0));
+        assert!(f64_eq(router.cost(&res), 0.0));
+    }
+
+    #[test]
+    fn fitness_cache_keeps_one_generation_back() {
+        let order = |v: Id| RouteState(vec![v]);
+        let mut cache = FitnessCache::default();
+        cache.insert(order(1), 0.5);
+        cache.insert(order(2), 0.25);
+        cache.next_generation();
+        // A survivor looked up again is carried into the new generation.
+        assert_eq!(cache.get(&order(1)), Some(0.5));
+        cache.insert(order(3), 0.75);
+        cache.next_generation();
+        assert_eq!(cache.get(&order(1)), Some(0.5));
+        assert_eq!(cache.get(&order(3)), Some(0.75));
+        // Not seen for a whole generation.
+        assert_eq!(cache.get(&order(2)), None);
+        assert_eq!(cache.len(), 2);
+    }
+    #[test]
+    fn mutate_is_seeded() {
+        let mutated = |seed: u64| {
+            let router = Router::with_seed(nets(8).0, seed);
+            let mut s = RouteState((0..8).collect());
+            let mut seen = Vec::new();
+            for idx in 0..4 {
+                for _ in 0..5 {
+                    router.mutate(&mut s, 1.0, idx);
+                    seen.push(s.clone());
+                }
+            }
+            seen
+        };
+        assert_eq!(mutated(3), mutated(3));
+        assert_ne!(mutated(3), mutated(4));
+    }
+
+    #[test]
+    fn ga_completes_when_a_net_fails() {
+        let (pcb, ids) = nets(3);
+        let mut router = Router::with_seed(pcb, 1);
+        let unroutable = ids[0];
+        router.set_strategy_factory(move |_, net_order| {
+            Box::new(OneFailsStrategy { net_order, unroutable })
+        });
+        let params = GaParams { generations: Generations::Fixed(2), pop_size: 8, seed: Some(1) };
+        // Orders that error score nothing, so the best order is one that
+        // routes the other nets.
+        let res = router.run_ga(&params).unwrap();
+        assert!(res.failed);
+        assert_eq!(res.failed_nets, vec![unroutable]);
+        assert_eq!(res.wires.len(), 2);
+    }
+
+    #[test]
+    fn ga_routes_each_order_once() {
+        let (pcb, _) = nets(5);
+        let mut router = Router::with_seed(pcb, 1);
+        let routed = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&routed);
+        router.set_strategy_factory(move |_, net_order| {
+            log.lock().unwrap().push(net_order.clone());
+            Box::new(FakeStrategy { net_order, delay: Duration::ZERO })
+        });
+        router.set_cost_model(InversionCost);
+        let params = GaParams { generations: Generations::Fixed(2), pop_size: 8, seed: Some(1) };
+        router.run_ga(&params).unwrap();
+        // The cache remembers the previous generation, so over two
+        // generations nothing is routed twice, apart from the final route of
+        // the best order.
+        let mut routed = routed.lock().unwrap().clone();
+        let best = routed.pop().unwrap();
+        assert!(routed.contains(&best));
+        let distinct: HashSet<_> = routed.iter().collect();
+        assert_eq!(distinct.len(), routed.len(), "{routed:?}");
+    }
+}
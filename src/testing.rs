@@ -0,0 +1,152 @@
+// Small boards built in code, shared by tests.
+use eyre::Result;
+use itertools::Itertools;
+use memedsn::lexer::Lexer;
+use memedsn::parser::Parser;
+use memegeom::primitive::point::Pt;
+use memegeom::primitive::{circ, path, poly, pt, ShapeOps};
+use strum::IntoEnumIterator;
+
+use crate::dsn::design_to_pcb::DesignToPcb;
+use crate::dsn::sexp;
+use crate::model::pcb::{
+    Clearance, Component, Layer, LayerId, LayerKind, LayerSet, LayerShape, Net, ObjectKind,
+    Padstack, Pcb, Pin, PinRef, Rule, RuleSet, Wire,
+};
+use crate::name::Id;
+
+// Half-width of wires on test boards.
+pub(crate) const RADIUS: f64 = 0.1;
+pub(crate) const CLEARANCE: f64 = 0.1;
+// Radius of round pads and vias.
+pub(crate) const PAD_R: f64 = 0.3;
+
+// Units for |dsn| designs: coordinates in um.
+pub(crate) const UM: &str = "(resolution um 10) (unit um)";
+
+// Empty |w| by |h| board with signal layers F.Cu (0) and B.Cu (1), a default
+// net class using |RADIUS| and |CLEARANCE|, and a through-hole via.
+pub(crate) fn board(w: f64, h: f64) -> Pcb {
+    outline_board(&rect_outline(w, h))
+}
+
+// Like |board|, but with no via padstacks.
+pub(crate) fn bare_board(w: f64, h: f64) -> Pcb {
+    bare_outline_board(&rect_outline(w, h))
+}
+
+// Like |board|, but with the polygon |outline| as the boundary.
+pub(crate) fn outline_board(outline: &[Pt]) -> Pcb {
+    let mut pcb = bare_outline_board(outline);
+    let all = pcb.layers_by_kind(LayerKind::All);
+    let via = Padstack { id: pcb.to_id("via"), shapes: vec![round(all)], attach: false };
+    pcb.add_via_padstack(via);
+    pcb
+}
+
+fn rect_outline(w: f64, h: f64) -> [Pt; 4] {
+    [pt(0.0, 0.0), pt(w, 0.0), pt(w, h), pt(0.0, h)]
+}
+
+fn bare_outline_board(outline: &[Pt]) -> Pcb {
+    let mut pcb = Pcb::default();
+    pcb.set_pcb_name("test");
+    for (layer_id, name) in ["F.Cu", "B.Cu"].into_iter().enumerate() {
+        let name_id = pcb.to_id(name);
+        pcb.add_layer(Layer { name_id, layer_id, kind: LayerKind::Signal });
+    }
+    let all = pcb.layers_by_kind(LayerKind::All);
+    pcb.add_boundary(LayerShape { layers: all, shape: poly(outline).shape() });
+
+    let pairs: Vec<_> = ObjectKind::iter().cartesian_product(ObjectKind::iter()).collect();
+    let id = pcb.to_id("default");
+    let rules = vec![Rule::Radius(RADIUS), Rule::Clearance(Clearance::new(CLEARANCE, &pairs))];
+    pcb.add_ruleset(RuleSet::new(id, rules).unwrap());
+    pcb.set_default_net_ruleset(id);
+    pcb
+}
+
+// Round pad of radius |PAD_R| on |layers|.
+pub(crate) fn round(layers: LayerSet) -> LayerShape {
+    LayerShape { layers, shape: circ(Pt::zero(), PAD_R).shape() }
+}
+
+// Adds component |name| at |p| with through-hole round pins "1", "2", ... at
+// the given offsets. Returns its id.
+pub(crate) fn add_component(pcb: &mut Pcb, name: &str, p: Pt, pins: &[Pt]) -> Id {
+    let all = pcb.layers_by_kind(LayerKind::All);
+    add_smd_component(pcb, name, p, pins, all)
+}
+
+// Like |add_component|, but with pads only on |layers|.
+pub(crate) fn add_smd_component(
+    pcb: &mut Pcb,
+    name: &str,
+    p: Pt,
+    pins: &[Pt],
+    layers: LayerSet,
+) -> Id {
+    let mut c = Component::default();
+    c.id = pcb.to_id(name);
+    c.footprint_id = pcb.to_id("part");
+    c.p = p;
+    let padstack = Padstack { id: pcb.to_id("pad"), shapes: vec![round(layers)], attach: false };
+    for (i, &p) in pins.iter().enumerate() {
+        let id = pcb.to_id(&(i + 1).to_string());
+        c.add_pin(Pin { id, padstack: padstack.clone(), rotation: 0.0, p });
+    }
+    let id = c.id;
+    pcb.add_component(c);
+    id
+}
+
+// Adds net |name| connecting the given (component, pin) names. Returns its id.
+pub(crate) fn add_net(pcb: &mut Pcb, name: &str, pins: &[(&str, &str)]) -> Id {
+    let id = pcb.to_id(name);
+    let pins = pins.iter().map(|(c, p)| PinRef { component: pcb.to_id(c), pin: pcb.to_id(p) });
+    let pins = pins.collect();
+    pcb.add_net(Net { id, pins });
+    id
+}
+
+// Path wire |RADIUS| wide through |pts| on |layer|.
+pub(crate) fn wire(net_id: Id, layer: LayerId, pts: &[Pt]) -> Wire {
+    let shape = LayerShape { layers: LayerSet::one(layer), shape: path(pts, RADIUS).shape() };
+    Wire { shape, net_id }
+}
+
+// Specctra design with two signal layers, a 10 mm square boundary if the
+// units are |UM|, and a board wide 200 unit width and clearance. |structure|
+// is added to the structure section, and |body| follows it, so it should
+// have the placement, library, network, and wiring sections.
+pub(crate) fn dsn(units: &str, structure: &str, body: &str) -> String {
+    format!(
+        r#"(pcb test.dsn
+  (parser
+    (string_quote ")
+    (space_in_quoted_tokens on)
+    (host_cad "KiCad's Pcbnew")
+    (host_version "6.0")
+  )
+  {units}
+  (structure
+    (layer F.Cu (type signal) (property (index 0)))
+    (layer B.Cu (type signal) (property (index 1)))
+    (boundary (path pcb 0  0 0  10000 0  10000 10000  0 10000  0 0))
+    {structure}
+    (rule (width 200) (clearance 200))
+  )
+  {body}
+)
+"#
+    )
+}
+
+// Parses and converts a Specctra design, like |headless::load_pcb| does for
+// files.
+pub(crate) fn load_dsn(data: &str) -> Result<Pcb> {
+    sexp::parse(data)?;
+    let tokens = Lexer::new(data)?.lex()?;
+    let pcb = Parser::new(&tokens).parse()?;
+    Ok(DesignToPcb::new(pcb).convert()?)
+}